@@ -0,0 +1,274 @@
+//! Embeds the files in `static/` and `migrations/` into the binary.
+//!
+//! For each static file, this generates a content hash, a hashed file name,
+//! and (for compressible files) gzip and Brotli variants, so that none of this
+//! work has to be done while serving requests.
+//!
+//! For each migration, this generates a checksum of its up SQL, so that
+//! migrations that are edited after being applied can be detected.
+
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+use flate2::{write::GzEncoder, Compression};
+use sha2::{Digest, Sha256};
+
+/// The directory containing the static assets, relative to the crate root.
+const STATIC_DIR: &str = "static";
+
+/// The directory containing the migrations, relative to the crate root.
+const MIGRATIONS_DIR: &str = "migrations";
+
+/// The number of hex digits of the content hash used in file names.
+const HASH_LENGTH: usize = 16;
+
+/// Returns the `Content-Type` of a file with the extension `extension`, and
+/// whether files of that type are worth compressing.
+fn content_type(extension: &str) -> (&'static str, bool) {
+    match extension {
+        "css" => ("text/css; charset=utf-8", true),
+        "js" => ("text/javascript; charset=utf-8", true),
+        "json" => ("application/json", true),
+        "webmanifest" => ("application/manifest+json", true),
+        "html" => ("text/html; charset=utf-8", true),
+        "txt" => ("text/plain; charset=utf-8", true),
+        "svg" => ("image/svg+xml", true),
+        "ico" => ("image/x-icon", true),
+        "png" => ("image/png", false),
+        "jpg" | "jpeg" => ("image/jpeg", false),
+        "webp" => ("image/webp", false),
+        "woff2" => ("font/woff2", false),
+        _ => ("application/octet-stream", false),
+    }
+}
+
+/// Returns the paths of all files under `dir`, recursively, in sorted order.
+fn list_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = vec![];
+    let Ok(entries) = fs::read_dir(dir) else {
+        return files;
+    };
+    for entry in entries {
+        let path = entry.expect("Failed to read directory").path();
+        if path.is_dir() {
+            files.extend(list_files(&path));
+        } else {
+            files.push(path);
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Returns the SHA-256 hash of `data` as lowercase hex.
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .fold(String::new(), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
+
+/// Compresses `data` with gzip at the best compression level.
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(vec![], Compression::best());
+    encoder
+        .write_all(data)
+        .expect("Failed to gzip static asset");
+    encoder.finish().expect("Failed to gzip static asset")
+}
+
+/// Compresses `data` with Brotli at the best quality.
+fn brotli(data: &[u8]) -> Vec<u8> {
+    let mut output = vec![];
+    {
+        let mut encoder =
+            brotli::CompressorWriter::new(&mut output, 4096, 11, 22);
+        encoder
+            .write_all(data)
+            .expect("Failed to compress static asset with Brotli");
+    }
+    output
+}
+
+/// Writes `data` to `name` in `out_dir` and returns an expression that
+/// includes it.
+fn write_variant(out_dir: &Path, name: &str, data: &[u8]) -> String {
+    let path = out_dir.join(name);
+    fs::write(&path, data).expect("Failed to write static asset");
+    format!("include_bytes!({:?})", path.display().to_string())
+}
+
+/// Writes a compressed variant if it is smaller than `original`, and returns
+/// an expression for it (or `None`).
+fn compressed_variant(
+    out_dir: &Path,
+    name: &str,
+    original: &[u8],
+    compressed: &[u8],
+) -> String {
+    if compressed.len() < original.len() {
+        format!("Some({})", write_variant(out_dir, name, compressed))
+    } else {
+        "None".to_owned()
+    }
+}
+
+/// Embeds the files in `static/`, writing the index of assets to
+/// `static_assets.rs` in `out_dir`.
+fn embed_static_assets(out_dir: &Path) {
+    println!("cargo:rerun-if-changed={STATIC_DIR}");
+
+    let asset_dir = out_dir.join("static");
+    fs::create_dir_all(&asset_dir).expect("Failed to create asset directory");
+
+    let mut code = String::from("&[\n");
+    for file in list_files(Path::new(STATIC_DIR)) {
+        println!("cargo:rerun-if-changed={}", file.display());
+
+        let data = fs::read(&file).expect("Failed to read static asset");
+        let relative_path = file
+            .strip_prefix(STATIC_DIR)
+            .expect("Static asset outside static directory")
+            .to_str()
+            .expect("Static asset path is not UTF-8")
+            .replace('\\', "/");
+
+        let hash = sha256_hex(&data);
+        let hash = &hash[..HASH_LENGTH];
+
+        let extension = file
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or("");
+        let (content_type, compressible) = content_type(extension);
+
+        // e.g. "css/style.css" -> "css/style.0123456789abcdef.css"
+        let hashed_path = match relative_path.rsplit_once('.') {
+            Some((stem, extension)) if !stem.ends_with('/') => {
+                format!("{stem}.{hash}.{extension}")
+            }
+            _ => format!("{relative_path}.{hash}"),
+        };
+
+        let variant_name = hashed_path.replace('/', "_");
+        let body = write_variant(&asset_dir, &variant_name, &data);
+        let (gzip, brotli) = if compressible {
+            (
+                compressed_variant(
+                    &asset_dir,
+                    &format!("{variant_name}.gz"),
+                    &data,
+                    &gzip(&data),
+                ),
+                compressed_variant(
+                    &asset_dir,
+                    &format!("{variant_name}.br"),
+                    &data,
+                    &brotli(&data),
+                ),
+            )
+        } else {
+            ("None".to_owned(), "None".to_owned())
+        };
+
+        let _ = writeln!(
+            code,
+            "    Asset {{\n        \
+             path: {relative_path:?},\n        \
+             hashed_path: {hashed_path:?},\n        \
+             content_type: {content_type:?},\n        \
+             hash: {hash:?},\n        \
+             body: {body},\n        \
+             gzip: {gzip},\n        \
+             brotli: {brotli},\n    \
+             }},"
+        );
+    }
+    code.push(']');
+
+    fs::write(out_dir.join("static_assets.rs"), code)
+        .expect("Failed to write static asset index");
+}
+
+/// Embeds the migrations in `migrations/`, writing the list of migrations to
+/// `migrations.rs` in `out_dir`.
+///
+/// Migrations are named `<version>_<description>.up.sql`, with an optional
+/// `<version>_<description>.down.sql` for rolling back. Versions must be
+/// numbered consecutively from 1.
+fn embed_migrations(out_dir: &Path) {
+    println!("cargo:rerun-if-changed={MIGRATIONS_DIR}");
+
+    let manifest_dir =
+        PathBuf::from(env::var("CARGO_MANIFEST_DIR").expect("No manifest dir"));
+
+    let mut code = String::from("&[\n");
+    let mut expected_version = 1;
+    for file in list_files(Path::new(MIGRATIONS_DIR)) {
+        println!("cargo:rerun-if-changed={}", file.display());
+
+        let name = file
+            .file_name()
+            .and_then(|name| name.to_str())
+            .expect("Migration file name is not UTF-8");
+        if name.ends_with(".down.sql") {
+            continue;
+        }
+        let Some(stem) = name.strip_suffix(".up.sql") else {
+            panic!("Migration {name} must end with .up.sql or .down.sql");
+        };
+        let (version, description) = stem
+            .split_once('_')
+            .and_then(|(version, description)| {
+                Some((version.parse::<i64>().ok()?, description))
+            })
+            .unwrap_or_else(|| {
+                panic!("Migration {name} must be named <version>_<name>")
+            });
+        assert_eq!(
+            version, expected_version,
+            "Migration {name} should have version {expected_version}"
+        );
+        expected_version += 1;
+
+        let up = fs::read(&file).expect("Failed to read migration");
+        let down_file = file.with_file_name(format!("{stem}.down.sql"));
+        let down = if down_file.exists() {
+            println!("cargo:rerun-if-changed={}", down_file.display());
+            format!(
+                "Some(include_str!({:?}))",
+                manifest_dir.join(&down_file).display().to_string()
+            )
+        } else {
+            "None".to_owned()
+        };
+
+        let _ = writeln!(
+            code,
+            "    Migration {{\n        \
+             version: {version},\n        \
+             description: {description:?},\n        \
+             up: include_str!({up_path:?}),\n        \
+             down: {down},\n        \
+             checksum: {checksum:?},\n    \
+             }},",
+            description = description.replace('_', " "),
+            up_path = manifest_dir.join(&file).display().to_string(),
+            checksum = sha256_hex(&up),
+        );
+    }
+    code.push(']');
+
+    fs::write(out_dir.join("migrations.rs"), code)
+        .expect("Failed to write migration list");
+}
+
+fn main() {
+    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR not set"));
+    embed_static_assets(&out_dir);
+    embed_migrations(&out_dir);
+}
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS recipes_ingredients_by_ingredient;
+
+DROP INDEX IF EXISTS recipes_categories_by_category;
@@ -0,0 +1,5 @@
+CREATE INDEX IF NOT EXISTS recipes_ingredients_by_ingredient
+  ON recipes_ingredients (ingredient_id);
+
+CREATE INDEX IF NOT EXISTS recipes_categories_by_category
+  ON recipes_categories (category_id);
@@ -1,5 +1,3 @@
-BEGIN;
-
 CREATE TABLE IF NOT EXISTS db_version (
   version         INTEGER PRIMARY KEY NOT NULL
 );
@@ -55,5 +53,3 @@ CREATE TABLE IF NOT EXISTS recipes_categories (
   category_id     INTEGER NOT NULL,
   PRIMARY KEY (recipe_id, category_id)
 );
-
-COMMIT;
@@ -1,7 +1,7 @@
-INSERT INTO recipes VALUES (314, "Cherry cheese pie", false);
-INSERT INTO ingredients VALUES(628, "Cherry", 6.28);
+INSERT INTO recipes VALUES (314, 'Cherry cheese pie', false);
+INSERT INTO ingredients VALUES(628, 'Cherry', 6.28);
 INSERT INTO recipes_versions VALUES(314, 0, 1688701914, 300);
 INSERT INTO recipes_ingredients VALUES(314, 0, 628, 0, 1, 2);
-INSERT INTO recipes_instructions VALUES(314, 0, 1, "Make the crust");
-INSERT INTO categories VALUES(2718, "Desserts");
+INSERT INTO recipes_instructions VALUES(314, 0, 1, 'Make the crust');
+INSERT INTO categories VALUES(2718, 'Desserts');
 INSERT INTO recipes_categories VALUES(314, 2718);
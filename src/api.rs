@@ -2,4 +2,5 @@ mod constants;
 mod routes;
 mod utils;
 
-pub use routes::create_router;
+pub use constants::LISTING_LIMIT;
+pub use routes::{create_archive_router, create_router};
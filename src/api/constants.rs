@@ -9,3 +9,7 @@ pub const LISTING_LIMIT: i64 = 1024;
 /// This can be overriden on certain endpoints, but the page size cannot exceed
 /// `LISTING_LIMIT`.
 pub const DEFAULT_PAGE_SIZE: u64 = 128;
+
+/// The maximum size (in bytes) of a file that can be imported in a single
+/// request.
+pub const IMPORT_SIZE_LIMIT: usize = 16 * 1024 * 1024;
@@ -1,17 +1,30 @@
+mod archive;
 mod categories;
+mod cooking;
 mod ingredients;
+mod parse;
 mod recipes;
 
 use std::sync::Arc;
 
+pub use archive::create_router as create_archive_router;
 use axum::Router;
 
+use crate::cooking::CookingSessions;
 use crate::database::Database;
 
 /// Creates a router that handles all API requests.
-pub fn create_router(database: Arc<Database>) -> Router {
+pub fn create_router(
+    database: Arc<Database>,
+    cooking_sessions: Arc<CookingSessions>,
+) -> Router {
     Router::new()
         .nest("/categories", categories::create_router(database.clone()))
+        .nest(
+            "/cooking",
+            cooking::create_router(database.clone(), cooking_sessions),
+        )
         .nest("/ingredients", ingredients::create_router(database.clone()))
+        .nest("/parse", parse::create_router(database.clone()))
         .nest("/recipes", recipes::create_router(database))
 }
@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{DefaultBodyLimit, State},
+    http::header,
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use log::debug;
+
+use crate::api::constants::IMPORT_SIZE_LIMIT;
+use crate::api::utils::Error;
+use crate::database::{Archive, Database, ImportSummary};
+
+/// Exports the entire database, including hidden recipes, as a JSON archive.
+async fn export_archive(
+    State(database): State<Arc<Database>>,
+) -> Result<impl IntoResponse, Error> {
+    debug!("Exporting database archive");
+
+    let schema_version = database.get_version();
+    let archive = database
+        .with_transaction(move |transaction| {
+            Box::pin(async move {
+                Archive::export(transaction, schema_version).await
+            })
+        })
+        .await
+        .map_err(Error::from_db)?;
+
+    let file_name =
+        format!("recipes-{}.json", archive.exported.format("%Y-%m-%d"));
+
+    Ok((
+        [(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{file_name}\""),
+        )],
+        Json(archive),
+    ))
+}
+
+/// Imports a JSON archive produced by `export_archive`.
+///
+/// The whole archive is imported in a single transaction, so nothing is
+/// imported if any part of it is invalid.
+async fn import_archive(
+    State(database): State<Arc<Database>>,
+    Json(archive): Json<Archive>,
+) -> Result<Json<ImportSummary>, Error> {
+    debug!("Importing database archive");
+
+    let schema_version = database.get_version();
+    Ok(Json(
+        database
+            .with_transaction(move |transaction| {
+                Box::pin(async move {
+                    archive.import(transaction, schema_version).await
+                })
+            })
+            .await
+            .map_err(Error::from_db)?,
+    ))
+}
+
+/// Creates a router that serves routes for exporting and importing the whole
+/// database.
+pub fn create_router<S>(database: Arc<Database>) -> Router<S> {
+    Router::new()
+        .route(
+            "/",
+            get(export_archive)
+                .post(import_archive)
+                .layer(DefaultBodyLimit::max(IMPORT_SIZE_LIMIT)),
+        )
+        .with_state(database)
+}
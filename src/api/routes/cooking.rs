@@ -0,0 +1,242 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{delete, get, post},
+    Json, Router,
+};
+use chrono::{offset::Utc, DateTime, Duration};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::api::utils::Error;
+use crate::cooking::{
+    find_timers, CookingSession, CookingSessions, SuggestedTimer,
+    MAX_TIMER_LABEL_LENGTH, MAX_TIMER_SECONDS,
+};
+use crate::database::Database;
+use crate::formats::ParsedRecipe;
+use crate::models::RecipeVersionID;
+
+/// The state shared by cooking routes.
+#[derive(Clone)]
+struct CookingState {
+    database: Arc<Database>,
+    sessions: Arc<CookingSessions>,
+}
+
+/// An instruction, along with the timers suggested by its text.
+#[derive(Serialize)]
+struct CookingStep {
+    text: String,
+    timers: Vec<SuggestedTimer>,
+}
+
+/// A recipe version being cooked, and the progress of its session.
+#[derive(Serialize)]
+struct CookingView {
+    recipe_id: i64,
+    version_id: i64,
+    name: String,
+    steps: Vec<CookingStep>,
+    session: CookingSession,
+
+    /// The server's current time, so that clients can show how long is left
+    /// on each timer even if their clocks are wrong.
+    now: DateTime<Utc>,
+}
+
+/// A request to move to a different step.
+#[derive(Deserialize)]
+struct SetStepData {
+    step: usize,
+}
+
+/// A request to start a timer.
+#[derive(Deserialize)]
+struct StartTimerData {
+    step: usize,
+    label: String,
+    seconds: i64,
+}
+
+/// Loads the name and steps of the recipe version with ID `id`.
+async fn load_steps(
+    database: &Database,
+    id: RecipeVersionID,
+) -> Result<(String, Vec<CookingStep>), Error> {
+    let recipe = database
+        .with_transaction(move |transaction| {
+            Box::pin(async move { ParsedRecipe::load(transaction, id).await })
+        })
+        .await
+        .map_err(Error::from_db)?;
+
+    let steps = recipe
+        .instructions
+        .into_iter()
+        .map(|text| CookingStep {
+            timers: find_timers(&text),
+            text,
+        })
+        .collect();
+    Ok((recipe.name, steps))
+}
+
+/// Combines a recipe version's name and steps with its cooking session.
+fn cooking_view(
+    id: RecipeVersionID,
+    (name, steps): (String, Vec<CookingStep>),
+    session: CookingSession,
+) -> Json<CookingView> {
+    Json(CookingView {
+        recipe_id: id.recipe_id,
+        version_id: id.version_id,
+        name,
+        steps,
+        session,
+        now: Utc::now(),
+    })
+}
+
+/// Returns an error unless `step` is the index of one of `steps`.
+fn check_step(steps: &[CookingStep], step: usize) -> Result<(), Error> {
+    if step < steps.len() {
+        Ok(())
+    } else {
+        Err(Error::bad_request("Invalid step"))
+    }
+}
+
+/// Gets the cooking session for version `version_id` of the recipe with ID
+/// `recipe_id`. Versions that are not being cooked are shown at their first
+/// step.
+async fn get_session(
+    State(state): State<CookingState>,
+    Path((recipe_id, version_id)): Path<(i64, i64)>,
+) -> Result<Json<CookingView>, Error> {
+    debug!(
+        "Getting cooking session for recipe {recipe_id} version {version_id}"
+    );
+
+    let id = RecipeVersionID {
+        recipe_id,
+        version_id,
+    };
+    let steps = load_steps(&state.database, id).await?;
+    Ok(cooking_view(id, steps, state.sessions.get(id)))
+}
+
+/// Moves the cooking session for version `version_id` of the recipe with ID
+/// `recipe_id` to another step, starting the session if needed.
+async fn set_step(
+    State(state): State<CookingState>,
+    Path((recipe_id, version_id)): Path<(i64, i64)>,
+    Json(data): Json<SetStepData>,
+) -> Result<Json<CookingView>, Error> {
+    debug!(
+        "Moving cooking session for recipe {recipe_id} version {version_id} \
+         to step {}",
+        data.step
+    );
+
+    let id = RecipeVersionID {
+        recipe_id,
+        version_id,
+    };
+    let steps = load_steps(&state.database, id).await?;
+    check_step(&steps.1, data.step)?;
+    let session = state.sessions.set_step(id, data.step);
+    Ok(cooking_view(id, steps, session))
+}
+
+/// Ends the cooking session for version `version_id` of the recipe with ID
+/// `recipe_id`, if there is one.
+async fn end_session(
+    State(state): State<CookingState>,
+    Path((recipe_id, version_id)): Path<(i64, i64)>,
+) -> StatusCode {
+    debug!(
+        "Ending cooking session for recipe {recipe_id} version {version_id}"
+    );
+
+    state.sessions.end(RecipeVersionID {
+        recipe_id,
+        version_id,
+    });
+    StatusCode::NO_CONTENT
+}
+
+/// Starts a timer in the cooking session for version `version_id` of the
+/// recipe with ID `recipe_id`, starting the session if needed.
+async fn start_timer(
+    State(state): State<CookingState>,
+    Path((recipe_id, version_id)): Path<(i64, i64)>,
+    Json(data): Json<StartTimerData>,
+) -> Result<Json<CookingView>, Error> {
+    debug!(
+        "Starting {}-second timer for recipe {recipe_id} version {version_id}",
+        data.seconds
+    );
+
+    if !(1..=MAX_TIMER_SECONDS).contains(&data.seconds) {
+        return Err(Error::bad_request("Invalid timer length"));
+    }
+    if data.label.chars().count() > MAX_TIMER_LABEL_LENGTH {
+        return Err(Error::bad_request("Timer label is too long"));
+    }
+
+    let id = RecipeVersionID {
+        recipe_id,
+        version_id,
+    };
+    let steps = load_steps(&state.database, id).await?;
+    check_step(&steps.1, data.step)?;
+    let session = state
+        .sessions
+        .start_timer(id, data.step, data.label, Duration::seconds(data.seconds))
+        .ok_or_else(|| Error::bad_request("Too many timers"))?;
+    Ok(cooking_view(id, steps, session))
+}
+
+/// Stops or dismisses the timer with ID `timer_id` in the cooking session
+/// for version `version_id` of the recipe with ID `recipe_id`.
+async fn remove_timer(
+    State(state): State<CookingState>,
+    Path((recipe_id, version_id, timer_id)): Path<(i64, i64, u64)>,
+) -> Result<Json<CookingView>, Error> {
+    debug!(
+        "Removing timer {timer_id} for recipe {recipe_id} version {version_id}"
+    );
+
+    let id = RecipeVersionID {
+        recipe_id,
+        version_id,
+    };
+    let steps = load_steps(&state.database, id).await?;
+    let session = state
+        .sessions
+        .remove_timer(id, timer_id)
+        .ok_or_else(|| Error::bad_request("Invalid timer"))?;
+    Ok(cooking_view(id, steps, session))
+}
+
+/// Creates a router that serves routes for following recipes in cooking
+/// mode, keeping each session's progress in `sessions`.
+pub fn create_router<S>(
+    database: Arc<Database>,
+    sessions: Arc<CookingSessions>,
+) -> Router<S> {
+    Router::new()
+        .route(
+            "/:recipe_id/:version_id",
+            get(get_session).put(set_step).delete(end_session),
+        )
+        .route("/:recipe_id/:version_id/timers", post(start_timer))
+        .route(
+            "/:recipe_id/:version_id/timers/:timer_id",
+            delete(remove_timer),
+        )
+        .with_state(CookingState { database, sessions })
+}
@@ -0,0 +1,160 @@
+use std::sync::Arc;
+
+use axum::{extract::State, routing::post, Json, Router};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::api::constants::LISTING_LIMIT;
+use crate::api::utils::Error;
+use crate::database::Database;
+use crate::formats::{name_similarity, parse_ingredient_line};
+use crate::models::{Ingredient, MeasurementType, QuantifiedIngredient, Ref};
+
+/// The maximum number of existing ingredients proposed for each line.
+const MAX_MATCHES: usize = 3;
+
+/// The similarity below which an existing ingredient is not proposed.
+const MIN_SIMILARITY: f64 = 0.3;
+
+/// Represents the data needed to parse ingredient lines.
+#[derive(Deserialize)]
+struct ParseIngredientsData {
+    lines: Vec<String>,
+}
+
+/// A possible interpretation of an ingredient line.
+///
+/// Exactly one of `quantified_ingredient` (an existing ingredient) and
+/// `new_ingredient_name` (an ingredient that would need to be created) is
+/// present.
+#[derive(Serialize)]
+struct IngredientCandidate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quantified_ingredient: Option<QuantifiedIngredient>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new_ingredient_name: Option<String>,
+
+    /// How likely this interpretation is to be correct, from 0 to 1.
+    confidence: f64,
+}
+
+/// The structured form of an ingredient line.
+#[derive(Serialize)]
+struct ParsedIngredientLine {
+    name: String,
+    quantity: f64, // In SI standard units.
+    measurement: MeasurementType,
+    unit: Option<String>,
+    preparation: Option<String>,
+
+    /// Possible interpretations, from most to least likely.
+    candidates: Vec<IngredientCandidate>,
+}
+
+/// Parses natural-language ingredient lines (e.g. "2 1/2 cups all-purpose
+/// flour, sifted") and matches them against the ingredients in the database.
+///
+/// Returns one entry per line, in order. Lines that contain no ingredient are
+/// returned as `null`.
+async fn parse_ingredients(
+    State(database): State<Arc<Database>>,
+    Json(data): Json<ParseIngredientsData>,
+) -> Result<Json<Vec<Option<ParsedIngredientLine>>>, Error> {
+    debug!("Parsing {} ingredient lines", data.lines.len());
+
+    if data.lines.len() > usize::try_from(LISTING_LIMIT).unwrap_or(usize::MAX) {
+        return Err(Error::bad_request("Too many lines"));
+    }
+
+    let ingredients = database
+        .with_transaction(move |transaction| {
+            Box::pin(async move {
+                let result: Vec<(i64, String, f64)> = sqlx::query_as(
+                    "SELECT id, name, energy_density FROM ingredients",
+                )
+                .fetch_all(&mut **transaction)
+                .await?;
+
+                Ok(result
+                    .into_iter()
+                    .map(|(id, name, energy_density)| Ingredient {
+                        id,
+                        name,
+                        energy_density,
+                    })
+                    .collect::<Vec<_>>())
+            })
+        })
+        .await
+        .map_err(Error::from_db)?;
+
+    Ok(Json(
+        data.lines
+            .iter()
+            .map(|line| {
+                let line = parse_ingredient_line(line)?;
+                let parsed = line.ingredient;
+
+                let mut matches = ingredients
+                    .iter()
+                    .map(|ingredient| {
+                        (
+                            name_similarity(&parsed.name, &ingredient.name),
+                            ingredient,
+                        )
+                    })
+                    .filter(|(similarity, _)| *similarity >= MIN_SIMILARITY)
+                    .collect::<Vec<_>>();
+                matches.sort_by(|a, b| b.0.total_cmp(&a.0));
+                matches.truncate(MAX_MATCHES);
+
+                let best =
+                    matches.first().map_or(0.0, |(similarity, _)| *similarity);
+                let mut candidates = matches
+                    .into_iter()
+                    .map(|(similarity, ingredient)| IngredientCandidate {
+                        quantified_ingredient: Some(QuantifiedIngredient {
+                            ingredient: Ref::with_value(
+                                ingredient.id,
+                                ingredient.clone(),
+                            ),
+                            quantity: parsed.quantity,
+                            measurement: parsed.measurement,
+                        }),
+                        new_ingredient_name: None,
+                        confidence: similarity * line.confidence,
+                    })
+                    .collect::<Vec<_>>();
+
+                // Unless there is an exact match, propose a new ingredient.
+                if best < 1.0 {
+                    candidates.push(IngredientCandidate {
+                        quantified_ingredient: None,
+                        new_ingredient_name: Some(parsed.name.clone()),
+                        confidence: (1.0 - best) * line.confidence,
+                    });
+                }
+                candidates
+                    .sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+
+                Some(ParsedIngredientLine {
+                    name: parsed.name,
+                    quantity: parsed.quantity,
+                    measurement: parsed.measurement,
+                    unit: line.unit,
+                    preparation: line.preparation,
+                    candidates,
+                })
+            })
+            .collect(),
+    ))
+}
+
+/// Creates a router that serves routes for parsing natural-language recipe
+/// text.
+pub fn create_router<S>(database: Arc<Database>) -> Router<S> {
+    Router::new()
+        .route("/ingredients", post(parse_ingredients))
+        .with_state(database)
+}
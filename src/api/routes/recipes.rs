@@ -14,6 +14,7 @@ use crate::api::utils::Error;
 use crate::database::Database;
 use crate::models::{Category, Model, Recipe, Ref};
 
+mod import;
 mod versions;
 
 fn default_filter_limit() -> u64 {
@@ -118,6 +119,7 @@ pub fn create_router<S>(database: Arc<Database>) -> Router<S> {
     Router::new()
         .route("/", get(list_recipes))
         .route("/", post(create_recipe))
+        .nest("/import", import::create_router(database.clone()))
         .route("/:recipe_id", get(get_recipe))
         .nest(
             "/:recipe_id/versions",
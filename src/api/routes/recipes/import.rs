@@ -0,0 +1,153 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::{DefaultBodyLimit, Query, State},
+    routing::post,
+    Json, Router,
+};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::api::constants::IMPORT_SIZE_LIMIT;
+use crate::api::utils::Error;
+use crate::database::Database;
+use crate::formats::{
+    decode_text, is_mealmaster, parse_cooklang, parse_mealmaster,
+    parse_plain_text,
+};
+use crate::models::{Model, Recipe};
+
+/// Options for importing a single recipe from an external format.
+#[derive(Deserialize)]
+struct ImportOptions {
+    /// The name to give the recipe if the imported text does not specify one.
+    name: Option<String>,
+}
+
+/// Imports a recipe written in Cooklang as a new recipe with a single version.
+/// Returns the new recipe's JSON.
+async fn import_cooklang(
+    State(database): State<Arc<Database>>,
+    Query(options): Query<ImportOptions>,
+    body: String,
+) -> Result<Json<Recipe>, Error> {
+    debug!("Importing Cooklang recipe");
+
+    let default_name = options.name.as_deref().unwrap_or("Untitled recipe");
+    let parsed =
+        parse_cooklang(&body, default_name).map_err(Error::bad_request)?;
+
+    Ok(Json(
+        database
+            .with_transaction(move |transaction| {
+                Box::pin(async move {
+                    let id = parsed.store_new(transaction).await?;
+                    Recipe::get_filled(transaction, id.recipe_id).await
+                })
+            })
+            .await
+            .map_err(Error::from_db)?,
+    ))
+}
+
+/// A format for files that contain many recipes.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum BulkFormat {
+    /// Meal-Master (`MMMMM-----`) exports.
+    MealMaster,
+
+    /// Plain text with "Ingredients:" and "Directions:" sections.
+    Text,
+}
+
+/// Options for importing many recipes at once.
+///
+/// If `format` is not specified, it is detected from the file's contents.
+#[derive(Deserialize)]
+struct BulkImportOptions {
+    format: Option<BulkFormat>,
+}
+
+/// The outcome of importing one recipe from a bulk import file.
+///
+/// Exactly one of `recipe_id` and `error_message` is present.
+#[derive(Serialize)]
+struct ImportReport {
+    title: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recipe_id: Option<i64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_message: Option<String>,
+
+    /// Parts of the recipe that were ignored because they could not be
+    /// parsed.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
+}
+
+/// Imports every recipe in a file containing many recipes. Each recipe is
+/// stored in its own transaction, so recipes that cannot be parsed or stored
+/// do not prevent the others from being imported.
+///
+/// Lines that aren't valid UTF-8 are decoded as Windows-1252, which older
+/// Meal-Master programs write.
+///
+/// Returns a report for each recipe found in the file, in file order.
+async fn import_bulk(
+    State(database): State<Arc<Database>>,
+    Query(options): Query<BulkImportOptions>,
+    body: Bytes,
+) -> Json<Vec<ImportReport>> {
+    let body = decode_text(&body);
+    let format = options.format.unwrap_or(if is_mealmaster(&body) {
+        BulkFormat::MealMaster
+    } else {
+        BulkFormat::Text
+    });
+
+    let entries = match format {
+        BulkFormat::MealMaster => parse_mealmaster(&body),
+        BulkFormat::Text => parse_plain_text(&body),
+    };
+
+    debug!("Importing {} recipes in bulk", entries.len());
+
+    let mut reports = vec![];
+    for entry in entries {
+        let result = match entry.recipe {
+            Ok(recipe) => database
+                .with_transaction(move |transaction| {
+                    Box::pin(async move { recipe.store_new(transaction).await })
+                })
+                .await
+                .map(|id| id.recipe_id)
+                .map_err(|error| Error::from_db(error).message().to_owned()),
+            Err(message) => Err(message),
+        };
+
+        reports.push(ImportReport {
+            title: entry.title,
+            recipe_id: result.as_ref().ok().copied(),
+            error_message: result.err(),
+            warnings: entry.warnings,
+        });
+    }
+
+    Json(reports)
+}
+
+/// Creates a router that serves routes for importing recipes from external
+/// formats.
+pub fn create_router<S>(database: Arc<Database>) -> Router<S> {
+    Router::new()
+        .route("/cooklang", post(import_cooklang))
+        .route(
+            "/bulk",
+            post(import_bulk).layer(DefaultBodyLimit::max(IMPORT_SIZE_LIMIT)),
+        )
+        .with_state(database)
+}
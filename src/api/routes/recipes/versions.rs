@@ -2,15 +2,30 @@ use std::sync::Arc;
 
 use axum::{
     extract::{Path, State},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
     routing::get,
     Json, Router,
 };
+use chrono::{offset::Utc, Duration};
 use log::debug;
+use serde::Deserialize;
 
 use crate::api::constants::LISTING_LIMIT;
-use crate::api::utils::Error;
+use crate::api::utils::{preferred_media_type, Error};
 use crate::database::{self, Database};
-use crate::models::{Model, RecipeVersion, RecipeVersionID};
+use crate::formats::{
+    to_cooklang, to_markdown, to_pdf, to_plain_text, ParsedRecipe,
+};
+use crate::models::{
+    Instruction, MeasurementType, Model, QuantifiedIngredient, RecipeVersion,
+    RecipeVersionID, Ref,
+};
+
+/// The media types that a recipe version can be returned as.
+const JSON: &str = "application/json";
+const MARKDOWN: &str = "text/markdown";
+const PLAIN_TEXT: &str = "text/plain";
 
 /// Lists all versions of the recipe with the id `recipe_id`, using `database`
 /// to retrieve the recipes.
@@ -74,11 +89,15 @@ async fn list_versions(
 }
 
 /// Gets the version with ID `version_id` of the recipe with ID `recipe_id`.
+///
+/// The version is returned as JSON, unless the request's `Accept` header
+/// prefers Markdown (`text/markdown`) or plain text (`text/plain`), in which
+/// case it is rendered along with its recipe's name and categories.
 async fn get_version(
     State(database): State<Arc<Database>>,
-    Path(recipe_id): Path<i64>,
-    Path(version_id): Path<i64>,
-) -> Result<Json<RecipeVersion>, Error> {
+    Path((recipe_id, version_id)): Path<(i64, i64)>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
     debug!("Getting recipe {recipe_id} version {version_id}");
 
     let id = RecipeVersionID {
@@ -86,10 +105,186 @@ async fn get_version(
         version_id,
     };
 
+    let media_type =
+        preferred_media_type(&headers, &[JSON, MARKDOWN, PLAIN_TEXT])
+            .unwrap_or(JSON);
+
+    if media_type == JSON {
+        return Ok((
+            [(header::VARY, "Accept")],
+            Json(
+                database
+                    .with_transaction(move |transaction| {
+                        Box::pin(async move {
+                            RecipeVersion::get_filled(transaction, id).await
+                        })
+                    })
+                    .await
+                    .map_err(Error::from_db)?,
+            ),
+        )
+            .into_response());
+    }
+
+    let recipe = database
+        .with_transaction(move |transaction| {
+            Box::pin(async move { ParsedRecipe::load(transaction, id).await })
+        })
+        .await
+        .map_err(Error::from_db)?;
+
+    let text = if media_type == MARKDOWN {
+        to_markdown(&recipe)
+    } else {
+        to_plain_text(&recipe)
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format!("{media_type}; charset=utf-8")),
+            (header::VARY, "Accept".to_owned()),
+        ],
+        text,
+    )
+        .into_response())
+}
+
+/// Returns a file name for exports of `recipe`, without an extension.
+fn file_name(recipe: &ParsedRecipe) -> String {
+    recipe
+        .name
+        .chars()
+        .filter(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_'))
+        .collect()
+}
+
+/// Exports the version with ID `version_id` of the recipe with ID `recipe_id`
+/// as a Cooklang file.
+async fn export_cooklang(
+    State(database): State<Arc<Database>>,
+    Path((recipe_id, version_id)): Path<(i64, i64)>,
+) -> Result<impl IntoResponse, Error> {
+    debug!("Exporting recipe {recipe_id} version {version_id} as Cooklang");
+
+    let id = RecipeVersionID {
+        recipe_id,
+        version_id,
+    };
+
+    let recipe = database
+        .with_transaction(move |transaction| {
+            Box::pin(async move { ParsedRecipe::load(transaction, id).await })
+        })
+        .await
+        .map_err(Error::from_db)?;
+
+    let file_name = file_name(&recipe);
+    let cooklang = to_cooklang(&recipe).map_err(Error::bad_request)?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/plain; charset=utf-8".to_owned()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{file_name}.cook\""),
+            ),
+        ],
+        cooklang,
+    ))
+}
+
+/// Exports the version with ID `version_id` of the recipe with ID `recipe_id`
+/// as a one-page PDF recipe card.
+async fn export_pdf(
+    State(database): State<Arc<Database>>,
+    Path((recipe_id, version_id)): Path<(i64, i64)>,
+) -> Result<impl IntoResponse, Error> {
+    debug!("Exporting recipe {recipe_id} version {version_id} as PDF");
+
+    let id = RecipeVersionID {
+        recipe_id,
+        version_id,
+    };
+
+    let recipe = database
+        .with_transaction(move |transaction| {
+            Box::pin(async move { ParsedRecipe::load(transaction, id).await })
+        })
+        .await
+        .map_err(Error::from_db)?;
+
+    let file_name = file_name(&recipe);
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/pdf".to_owned()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("inline; filename=\"{file_name}.pdf\""),
+            ),
+        ],
+        to_pdf(&recipe),
+    ))
+}
+
+/// An ingredient and its quantity in a new recipe version.
+#[derive(Deserialize)]
+struct CreateQuantifiedIngredientData {
+    ingredient_id: i64,
+    quantity: f64, // In SI standard units.
+    measurement: MeasurementType,
+}
+
+/// The data required to create a new version of a recipe.
+#[derive(Deserialize)]
+struct CreateVersionData {
+    ingredients: Vec<CreateQuantifiedIngredientData>,
+    instructions: Vec<String>,
+    duration: i64, // In seconds.
+}
+
+/// Creates a new version of the recipe with ID `recipe_id`. Returns the new
+/// version's JSON, including its ID.
+async fn create_version(
+    State(database): State<Arc<Database>>,
+    Path(recipe_id): Path<i64>,
+    Json(data): Json<CreateVersionData>,
+) -> Result<Json<RecipeVersion>, Error> {
+    debug!("Creating new version of recipe {recipe_id}");
+
+    if data.duration.abs() > Duration::max_value().num_seconds() {
+        return Err(Error::bad_request("Duration is too long"));
+    }
+    let duration = Duration::seconds(data.duration);
+
+    let ingredients = data
+        .ingredients
+        .into_iter()
+        .map(|ingredient| QuantifiedIngredient {
+            ingredient: Ref::new(ingredient.ingredient_id),
+            quantity: ingredient.quantity,
+            measurement: ingredient.measurement,
+        })
+        .collect();
+    let instructions = data
+        .instructions
+        .into_iter()
+        .map(|text| Instruction { text })
+        .collect();
+
     Ok(Json(
         database
             .with_transaction(move |transaction| {
                 Box::pin(async move {
+                    let id = RecipeVersion::store_new(
+                        transaction,
+                        recipe_id,
+                        Utc::now(),
+                        ingredients,
+                        instructions,
+                        duration,
+                    )
+                    .await?;
                     RecipeVersion::get_filled(transaction, id).await
                 })
             })
@@ -103,7 +298,9 @@ async fn get_version(
 /// This router must be nested under a path that provides `:recipe_id`.
 pub fn create_router<S>(database: Arc<Database>) -> Router<S> {
     Router::new()
-        .route("/", get(list_versions))
+        .route("/", get(list_versions).post(create_version))
         .route("/:version_id", get(get_version))
+        .route("/:version_id/cooklang", get(export_cooklang))
+        .route("/:version_id/pdf", get(export_pdf))
         .with_state(database)
 }
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 use std::fmt;
 
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
@@ -18,6 +18,15 @@ pub struct Error {
 }
 
 impl Error {
+    /// Creates a 400 error with a message describing what was wrong with the
+    /// request.
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self {
+            status_code: StatusCode::BAD_REQUEST,
+            message: message.into(),
+        }
+    }
+
     /// Creates an API error from a `SQLx` error.
     ///
     /// `RowNotFound` errors are converted into 404 errors.
@@ -44,6 +53,11 @@ impl Error {
             message,
         }
     }
+
+    /// Returns the message that describes this error to API users.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
 }
 
 impl fmt::Display for Error {
@@ -66,3 +80,124 @@ impl IntoResponse for Error {
             .into_response()
     }
 }
+
+/// Returns the entry of `supported` that the request's `Accept` header
+/// prefers most, if it accepts any of them.
+///
+/// Media types are compared ignoring case, and `*/*` and `type/*` ranges
+/// match any supported type. Each type's quality comes from the most specific
+/// range that matches it, so that e.g. `application/json;q=0, */*` refuses
+/// JSON. Types of equal quality are preferred in the order that their ranges
+/// are listed. Requests without an `Accept` header accept the first supported
+/// type.
+pub fn preferred_media_type<'a>(
+    headers: &HeaderMap,
+    supported: &[&'a str],
+) -> Option<&'a str> {
+    let Some(accept) = headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return supported.first().copied();
+    };
+
+    let ranges = accept
+        .split(',')
+        .map(|range| {
+            let mut parts = range.split(';').map(str::trim);
+            let media_range = parts.next().unwrap_or("").to_lowercase();
+            let quality = parts
+                .filter_map(|parameter| parameter.strip_prefix("q="))
+                .find_map(|quality| quality.parse::<f64>().ok())
+                .unwrap_or(1.0);
+            (media_range, quality)
+        })
+        .collect::<Vec<_>>();
+
+    // The (specificity, quality, position) of the range that applies to
+    // `media_type`, if any.
+    let applicable_range = |media_type: &str| {
+        let mut applicable: Option<(u8, f64, usize)> = None;
+        for (position, (media_range, quality)) in ranges.iter().enumerate() {
+            let specificity = if media_range.eq_ignore_ascii_case(media_type) {
+                2
+            } else if media_range.strip_suffix("/*").is_some_and(|prefix| {
+                media_type
+                    .split('/')
+                    .next()
+                    .is_some_and(|kind| kind.eq_ignore_ascii_case(prefix))
+            }) {
+                1
+            } else if media_range == "*/*" {
+                0
+            } else {
+                continue;
+            };
+            if applicable.is_none_or(|(best, _, _)| specificity > best) {
+                applicable = Some((specificity, *quality, position));
+            }
+        }
+        applicable
+    };
+
+    let mut best: Option<(&str, f64, usize)> = None;
+    for &media_type in supported {
+        let Some((_, quality, position)) = applicable_range(media_type) else {
+            continue;
+        };
+        if quality <= 0.0 {
+            continue;
+        }
+        if best.is_none_or(|(_, best_quality, best_position)| {
+            quality
+                .total_cmp(&best_quality)
+                .then(best_position.cmp(&position))
+                .is_gt()
+        }) {
+            best = Some((media_type, quality, position));
+        }
+    }
+    best.map(|(media_type, _, _)| media_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    const SUPPORTED: &[&str] = &["application/json", "text/html"];
+
+    /// Returns the type that `accept` prefers out of `SUPPORTED`.
+    fn preferred(accept: &str) -> Option<&'static str> {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_str(accept).unwrap());
+        preferred_media_type(&headers, SUPPORTED)
+    }
+
+    #[test]
+    fn test_preferred_media_type() {
+        assert_eq!(
+            preferred_media_type(&HeaderMap::new(), SUPPORTED),
+            Some("application/json")
+        );
+
+        // Quality, then order.
+        assert_eq!(
+            preferred("application/json;q=0.5, text/html"),
+            Some("text/html")
+        );
+        assert_eq!(preferred("text/html, application/json"), Some("text/html"));
+        assert_eq!(preferred("Text/HTML;q=0.9, */*;q=0.1"), Some("text/html"));
+
+        // Wildcards.
+        assert_eq!(preferred("*/*"), Some("application/json"));
+        assert_eq!(preferred("text/*"), Some("text/html"));
+        assert_eq!(preferred("image/png"), None);
+
+        // Exclusions take precedence over wildcards.
+        assert_eq!(preferred("application/json;q=0, */*"), Some("text/html"));
+        assert_eq!(preferred("text/*;q=0, */*"), Some("application/json"));
+        assert_eq!(preferred("application/json;q=0, text/html;q=0, */*"), None);
+    }
+}
@@ -0,0 +1,84 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+
+use crate::config::{self, Config};
+
+/// A web server for creating, editing, and viewing your recipes.
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+/// The arguments shared by all subcommands.
+#[derive(Args)]
+pub struct ConfigArgs {
+    /// The TOML config file to use. Its settings can be overridden by
+    /// environment variables such as `RECIPES_SERVER__PORT=8080`
+    pub config: PathBuf,
+
+    /// Override a config setting, e.g. `--set server.port=8080`. Takes
+    /// precedence over the config file and environment variables
+    #[arg(short, long = "set", value_name = "KEY=VALUE")]
+    pub settings: Vec<String>,
+}
+
+impl ConfigArgs {
+    /// Reads the configuration from the config file, the environment, and the
+    /// command line.
+    pub fn read_config(&self) -> Result<Config, String> {
+        config::read_config(&self.config, &self.settings)
+    }
+}
+
+/// The operations that the program can perform.
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run the web server
+    Serve(ConfigArgs),
+
+    /// Create the database and its tables if they do not exist
+    InitDb(ConfigArgs),
+
+    /// Migrate the database to a newer schema version
+    Migrate {
+        #[command(flatten)]
+        config: ConfigArgs,
+
+        /// The version to migrate to [default: the latest version]
+        #[arg(long, value_name = "VERSION")]
+        to: Option<i64>,
+
+        /// Show the migrations that would be applied without applying them
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Check that a config file is valid and show its settings
+    CheckConfig(ConfigArgs),
+
+    /// Export the whole database as a JSON archive
+    Export {
+        #[command(flatten)]
+        config: ConfigArgs,
+
+        /// The file to write the archive to [default: standard output]
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+
+    /// Import a JSON archive created by `export`
+    Import {
+        #[command(flatten)]
+        config: ConfigArgs,
+
+        /// The archive to import, or "-" for standard input
+        #[arg(value_name = "FILE")]
+        input: PathBuf,
+    },
+
+    /// Add sample data to an empty database
+    Seed(ConfigArgs),
+}
@@ -0,0 +1,159 @@
+//! The subcommands other than `serve`, for managing the database from the
+//! command line.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use crate::config::Config;
+use crate::database::{latest_version, Archive, Database};
+use crate::util::stringify_err;
+
+/// Creates the database and its tables if they do not exist, then migrates
+/// it to the latest version.
+///
+/// The server does this itself when it starts, so this is only needed to
+/// prepare a database in advance.
+pub async fn init_db(config: Config) -> Result<(), String> {
+    let database = stringify_err(Database::new(config.database).await)?;
+    println!("Database initialized at version {}", database.get_version());
+    Ok(())
+}
+
+/// Migrates the database to `target_version`, or to the latest version if it
+/// is `None`. If `dry_run` is true, the migrations are only listed, and the
+/// database must already exist.
+pub async fn migrate(
+    config: Config,
+    target_version: Option<i64>,
+    dry_run: bool,
+) -> Result<(), String> {
+    let mut database = stringify_err(if dry_run {
+        Database::open(config.database).await
+    } else {
+        Database::connect(config.database).await
+    })?;
+    let starting_version = database.get_version();
+
+    let (steps, backup_path) =
+        stringify_err(database.migrate(target_version, dry_run).await)?;
+    if steps.is_empty() {
+        println!("Database is already at version {starting_version}");
+    }
+    if let Some(backup_path) = backup_path {
+        println!("Backed up database to {}", backup_path.display());
+    }
+    for step in steps {
+        let action = match (dry_run, step.to_version > step.from_version) {
+            (true, true) => "Would migrate",
+            (true, false) => "Would roll back",
+            (false, true) => "Migrated",
+            (false, false) => "Rolled back",
+        };
+        println!(
+            "{action} from version {} to version {} ({})",
+            step.from_version, step.to_version, step.description
+        );
+    }
+    Ok(())
+}
+
+/// Prints the settings in `config`, which was read from `path` and the
+/// overrides in the environment and on the command line. Secrets are
+/// redacted.
+pub fn check_config(path: &Path, config: &Config) -> Result<(), String> {
+    println!("Config file {} is valid", path.display());
+    println!("Effective configuration:");
+    println!();
+    print!("{}", config.to_redacted_toml()?);
+    Ok(())
+}
+
+/// Exports the whole database as a JSON archive, written to `output` or to
+/// standard output if it is `None`.
+///
+/// The database is only read, so it must already be at the latest version.
+pub async fn export(
+    config: Config,
+    output: Option<&Path>,
+) -> Result<(), String> {
+    let database = Database::open(config.database).await.map_err(|error| {
+        if error.is_missing_table() {
+            "Database has no schema version; run `migrate` before \
+                 exporting"
+                .to_owned()
+        } else {
+            error.to_string()
+        }
+    })?;
+    let schema_version = database.get_version();
+    if schema_version != latest_version() {
+        return Err(format!(
+            "Database is at version {schema_version}, not the latest version \
+             {}; run `migrate` before exporting",
+            latest_version()
+        ));
+    }
+    let archive = stringify_err(
+        database
+            .with_transaction(move |transaction| {
+                Box::pin(async move {
+                    Archive::export(transaction, schema_version).await
+                })
+            })
+            .await,
+    )?;
+
+    let json = stringify_err(serde_json::to_string_pretty(&archive))?;
+    match output {
+        Some(path) => stringify_err(fs::write(path, json)),
+        None => stringify_err(writeln!(io::stdout(), "{json}")),
+    }
+}
+
+/// Imports the JSON archive at `input`, or from standard input if `input` is
+/// "-".
+pub async fn import(config: Config, input: &Path) -> Result<(), String> {
+    let json = if input.as_os_str() == "-" {
+        let mut json = String::new();
+        stringify_err(io::stdin().read_to_string(&mut json))?;
+        json
+    } else {
+        stringify_err(fs::read_to_string(input))?
+    };
+    let archive: Archive = serde_json::from_str(&json)
+        .map_err(|error| format!("Invalid archive: {error}"))?;
+
+    let database = stringify_err(Database::new(config.database).await)?;
+    let schema_version = database.get_version();
+    let summary = stringify_err(
+        database
+            .with_transaction(move |transaction| {
+                Box::pin(async move {
+                    archive.import(transaction, schema_version).await
+                })
+            })
+            .await,
+    )?;
+
+    println!(
+        "Imported {} ingredients, {} categories, {} recipes and {} recipe \
+         versions",
+        summary.ingredients,
+        summary.categories,
+        summary.recipes,
+        summary.recipe_versions
+    );
+    if !summary.ids_preserved {
+        println!("The database was not empty, so new IDs were assigned");
+    }
+    Ok(())
+}
+
+/// Adds sample data to an empty database.
+pub async fn seed(config: Config) -> Result<(), String> {
+    let database = stringify_err(Database::new(config.database).await)?;
+    stringify_err(database.seed().await)?;
+    println!("Sample data added");
+    Ok(())
+}
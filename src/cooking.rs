@@ -0,0 +1,5 @@
+mod sessions;
+mod timers;
+
+pub use sessions::{CookingSession, CookingSessions, MAX_TIMER_LABEL_LENGTH};
+pub use timers::{find_timers, SuggestedTimer, MAX_TIMER_SECONDS};
@@ -0,0 +1,205 @@
+//! Server-side state for cooking mode, shared by every device that is
+//! cooking the same recipe version.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+
+use chrono::{offset::Utc, DateTime, Duration};
+use serde::Serialize;
+
+use crate::models::RecipeVersionID;
+
+/// How long a session is kept after it was last changed, in hours.
+const SESSION_LIFETIME_HOURS: i64 = 12;
+
+/// The most timers that a session can have at once.
+const MAX_TIMERS: usize = 20;
+
+/// The longest label that a timer can have, in characters.
+pub const MAX_TIMER_LABEL_LENGTH: usize = 100;
+
+/// A timer started while cooking.
+#[derive(Clone, Serialize)]
+pub struct Timer {
+    /// The ID of the timer, unique within its session.
+    pub id: u64,
+
+    /// What the timer is for, e.g. "25 minutes".
+    pub label: String,
+
+    /// The index of the step that the timer was started from.
+    pub step: usize,
+
+    /// When the timer finishes.
+    pub ends: DateTime<Utc>,
+}
+
+/// The progress of one recipe version being cooked.
+#[derive(Clone, Serialize)]
+pub struct CookingSession {
+    /// The index of the step currently being shown.
+    pub step: usize,
+
+    /// The timers that have been started and not dismissed, including
+    /// finished ones.
+    pub timers: Vec<Timer>,
+
+    /// When the session was last changed.
+    pub updated: DateTime<Utc>,
+
+    #[serde(skip)]
+    next_timer_id: u64,
+}
+
+impl CookingSession {
+    /// Creates a session at the first step with no timers.
+    fn new() -> Self {
+        Self {
+            step: 0,
+            timers: vec![],
+            updated: Utc::now(),
+            next_timer_id: 1,
+        }
+    }
+}
+
+/// Removes the sessions in `sessions` that haven't changed for
+/// `SESSION_LIFETIME_HOURS`.
+fn remove_expired(sessions: &mut HashMap<RecipeVersionID, CookingSession>) {
+    let expired_before = Utc::now() - Duration::hours(SESSION_LIFETIME_HOURS);
+    sessions.retain(|_, session| session.updated >= expired_before);
+}
+
+/// All cooking sessions in progress, keyed by the recipe version being
+/// cooked.
+///
+/// Sessions are only kept in memory, and are forgotten after
+/// `SESSION_LIFETIME_HOURS` without changes.
+#[derive(Default)]
+pub struct CookingSessions {
+    sessions: Mutex<HashMap<RecipeVersionID, CookingSession>>,
+}
+
+impl CookingSessions {
+    /// Calls `change` with the session for `id`, creating it if needed, and
+    /// marks the session as updated. Expired sessions are removed first.
+    fn update<T>(
+        &self,
+        id: RecipeVersionID,
+        change: impl FnOnce(&mut CookingSession) -> T,
+    ) -> T {
+        let mut sessions =
+            self.sessions.lock().unwrap_or_else(PoisonError::into_inner);
+        remove_expired(&mut sessions);
+
+        let session = sessions.entry(id).or_insert_with(CookingSession::new);
+        session.updated = Utc::now();
+        change(session)
+    }
+
+    /// Returns the session for `id`, or a new session at the first step if
+    /// there is none.
+    pub fn get(&self, id: RecipeVersionID) -> CookingSession {
+        self.sessions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&id)
+            .cloned()
+            .unwrap_or_else(CookingSession::new)
+    }
+
+    /// Moves the session for `id` to the step with index `step`.
+    pub fn set_step(&self, id: RecipeVersionID, step: usize) -> CookingSession {
+        self.update(id, |session| {
+            session.step = step;
+            session.clone()
+        })
+    }
+
+    /// Starts a timer called `label` that lasts `duration` in the session for
+    /// `id`.
+    ///
+    /// Returns `None` if the session already has `MAX_TIMERS` timers.
+    pub fn start_timer(
+        &self,
+        id: RecipeVersionID,
+        step: usize,
+        label: String,
+        duration: Duration,
+    ) -> Option<CookingSession> {
+        self.update(id, |session| {
+            if session.timers.len() >= MAX_TIMERS {
+                return None;
+            }
+            session.timers.push(Timer {
+                id: session.next_timer_id,
+                label,
+                step,
+                ends: Utc::now() + duration,
+            });
+            session.next_timer_id += 1;
+            Some(session.clone())
+        })
+    }
+
+    /// Stops or dismisses the timer with ID `timer_id` in the session for
+    /// `id`.
+    ///
+    /// Returns `None` if there is no such timer.
+    pub fn remove_timer(
+        &self,
+        id: RecipeVersionID,
+        timer_id: u64,
+    ) -> Option<CookingSession> {
+        let mut sessions =
+            self.sessions.lock().unwrap_or_else(PoisonError::into_inner);
+        remove_expired(&mut sessions);
+
+        let session = sessions.get_mut(&id)?;
+        let position = session
+            .timers
+            .iter()
+            .position(|timer| timer.id == timer_id)?;
+        session.timers.remove(position);
+        session.updated = Utc::now();
+        Some(session.clone())
+    }
+
+    /// Ends the session for `id`, forgetting its step and timers.
+    pub fn end(&self, id: RecipeVersionID) {
+        self.sessions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timers() {
+        let sessions = CookingSessions::default();
+        let id = RecipeVersionID {
+            recipe_id: 1,
+            version_id: 0,
+        };
+
+        assert!(sessions.remove_timer(id, 1).is_none());
+        assert!(sessions.sessions.lock().unwrap().is_empty());
+
+        for _ in 0..MAX_TIMERS {
+            assert!(sessions
+                .start_timer(id, 0, "Boil".to_owned(), Duration::minutes(5))
+                .is_some());
+        }
+        assert!(sessions
+            .start_timer(id, 0, "Boil".to_owned(), Duration::minutes(5))
+            .is_none());
+
+        let session = sessions.remove_timer(id, 1).unwrap();
+        assert_eq!(session.timers.len(), MAX_TIMERS - 1);
+        assert!(sessions.remove_timer(id, 1).is_none());
+    }
+}
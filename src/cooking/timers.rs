@@ -0,0 +1,146 @@
+//! Detection of durations mentioned in instruction text, such as "bake for
+//! 25 minutes", so that cooking mode can offer timers for them.
+
+use serde::Serialize;
+
+use crate::units::{duration_unit_seconds, parse_amount, parse_duration};
+
+/// The longest timer that can be suggested or started, in seconds (one week).
+pub const MAX_TIMER_SECONDS: i64 = 7 * 24 * 60 * 60;
+
+/// A duration mentioned in an instruction, which can be used as a timer.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SuggestedTimer {
+    /// The text that mentions the duration, e.g. "25 minutes".
+    pub label: String,
+
+    /// The length of the timer, in seconds.
+    pub seconds: i64,
+}
+
+/// Parses an amount starting at `words[index]`, such as "2", "1 1/2",
+/// "20-25" or "20 to 25".
+///
+/// Ranges are parsed as their lower bound, so that the cook checks early.
+/// Returns the amount and the index of the word after it.
+fn amount_at(words: &[&str], index: usize) -> Option<(f64, usize)> {
+    let amount = parse_amount(words.get(index)?)?;
+
+    match words.get(index + 1..index + 3) {
+        Some([fraction, _]) if fraction.contains('/') => {
+            let mixed = format!("{} {fraction}", words[index]);
+            return Some((parse_amount(&mixed)?, index + 2));
+        }
+        Some([separator, upper])
+            if matches!(*separator, "to" | "or")
+                && parse_amount(upper).is_some() =>
+        {
+            return Some((amount, index + 3));
+        }
+        _ => {}
+    }
+    Some((amount, index + 1))
+}
+
+/// Parses a single duration starting at `words[index]`, such as "25
+/// minutes", "1 1/2 hours" or "25min".
+///
+/// Returns the duration in seconds and the index of the word after it.
+fn duration_at(words: &[&str], index: usize) -> Option<(f64, usize)> {
+    let word = words.get(index)?;
+    if word.starts_with(|c: char| c.is_ascii_digit())
+        && word.contains(char::is_alphabetic)
+    {
+        let duration = parse_duration(word)?;
+        #[allow(clippy::cast_precision_loss)]
+        return Some((duration.num_seconds() as f64, index + 1));
+    }
+
+    let (amount, unit_index) = amount_at(words, index)?;
+    let unit_seconds = duration_unit_seconds(words.get(unit_index)?)?;
+    #[allow(clippy::cast_precision_loss)]
+    Some((amount * unit_seconds as f64, unit_index + 1))
+}
+
+/// Finds the durations mentioned in `text`, in the order they appear.
+///
+/// Adjacent durations such as "1 hour 30 minutes" or "1 hour and 30
+/// minutes" are combined into one timer.
+pub fn find_timers(text: &str) -> Vec<SuggestedTimer> {
+    let words = text
+        .split_whitespace()
+        .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()))
+        .collect::<Vec<_>>();
+
+    let mut timers = vec![];
+    let mut index = 0;
+    while index < words.len() {
+        let Some((mut seconds, mut end)) = duration_at(&words, index) else {
+            index += 1;
+            continue;
+        };
+
+        loop {
+            let next = if words
+                .get(end)
+                .is_some_and(|word| word.eq_ignore_ascii_case("and"))
+            {
+                end + 1
+            } else {
+                end
+            };
+            let Some((more_seconds, after)) = duration_at(&words, next) else {
+                break;
+            };
+            seconds += more_seconds;
+            end = after;
+        }
+
+        #[allow(clippy::cast_precision_loss)]
+        if (1.0..=MAX_TIMER_SECONDS as f64).contains(&seconds) {
+            #[allow(clippy::cast_possible_truncation)]
+            timers.push(SuggestedTimer {
+                label: words[index..end].join(" "),
+                seconds: seconds.round() as i64,
+            });
+        }
+        index = end;
+    }
+    timers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timers(text: &str) -> Vec<(String, i64)> {
+        find_timers(text)
+            .into_iter()
+            .map(|timer| (timer.label, timer.seconds))
+            .collect()
+    }
+
+    #[test]
+    fn test_find_timers() {
+        assert_eq!(
+            timers("Bake at 350 degrees for 25 minutes."),
+            [("25 minutes".to_owned(), 1500)]
+        );
+        assert_eq!(
+            timers("Simmer 1 hour and 30 min, then rest (10-15 minutes)."),
+            [
+                ("1 hour and 30 min".to_owned(), 5400),
+                ("10-15 minutes".to_owned(), 600)
+            ]
+        );
+        assert_eq!(
+            timers("Chill 1 1/2 hrs or 2 to 3 days; microwave 90s"),
+            [
+                ("1 1/2 hrs".to_owned(), 5400),
+                ("2 to 3 days".to_owned(), 172_800),
+                ("90s".to_owned(), 90)
+            ]
+        );
+        assert!(timers("Add 2 eggs and 250 g flour.").is_empty());
+    }
+}
@@ -1,33 +1,82 @@
+mod archive;
 mod error;
 mod migrator;
 mod modelcache;
 mod transaction;
 mod types;
 
+use std::fs;
 use std::future::Future;
+use std::path::PathBuf;
 use std::pin::Pin;
 use std::str::FromStr;
+use std::time::Instant;
 
+pub use archive::{Archive, ImportSummary};
+use chrono::offset::Utc;
 pub use error::{to_internal_db_error, Error};
-use log::{debug, trace, LevelFilter};
+use log::{debug, info, trace, warn, LevelFilter};
 use migrator::Migrator;
+pub use migrator::{latest_version, MigrationStep};
 use sqlx::any::{
     install_default_drivers, Any, AnyConnectOptions, AnyPoolOptions,
 };
-use sqlx::{ConnectOptions, Pool, Transaction};
-pub use types::{DBFut, DBResult};
+use sqlx::{ConnectOptions, Connection, Executor, Pool, Transaction};
+pub use types::DBResult;
 
 use crate::config::DatabaseConfig;
+use crate::{logging, metrics};
+
+/// Statistics about a database's connection pool.
+pub struct PoolStatistics {
+    /// The number of open connections, whether idle or in use.
+    pub size: u32,
+
+    /// The number of idle connections.
+    pub idle: u32,
+
+    /// The most connections that the pool may hold.
+    pub max_size: u32,
+}
 
 /// Represents a recipes database.
 ///
 /// Allows connecting to the database, querying the database, and updating the
 /// database.
 ///
-/// Automatically performs migrations as needed.
+/// Automatically creates the database's tables and performs migrations as
+/// needed.
 pub struct Database {
     connection_pool: Pool<Any>, // The pool of connections to the database
     version: i64,               // The version number reached after migrations
+    file_path: Option<PathBuf>, // The path of the database file, for SQLite
+    created: bool,              // Whether the tables were just created
+}
+
+/// The SQL that inserts a small set of sample data.
+const SAMPLE_DATA_SQL: &str = include_str!("../setup/sample_data.sql");
+
+/// Returns `url` with `SQLite`'s `mode=rwc` option added, so that connecting
+/// creates the database file if it does not exist. URLs for other databases,
+/// and `SQLite` URLs that already specify a mode, are returned unchanged.
+fn creating_url(url: &str) -> String {
+    if !url.starts_with("sqlite:") || url.contains("mode=") {
+        url.to_owned()
+    } else if url.contains('?') {
+        format!("{url}&mode=rwc")
+    } else {
+        format!("{url}?mode=rwc")
+    }
+}
+
+/// Returns the path of the database file that a `SQLite` connection URL
+/// refers to, or `None` for in-memory databases and other kinds of database.
+fn sqlite_file_path(url: &str) -> Option<PathBuf> {
+    let path = url
+        .strip_prefix("sqlite://")
+        .or_else(|| url.strip_prefix("sqlite:"))?;
+    let path = path.split('?').next().unwrap_or(path);
+    (!path.is_empty() && path != ":memory:").then(|| PathBuf::from(path))
 }
 
 impl Database {
@@ -36,45 +85,276 @@ impl Database {
     /// The database will connect using `config.connection_url` and will
     /// maintain at most `config.max_connections` connections at a time.
     ///
-    /// Migrations will be applied while the `Database` is created.
+    /// If the database is empty, its tables will be created. Migrations will be
+    /// applied while the `Database` is created.
     pub async fn new(config: DatabaseConfig) -> DBResult<Self> {
+        let mut database = Self::connect(config).await?;
+        database.migrate(None, false).await?;
+        Ok(database)
+    }
+
+    /// Connects to the database described by `config` without applying any
+    /// migrations.
+    ///
+    /// `SQLite` database files are created if they do not exist, and the tables
+    /// are created if the database is empty.
+    pub async fn connect(config: DatabaseConfig) -> DBResult<Self> {
+        let connection_pool = Self::connect_pool(
+            &creating_url(&config.connection_url),
+            config.max_connections,
+        )
+        .await?;
+        let created = Migrator::bootstrap(&connection_pool).await?;
+        Self::from_pool(connection_pool, &config.connection_url, created).await
+    }
+
+    /// Connects to the existing database described by `config` without
+    /// changing it.
+    ///
+    /// Unlike `connect`, this never creates a `SQLite` database file or the
+    /// database's tables, so it returns an error if the database has not been
+    /// set up.
+    pub async fn open(config: DatabaseConfig) -> DBResult<Self> {
+        let connection_pool =
+            Self::connect_pool(&config.connection_url, config.max_connections)
+                .await?;
+        Self::from_pool(connection_pool, &config.connection_url, false).await
+    }
+
+    /// Creates a `Database` that uses `connection_pool`, connected to
+    /// `connection_url`, after checking its migrations. `created` is whether
+    /// its tables were just created.
+    async fn from_pool(
+        connection_pool: Pool<Any>,
+        connection_url: &str,
+        created: bool,
+    ) -> DBResult<Self> {
+        let version =
+            Migrator::new(&connection_pool).await?.get_current_version();
+
+        debug!("Database connected at version {version}");
+
+        Ok(Self {
+            connection_pool,
+            version,
+            file_path: sqlite_file_path(connection_url),
+            created,
+        })
+    }
+
+    /// Connects to the database at `connection_url` with a pool of at most
+    /// `max_connections` connections.
+    async fn connect_pool(
+        connection_url: &str,
+        max_connections: u32,
+    ) -> DBResult<Pool<Any>> {
         trace!("Installing SQLx default drivers");
         install_default_drivers();
 
         debug!(
-            "Connecting to database using connection URL \"{}\"",
-            config.connection_url
+            "Connecting to database using connection URL \"{connection_url}\""
         );
 
         // Log SQL statements at debug level. This way, they don't show up with
         // the default logging configuration, but they can easily be enabled for
         // debugging.
-        let connect_options =
-            AnyConnectOptions::from_str(&config.connection_url)?
-                .log_statements(LevelFilter::Debug);
+        let connect_options = AnyConnectOptions::from_str(connection_url)?
+            .log_statements(LevelFilter::Debug);
 
-        let connection_pool = AnyPoolOptions::new()
-            .max_connections(config.max_connections)
+        Ok(AnyPoolOptions::new()
+            .max_connections(max_connections)
             .connect_with(connect_options)
+            .await?)
+    }
+
+    /// Returns statistics about the database's connection pool.
+    pub fn pool_statistics(&self) -> PoolStatistics {
+        PoolStatistics {
+            size: self.connection_pool.size(),
+            idle: u32::try_from(self.connection_pool.num_idle())
+                .unwrap_or(u32::MAX),
+            max_size: self.connection_pool.options().get_max_connections(),
+        }
+    }
+
+    /// Checks that the database is responding to queries.
+    pub async fn ping(&self) -> DBResult<()> {
+        sqlx::query("SELECT 1")
+            .execute(&self.connection_pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Reads the current migration version from the database.
+    ///
+    /// Unlike `get_version`, this reflects migrations run by other processes.
+    pub async fn read_version(&self) -> DBResult<i64> {
+        Ok(sqlx::query_scalar("SELECT version FROM db_version")
+            .fetch_one(&self.connection_pool)
+            .await?)
+    }
+
+    /// Closes the database's connections, waiting for any that are in use to
+    /// be returned first.
+    ///
+    /// The write-ahead log of a `SQLite` database is checkpointed into the
+    /// database file before closing.
+    pub async fn close(&self) {
+        if self.file_path.is_some() {
+            debug!("Checkpointing write-ahead log");
+            if let Err(error) = sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+                .execute(&self.connection_pool)
+                .await
+            {
+                warn!("Could not checkpoint write-ahead log: {error}");
+            }
+        }
+        self.connection_pool.close().await;
+    }
+
+    /// Migrates the database to `target_version`, or to the latest version if
+    /// it is `None`. If `dry_run` is true, nothing is changed.
+    ///
+    /// `SQLite` databases are backed up next to the database file before any
+    /// migration is applied, unless their tables were only just created.
+    ///
+    /// Returns the migration steps that were (or would be) applied, and the
+    /// path of the backup if one was made.
+    pub async fn migrate(
+        &mut self,
+        target_version: Option<i64>,
+        dry_run: bool,
+    ) -> DBResult<(Vec<MigrationStep>, Option<PathBuf>)> {
+        let mut migrator = Migrator::new(&self.connection_pool).await?;
+        let planned_steps = migrator.plan(target_version)?;
+        if dry_run || planned_steps.is_empty() {
+            return Ok((planned_steps, None));
+        }
+
+        let backup_path = if self.created {
+            None
+        } else {
+            self.back_up().await?
+        };
+        let steps = migrator.run_migrations(target_version).await?;
+
+        self.version = migrator.get_current_version();
+        debug!("Database migrated to version {}", self.version);
+
+        Ok((steps, backup_path))
+    }
+
+    /// Copies a `SQLite` database to a new file next to the database file,
+    /// named after the database's version and the current time. Existing
+    /// files are never overwritten.
+    ///
+    /// Returns the path of the copy, or `None` if the database is not stored
+    /// in a `SQLite` file.
+    pub async fn back_up(&self) -> DBResult<Option<PathBuf>> {
+        let Some(file_path) = &self.file_path else {
+            return Ok(None);
+        };
+        let stem = format!(
+            "{}.v{}-{}",
+            file_path.display(),
+            self.version,
+            Utc::now().format("%Y%m%d%H%M%S")
+        );
+        let mut backup_path = PathBuf::from(format!("{stem}.bak"));
+        for copy in 2.. {
+            if !backup_path.exists() {
+                break;
+            }
+            backup_path = PathBuf::from(format!("{stem}-{copy}.bak"));
+        }
+
+        info!("Backing up database to {}", backup_path.display());
+        sqlx::query("VACUUM INTO $1")
+            .bind(backup_path.to_string_lossy().into_owned())
+            .execute(&self.connection_pool)
             .await?;
+        Ok(Some(backup_path))
+    }
 
-        // TODO: Use SQLx's built-in migration system
-        let mut migrator = Migrator::new(&connection_pool).await?;
-        migrator.run_migrations().await?;
+    /// Rebuilds the database file to reclaim unused space.
+    pub async fn vacuum(&self) -> DBResult<()> {
+        info!("Vacuuming database");
+        sqlx::query("VACUUM").execute(&self.connection_pool).await?;
+        Ok(())
+    }
 
-        let version = migrator.get_current_version();
+    /// Returns the size of a `SQLite` database's files in bytes, including its
+    /// write-ahead log, or `None` if the database is not stored in a file.
+    pub fn file_size(&self) -> Option<u64> {
+        let file_path = self.file_path.as_ref()?;
+        let mut wal_path = file_path.clone().into_os_string();
+        wal_path.push("-wal");
+        let size = fs::metadata(file_path).ok()?.len()
+            + fs::metadata(wal_path).map_or(0, |metadata| metadata.len());
+        Some(size)
+    }
 
-        debug!("Database connected and migrated to version {version}");
+    /// Returns the name of each table in the database, in alphabetical order,
+    /// with the number of rows in it.
+    pub async fn table_row_counts(&self) -> DBResult<Vec<(String, i64)>> {
+        self.with_transaction(|transaction| {
+            Box::pin(async move {
+                let tables: Vec<String> = sqlx::query_scalar(
+                    "SELECT name FROM sqlite_master \
+                     WHERE type = 'table' AND name NOT LIKE 'sqlite_%' \
+                     ORDER BY name",
+                )
+                .fetch_all(&mut **transaction)
+                .await?;
 
-        Ok(Self {
-            connection_pool,
-            version,
+                let mut row_counts = Vec::with_capacity(tables.len());
+                for table in tables {
+                    let quoted_table = table.replace('"', "\"\"");
+                    let row_count: i64 = sqlx::query_scalar(&format!(
+                        "SELECT COUNT(*) FROM \"{quoted_table}\""
+                    ))
+                    .fetch_one(&mut **transaction)
+                    .await?;
+                    row_counts.push((table, row_count));
+                }
+                Ok(row_counts)
+            })
         })
+        .await
+    }
+
+    /// Inserts a small set of sample data.
+    ///
+    /// Returns an error if the database already contains any ingredients,
+    /// categories or recipes.
+    pub async fn seed(&self) -> DBResult<()> {
+        self.with_transaction(|transaction| {
+            Box::pin(async move {
+                let row_count: i64 = sqlx::query_scalar(
+                    "SELECT (SELECT COUNT(*) FROM ingredients) \
+                     + (SELECT COUNT(*) FROM categories) \
+                     + (SELECT COUNT(*) FROM recipes)",
+                )
+                .fetch_one(&mut **transaction)
+                .await?;
+                if row_count != 0 {
+                    return Err(Error::BadArguments(
+                        "Sample data can only be added to an empty database"
+                            .to_owned(),
+                    ));
+                }
+
+                (&mut **transaction).execute(SAMPLE_DATA_SQL).await?;
+                Ok(())
+            })
+        })
+        .await
     }
 
     /// Returns the current migration version of the database.
     ///
-    /// This is the latest known version at the time of `Database` creation.
+    /// This is the version reached by the last migration run through this
+    /// `Database`, or the version at connection time if none has been run.
     pub fn get_version(&self) -> i64 {
         self.version
     }
@@ -86,15 +366,52 @@ impl Database {
     /// aborted.
     pub async fn with_transaction<T, Func>(&self, action: Func) -> DBResult<T>
     where
-        Func: for<'a> FnOnce(
-            &'a mut Transaction<'static, Any>,
+        Func: for<'a, 'c> FnOnce(
+            &'a mut Transaction<'c, Any>,
         ) -> Pin<
             Box<dyn Send + Future<Output = Result<T, Error>> + 'a>,
         >,
     {
-        let mut transaction = self.connection_pool.begin().await?;
-        let result = action(&mut transaction).await?;
-        transaction.commit().await?;
-        Ok(result)
+        let start = Instant::now();
+        let mut connection = self.connection_pool.acquire().await?;
+        metrics::record_pool_wait(start.elapsed());
+
+        // Mark the connection before `BEGIN` and unmark it after `COMMIT` or
+        // `ROLLBACK`, so that every statement is logged with the request's ID.
+        let markers = logging::request_markers();
+        let result = async {
+            if let Some(markers) = &markers {
+                (&mut *connection)
+                    .execute(markers.start_statement().as_str())
+                    .await?;
+            }
+            let mut transaction = connection.begin().await?;
+            match action(&mut transaction).await {
+                Ok(result) => {
+                    transaction.commit().await?;
+                    Ok(result)
+                }
+                Err(error) => {
+                    if let Err(rollback_error) = transaction.rollback().await {
+                        warn!(
+                            "Could not roll back transaction: {rollback_error}"
+                        );
+                    }
+                    Err(error)
+                }
+            }
+        }
+        .await;
+        if let Some(markers) = markers {
+            let end = (&mut *connection)
+                .execute(logging::RequestMarkers::end_statement().as_str())
+                .await;
+            if end.is_ok() {
+                markers.ended();
+            }
+        }
+
+        metrics::record_transaction(start.elapsed(), result.is_ok());
+        result
     }
 }
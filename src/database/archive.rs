@@ -0,0 +1,683 @@
+use std::collections::HashMap;
+
+use chrono::{offset::Utc, DateTime};
+use serde::{Deserialize, Serialize};
+use sqlx::{Any, Transaction};
+
+use super::{DBResult, Error};
+
+/// The version of the archive format produced by `Archive::export`.
+///
+/// This must be incremented whenever the format changes in a way that older
+/// versions of the server could not import.
+pub const ARCHIVE_FORMAT_VERSION: i64 = 1;
+
+/// A row of the `ingredients` table.
+#[derive(Deserialize, Serialize)]
+pub struct IngredientRow {
+    pub id: i64,
+    pub name: String,
+    pub energy_density: f64,
+}
+
+/// A row of the `categories` table.
+#[derive(Deserialize, Serialize)]
+pub struct CategoryRow {
+    pub id: i64,
+    pub name: String,
+}
+
+/// A row of the `recipes` table.
+#[derive(Deserialize, Serialize)]
+pub struct RecipeRow {
+    pub id: i64,
+    pub name: String,
+    pub hidden: bool,
+}
+
+/// A row of the `recipes_versions` table.
+#[derive(Deserialize, Serialize)]
+pub struct RecipeVersionRow {
+    pub recipe_id: i64,
+    pub version_id: i64,
+    pub created: i64,  // Seconds since the Unix epoch.
+    pub duration: i64, // In seconds.
+}
+
+/// A row of the `recipes_ingredients` table.
+#[derive(Deserialize, Serialize)]
+pub struct RecipeIngredientRow {
+    pub recipe_id: i64,
+    pub version_id: i64,
+    pub ingredient_id: i64,
+    pub list_order: i64,
+    pub quantity: f64,
+    pub measurement: i64,
+}
+
+/// A row of the `recipes_instructions` table.
+#[derive(Deserialize, Serialize)]
+pub struct RecipeInstructionRow {
+    pub recipe_id: i64,
+    pub version_id: i64,
+    pub step_number: i64,
+    pub step_text: String,
+}
+
+/// A row of the `recipes_categories` table.
+#[derive(Deserialize, Serialize)]
+pub struct RecipeCategoryRow {
+    pub recipe_id: i64,
+    pub category_id: i64,
+}
+
+/// A complete copy of the data in a recipes database, which can be written
+/// to and read from a single JSON document.
+#[derive(Deserialize, Serialize)]
+pub struct Archive {
+    /// The version of the archive format (`ARCHIVE_FORMAT_VERSION` when
+    /// exported by this version of the server).
+    pub format_version: i64,
+
+    /// The migration version of the database that the archive was exported
+    /// from.
+    pub schema_version: i64,
+
+    /// When the archive was exported.
+    pub exported: DateTime<Utc>,
+
+    pub ingredients: Vec<IngredientRow>,
+    pub categories: Vec<CategoryRow>,
+    pub recipes: Vec<RecipeRow>,
+    pub recipe_versions: Vec<RecipeVersionRow>,
+    pub recipe_ingredients: Vec<RecipeIngredientRow>,
+    pub recipe_instructions: Vec<RecipeInstructionRow>,
+    pub recipe_categories: Vec<RecipeCategoryRow>,
+}
+
+/// A summary of what was restored by `Archive::import`.
+#[derive(Serialize)]
+pub struct ImportSummary {
+    /// Whether the archive's IDs were kept. IDs are only kept when importing
+    /// into an empty database.
+    pub ids_preserved: bool,
+
+    pub ingredients: usize,
+    pub categories: usize,
+    pub recipes: usize,
+    pub recipe_versions: usize,
+}
+
+/// Returns the ID that should follow the largest ID in `table`.
+async fn next_id(
+    transaction: &mut Transaction<'_, Any>,
+    table: &str,
+) -> DBResult<i64> {
+    let last_id: Option<i64> = sqlx::query_scalar(&format!(
+        "SELECT id FROM {table} ORDER BY id DESC LIMIT 1"
+    ))
+    .fetch_optional(&mut **transaction)
+    .await?;
+    Ok(last_id.map_or(0, |id| id + 1))
+}
+
+/// Looks up the new ID of the row that had the ID `id` in the archive.
+fn remap(ids: &HashMap<i64, i64>, kind: &str, id: i64) -> DBResult<i64> {
+    ids.get(&id).copied().ok_or_else(|| {
+        Error::BadArguments(format!("Archive refers to unknown {kind} {id}"))
+    })
+}
+
+/// Chooses the ID for a row with a unique name that had the ID `archive_id`
+/// in the archive, and returns it along with whether the row still needs to be
+/// inserted.
+///
+/// Unless `preserve_ids` is set, rows are matched to existing rows of `table`
+/// by name (ignoring case), or are given the ID `next_id`, which is then
+/// incremented.
+async fn choose_named_id(
+    transaction: &mut Transaction<'_, Any>,
+    table: &str,
+    name: &str,
+    archive_id: i64,
+    preserve_ids: bool,
+    next_id: &mut i64,
+) -> DBResult<(i64, bool)> {
+    if preserve_ids {
+        return Ok((archive_id, true));
+    }
+
+    let existing_id: Option<i64> = sqlx::query_scalar(&format!(
+        "SELECT id FROM {table} WHERE LOWER(name) = LOWER($1) \
+         ORDER BY id LIMIT 1"
+    ))
+    .bind(name)
+    .fetch_optional(&mut **transaction)
+    .await?;
+
+    Ok(existing_id.map_or_else(
+        || {
+            *next_id += 1;
+            (*next_id - 1, true)
+        },
+        |id| (id, false),
+    ))
+}
+
+impl Archive {
+    /// Exports all data in the database using `transaction`, including
+    /// hidden recipes.
+    ///
+    /// `schema_version` is the current migration version of the database.
+    pub async fn export(
+        transaction: &mut Transaction<'_, Any>,
+        schema_version: i64,
+    ) -> DBResult<Self> {
+        let mut archive = Self {
+            format_version: ARCHIVE_FORMAT_VERSION,
+            schema_version,
+            exported: Utc::now(),
+            ingredients: vec![],
+            categories: vec![],
+            recipes: vec![],
+            recipe_versions: vec![],
+            recipe_ingredients: vec![],
+            recipe_instructions: vec![],
+            recipe_categories: vec![],
+        };
+        archive.export_named(transaction).await?;
+        archive.export_recipes(transaction).await?;
+        Ok(archive)
+    }
+
+    /// Exports the ingredients and categories tables.
+    async fn export_named(
+        &mut self,
+        transaction: &mut Transaction<'_, Any>,
+    ) -> DBResult<()> {
+        let ingredients: Vec<(i64, String, f64)> = sqlx::query_as(
+            "SELECT id, name, energy_density FROM ingredients ORDER BY id",
+        )
+        .fetch_all(&mut **transaction)
+        .await?;
+        self.ingredients = ingredients
+            .into_iter()
+            .map(|(id, name, energy_density)| IngredientRow {
+                id,
+                name,
+                energy_density,
+            })
+            .collect();
+
+        let categories: Vec<(i64, String)> =
+            sqlx::query_as("SELECT id, name FROM categories ORDER BY id")
+                .fetch_all(&mut **transaction)
+                .await?;
+        self.categories = categories
+            .into_iter()
+            .map(|(id, name)| CategoryRow { id, name })
+            .collect();
+
+        Ok(())
+    }
+
+    /// Exports the recipes table and the tables that depend on it.
+    async fn export_recipes(
+        &mut self,
+        transaction: &mut Transaction<'_, Any>,
+    ) -> DBResult<()> {
+        // The Any driver cannot decode BOOLEAN or DATETIME columns, so
+        // `hidden` and `created` are cast.
+        let recipes: Vec<(i64, String, i64)> = sqlx::query_as(
+            "SELECT id, name, CAST(hidden AS INTEGER) FROM recipes ORDER BY id",
+        )
+        .fetch_all(&mut **transaction)
+        .await?;
+        self.recipes = recipes
+            .into_iter()
+            .map(|(id, name, hidden)| RecipeRow {
+                id,
+                name,
+                hidden: hidden != 0,
+            })
+            .collect();
+
+        let versions: Vec<(i64, i64, i64, i64)> = sqlx::query_as(
+            "SELECT recipe_id, version_id, CAST(created AS INTEGER), \
+             duration FROM recipes_versions ORDER BY recipe_id, version_id",
+        )
+        .fetch_all(&mut **transaction)
+        .await?;
+        self.recipe_versions = versions
+            .into_iter()
+            .map(|(recipe_id, version_id, created, duration)| {
+                RecipeVersionRow {
+                    recipe_id,
+                    version_id,
+                    created,
+                    duration,
+                }
+            })
+            .collect();
+
+        let ingredients: Vec<(i64, i64, i64, i64, f64, i64)> = sqlx::query_as(
+            "SELECT recipe_id, version_id, ingredient_id, list_order, \
+             quantity, measurement FROM recipes_ingredients \
+             ORDER BY recipe_id, version_id, list_order",
+        )
+        .fetch_all(&mut **transaction)
+        .await?;
+        self.recipe_ingredients = ingredients
+            .into_iter()
+            .map(|row| RecipeIngredientRow {
+                recipe_id: row.0,
+                version_id: row.1,
+                ingredient_id: row.2,
+                list_order: row.3,
+                quantity: row.4,
+                measurement: row.5,
+            })
+            .collect();
+
+        let instructions: Vec<(i64, i64, i64, String)> = sqlx::query_as(
+            "SELECT recipe_id, version_id, step_number, step_text \
+             FROM recipes_instructions \
+             ORDER BY recipe_id, version_id, step_number",
+        )
+        .fetch_all(&mut **transaction)
+        .await?;
+        self.recipe_instructions = instructions
+            .into_iter()
+            .map(|(recipe_id, version_id, step_number, step_text)| {
+                RecipeInstructionRow {
+                    recipe_id,
+                    version_id,
+                    step_number,
+                    step_text,
+                }
+            })
+            .collect();
+
+        let categories: Vec<(i64, i64)> = sqlx::query_as(
+            "SELECT recipe_id, category_id FROM recipes_categories \
+             ORDER BY recipe_id, category_id",
+        )
+        .fetch_all(&mut **transaction)
+        .await?;
+        self.recipe_categories = categories
+            .into_iter()
+            .map(|(recipe_id, category_id)| RecipeCategoryRow {
+                recipe_id,
+                category_id,
+            })
+            .collect();
+
+        Ok(())
+    }
+
+    /// Restores the data in this archive using `transaction`.
+    ///
+    /// If the database is empty, all IDs from the archive are preserved.
+    /// Otherwise, the archive's recipes are given new IDs, and its ingredients
+    /// and categories are matched to existing ones by name (ignoring case) or
+    /// given new IDs.
+    ///
+    /// `schema_version` is the current migration version of the database.
+    /// Archives exported from newer databases are rejected.
+    pub async fn import(
+        self,
+        transaction: &mut Transaction<'_, Any>,
+        schema_version: i64,
+    ) -> DBResult<ImportSummary> {
+        if self.format_version != ARCHIVE_FORMAT_VERSION {
+            return Err(Error::BadArguments(format!(
+                "Unsupported archive format version {}",
+                self.format_version
+            )));
+        }
+        if self.schema_version > schema_version {
+            return Err(Error::BadArguments(format!(
+                "Archive is from a newer database (version {})",
+                self.schema_version
+            )));
+        }
+
+        let existing_rows: i64 = sqlx::query_scalar(
+            "SELECT (SELECT COUNT(id) FROM ingredients) \
+             + (SELECT COUNT(id) FROM categories) \
+             + (SELECT COUNT(id) FROM recipes)",
+        )
+        .fetch_one(&mut **transaction)
+        .await?;
+        let preserve_ids = existing_rows == 0;
+
+        let mut ingredient_ids = HashMap::new();
+        let mut next_ingredient_id =
+            next_id(transaction, "ingredients").await?;
+        for ingredient in &self.ingredients {
+            let (id, is_new) = choose_named_id(
+                transaction,
+                "ingredients",
+                &ingredient.name,
+                ingredient.id,
+                preserve_ids,
+                &mut next_ingredient_id,
+            )
+            .await?;
+            if is_new {
+                sqlx::query(
+                    "INSERT INTO ingredients (id, name, energy_density) \
+                     VALUES ($1, $2, $3)",
+                )
+                .bind(id)
+                .bind(&ingredient.name)
+                .bind(ingredient.energy_density)
+                .execute(&mut **transaction)
+                .await?;
+            }
+            ingredient_ids.insert(ingredient.id, id);
+        }
+
+        let mut category_ids = HashMap::new();
+        let mut next_category_id = next_id(transaction, "categories").await?;
+        for category in &self.categories {
+            let (id, is_new) = choose_named_id(
+                transaction,
+                "categories",
+                &category.name,
+                category.id,
+                preserve_ids,
+                &mut next_category_id,
+            )
+            .await?;
+            if is_new {
+                sqlx::query(
+                    "INSERT INTO categories (id, name) VALUES ($1, $2)",
+                )
+                .bind(id)
+                .bind(&category.name)
+                .execute(&mut **transaction)
+                .await?;
+            }
+            category_ids.insert(category.id, id);
+        }
+
+        self.import_recipes(
+            transaction,
+            preserve_ids,
+            &ingredient_ids,
+            &category_ids,
+        )
+        .await?;
+
+        Ok(ImportSummary {
+            ids_preserved: preserve_ids,
+            ingredients: self.ingredients.len(),
+            categories: self.categories.len(),
+            recipes: self.recipes.len(),
+            recipe_versions: self.recipe_versions.len(),
+        })
+    }
+
+    /// Restores the recipes table and the tables that depend on it, given the
+    /// new IDs of the archive's ingredients and categories.
+    async fn import_recipes(
+        &self,
+        transaction: &mut Transaction<'_, Any>,
+        preserve_ids: bool,
+        ingredient_ids: &HashMap<i64, i64>,
+        category_ids: &HashMap<i64, i64>,
+    ) -> DBResult<()> {
+        let mut recipe_ids = HashMap::new();
+        let mut next_recipe_id = next_id(transaction, "recipes").await?;
+        for recipe in &self.recipes {
+            let id = if preserve_ids {
+                recipe.id
+            } else {
+                next_recipe_id += 1;
+                next_recipe_id - 1
+            };
+            sqlx::query(
+                "INSERT INTO recipes (id, name, hidden) VALUES ($1, $2, $3)",
+            )
+            .bind(id)
+            .bind(&recipe.name)
+            .bind(recipe.hidden)
+            .execute(&mut **transaction)
+            .await?;
+            recipe_ids.insert(recipe.id, id);
+        }
+
+        for version in &self.recipe_versions {
+            sqlx::query(
+                "INSERT INTO recipes_versions \
+                 (recipe_id, version_id, created, duration) \
+                 VALUES ($1, $2, $3, $4)",
+            )
+            .bind(remap(&recipe_ids, "recipe", version.recipe_id)?)
+            .bind(version.version_id)
+            .bind(version.created)
+            .bind(version.duration)
+            .execute(&mut **transaction)
+            .await?;
+        }
+
+        for ingredient in &self.recipe_ingredients {
+            sqlx::query(
+                "INSERT INTO recipes_ingredients \
+                 (recipe_id, version_id, ingredient_id, list_order, \
+                 quantity, measurement) \
+                 VALUES ($1, $2, $3, $4, $5, $6)",
+            )
+            .bind(remap(&recipe_ids, "recipe", ingredient.recipe_id)?)
+            .bind(ingredient.version_id)
+            .bind(remap(
+                ingredient_ids,
+                "ingredient",
+                ingredient.ingredient_id,
+            )?)
+            .bind(ingredient.list_order)
+            .bind(ingredient.quantity)
+            .bind(ingredient.measurement)
+            .execute(&mut **transaction)
+            .await?;
+        }
+
+        for instruction in &self.recipe_instructions {
+            sqlx::query(
+                "INSERT INTO recipes_instructions \
+                 (recipe_id, version_id, step_number, step_text) \
+                 VALUES ($1, $2, $3, $4)",
+            )
+            .bind(remap(&recipe_ids, "recipe", instruction.recipe_id)?)
+            .bind(instruction.version_id)
+            .bind(instruction.step_number)
+            .bind(&instruction.step_text)
+            .execute(&mut **transaction)
+            .await?;
+        }
+
+        for link in &self.recipe_categories {
+            sqlx::query(
+                "INSERT INTO recipes_categories (recipe_id, category_id) \
+                 VALUES ($1, $2)",
+            )
+            .bind(remap(&recipe_ids, "recipe", link.recipe_id)?)
+            .bind(remap(category_ids, "category", link.category_id)?)
+            .execute(&mut **transaction)
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::Executor;
+
+    use super::*;
+    use crate::config::DatabaseConfig;
+    use crate::database::Database;
+
+    /// Creates an empty in-memory database.
+    async fn empty_database() -> Database {
+        Database::new(DatabaseConfig {
+            connection_url: "sqlite::memory:".to_owned(),
+            max_connections: 1,
+        })
+        .await
+        .unwrap()
+    }
+
+    /// Creates an in-memory database holding the sample data and a hidden
+    /// recipe.
+    async fn filled_database() -> Database {
+        let database = empty_database().await;
+        database.seed().await.unwrap();
+        database
+            .with_transaction(|transaction| {
+                Box::pin(async move {
+                    (&mut **transaction)
+                        .execute(
+                            "INSERT INTO recipes VALUES (7, 'Secret', true);
+                             INSERT INTO recipes_versions
+                             VALUES (7, 0, 1688701914, 60);
+                             INSERT INTO recipes_ingredients
+                             VALUES (7, 0, 628, 0, 2, 0);",
+                        )
+                        .await?;
+                    Ok(())
+                })
+            })
+            .await
+            .unwrap();
+        database
+    }
+
+    async fn export(database: &Database) -> Archive {
+        let schema_version = database.get_version();
+        database
+            .with_transaction(move |transaction| {
+                Box::pin(async move {
+                    Archive::export(transaction, schema_version).await
+                })
+            })
+            .await
+            .unwrap()
+    }
+
+    async fn import(
+        database: &Database,
+        archive: Archive,
+    ) -> DBResult<ImportSummary> {
+        let schema_version = database.get_version();
+        database
+            .with_transaction(move |transaction| {
+                Box::pin(async move {
+                    archive.import(transaction, schema_version).await
+                })
+            })
+            .await
+    }
+
+    /// Returns `archive` as JSON, without the time it was exported.
+    fn contents(archive: &Archive) -> serde_json::Value {
+        let mut value = serde_json::to_value(archive).unwrap();
+        value["exported"] = serde_json::Value::Null;
+        value
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_into_empty_database() {
+        let archive = export(&filled_database().await).await;
+        assert_eq!(archive.recipes.len(), 2);
+        assert!(archive.recipes.iter().any(|recipe| recipe.hidden));
+
+        let database = empty_database().await;
+        let summary = import(&database, export(&filled_database().await).await)
+            .await
+            .unwrap();
+        assert!(summary.ids_preserved);
+        assert_eq!(summary.recipes, 2);
+        assert_eq!(contents(&export(&database).await), contents(&archive));
+    }
+
+    #[tokio::test]
+    async fn test_import_into_existing_database() {
+        let database = filled_database().await;
+        let summary = import(&database, export(&database).await).await.unwrap();
+        assert!(!summary.ids_preserved);
+
+        let archive = export(&database).await;
+        // Ingredients and categories are matched by name, while recipes are
+        // copied with new IDs.
+        assert_eq!(archive.ingredients.len(), 1);
+        assert_eq!(archive.categories.len(), 1);
+        let recipe_ids = archive
+            .recipes
+            .iter()
+            .map(|recipe| (recipe.id, recipe.name.as_str(), recipe.hidden))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            recipe_ids,
+            vec![
+                (7, "Secret", true),
+                (314, "Cherry cheese pie", false),
+                (315, "Secret", true),
+                (316, "Cherry cheese pie", false),
+            ]
+        );
+
+        let ingredient_links = archive
+            .recipe_ingredients
+            .iter()
+            .map(|link| (link.recipe_id, link.ingredient_id))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ingredient_links,
+            vec![(7, 628), (314, 628), (315, 628), (316, 628)]
+        );
+        let category_links = archive
+            .recipe_categories
+            .iter()
+            .map(|link| (link.recipe_id, link.category_id))
+            .collect::<Vec<_>>();
+        assert_eq!(category_links, vec![(314, 2718), (316, 2718)]);
+        let instruction_recipes = archive
+            .recipe_instructions
+            .iter()
+            .map(|instruction| instruction.recipe_id)
+            .collect::<Vec<_>>();
+        assert_eq!(instruction_recipes, vec![314, 316]);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_archives() {
+        let database = empty_database().await;
+
+        let mut archive = export(&filled_database().await).await;
+        archive.format_version = ARCHIVE_FORMAT_VERSION + 1;
+        assert!(matches!(
+            import(&database, archive).await,
+            Err(Error::BadArguments(_))
+        ));
+
+        let mut archive = export(&filled_database().await).await;
+        archive.schema_version = database.get_version() + 1;
+        assert!(matches!(
+            import(&database, archive).await,
+            Err(Error::BadArguments(_))
+        ));
+
+        let mut archive = export(&filled_database().await).await;
+        archive.recipe_ingredients[0].ingredient_id = 1;
+        assert!(matches!(
+            import(&database, archive).await,
+            Err(Error::BadArguments(_))
+        ));
+
+        // Nothing is imported from rejected archives.
+        assert!(export(&database).await.recipes.is_empty());
+    }
+}
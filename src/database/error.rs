@@ -7,6 +7,18 @@ pub enum Error {
     Sql(sqlx::Error),
 }
 
+impl Error {
+    /// Returns whether this error is from a query on a table that doesn't
+    /// exist, e.g. because the database's tables haven't been created.
+    pub fn is_missing_table(&self) -> bool {
+        matches!(
+            self,
+            Self::Sql(sqlx::Error::Database(error))
+                if error.message().starts_with("no such table")
+        )
+    }
+}
+
 impl From<sqlx::Error> for Error {
     fn from(value: sqlx::Error) -> Self {
         Self::Sql(value)
@@ -1,18 +1,68 @@
-use std::collections::HashMap;
-use std::pin::Pin;
-
-use log::debug;
+use chrono::offset::Utc;
+use log::{debug, info};
 use sqlx::any::Any;
-use sqlx::{Pool, Transaction};
+use sqlx::{Executor, Pool};
+
+use super::{DBResult, Error};
+
+/// The SQL that creates the database's tables at version 0.
+const SCHEMA_SQL: &str = include_str!("../../setup/create_tables.sql");
+
+/// A migration embedded into the binary by the build script from the
+/// `migrations/` directory.
+struct Migration {
+    /// The version that the migration migrates to, from the version before.
+    version: i64,
+
+    description: &'static str,
+
+    /// The SQL that applies the migration.
+    up: &'static str,
+
+    /// The SQL that rolls the migration back, if it can be rolled back.
+    down: Option<&'static str>,
+
+    /// The SHA-256 hash of `up`, recorded when the migration is applied.
+    checksum: &'static str,
+}
+
+/// All migrations, ordered by version. Versions are consecutive from 1.
+static MIGRATIONS: &[Migration] =
+    include!(concat!(env!("OUT_DIR"), "/migrations.rs"));
+
+/// Returns the migration to version `version`.
+fn get_migration(version: i64) -> DBResult<&'static Migration> {
+    MIGRATIONS
+        .iter()
+        .find(|migration| migration.version == version)
+        .ok_or_else(|| {
+            Error::Internal(format!("No migration to version {version}"))
+        })
+}
 
-use super::{DBFut, DBResult};
+/// Returns the latest version that the migrations can reach.
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
 
-// TODO: Switch this file to use sqlx's built-in migrations.
-type Migration =
-    dyn Fn(&mut Transaction<'static, Any>) -> Pin<Box<dyn DBFut<i64>>>;
+/// A single migration step, from one version to an adjacent one. The step
+/// rolls a migration back if `to_version` is less than `from_version`.
+#[derive(Clone, Copy)]
+pub struct MigrationStep {
+    pub from_version: i64,
+    pub to_version: i64,
+    pub description: &'static str,
+}
 
-fn get_migrations() -> HashMap<i64, Box<Migration>> {
-    HashMap::<i64, Box<Migration>>::new()
+/// Returns whether the database has a table named `name`.
+async fn has_table(connection_pool: &Pool<Any>, name: &str) -> DBResult<bool> {
+    let count: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = $1",
+    )
+    .bind(name)
+    .fetch_one(connection_pool)
+    .await?;
+    Ok(count != 0)
 }
 
 pub struct Migrator<'a> {
@@ -21,18 +71,141 @@ pub struct Migrator<'a> {
 }
 
 impl<'a> Migrator<'a> {
+    /// Creates the tables used to track migrations, and the database's tables
+    /// if the database is empty, so that migrations can be run on it.
+    ///
+    /// A database is treated as empty if its `db_version` table is missing
+    /// or has no rows. Returns whether the database's tables were created.
+    pub async fn bootstrap(connection_pool: &Pool<Any>) -> DBResult<bool> {
+        let mut transaction = connection_pool.begin().await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS db_version \
+             (version INTEGER PRIMARY KEY NOT NULL)",
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS schema_migrations (\
+             version INTEGER PRIMARY KEY NOT NULL, \
+             description TEXT NOT NULL, \
+             checksum TEXT NOT NULL, \
+             applied INTEGER NOT NULL)",
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        let version_count: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM db_version")
+                .fetch_one(&mut *transaction)
+                .await?;
+        let created = version_count == 0;
+        if created {
+            info!("Creating tables in empty database");
+            (&mut *transaction).execute(SCHEMA_SQL).await?;
+        }
+
+        transaction.commit().await?;
+        Ok(created)
+    }
+
+    /// Reads the database's current version and checks that the migrations
+    /// that have been applied to it are the ones embedded in this binary.
+    ///
+    /// Databases created before migrations were tracked have no
+    /// `schema_migrations` table, and are treated as having none applied.
+    ///
+    /// Returns an error if the database is newer than the latest migration,
+    /// or if any applied migration has been changed since it was applied.
     pub async fn new(connection_pool: &'a Pool<Any>) -> DBResult<Migrator<'a>> {
         let current_version: i64 =
             sqlx::query_scalar("SELECT version FROM db_version")
                 .fetch_one(connection_pool)
                 .await?;
+
+        if current_version > latest_version() {
+            return Err(Error::Internal(format!(
+                "Database version {current_version} is newer than the latest \
+                 version known to this server ({})",
+                latest_version()
+            )));
+        }
+
+        let applied: Vec<(i64, String)> =
+            if has_table(connection_pool, "schema_migrations").await? {
+                sqlx::query_as(
+                    "SELECT version, checksum FROM schema_migrations \
+                     ORDER BY version",
+                )
+                .fetch_all(connection_pool)
+                .await?
+            } else {
+                vec![]
+            };
+        for (version, checksum) in applied {
+            let migration = get_migration(version)?;
+            if migration.checksum != checksum {
+                return Err(Error::Internal(format!(
+                    "Migration {version} ({}) has been changed since it was \
+                     applied",
+                    migration.description
+                )));
+            }
+        }
+
         Ok(Self {
             connection_pool,
             current_version,
         })
     }
 
-    pub async fn run_migrations(&mut self) -> DBResult<()> {
+    /// Returns the steps needed to migrate from the current version to
+    /// `target_version`, or to the latest version if it is `None`.
+    ///
+    /// Returns an error if `target_version` is unknown, or if reaching it
+    /// requires rolling back a migration that cannot be rolled back.
+    pub fn plan(
+        &self,
+        target_version: Option<i64>,
+    ) -> DBResult<Vec<MigrationStep>> {
+        let target_version = target_version.unwrap_or_else(latest_version);
+        if !(0..=latest_version()).contains(&target_version) {
+            return Err(Error::BadArguments(format!(
+                "Unknown version {target_version}; versions range from 0 to {}",
+                latest_version()
+            )));
+        }
+
+        let mut steps = vec![];
+        for version in self.current_version + 1..=target_version {
+            steps.push(MigrationStep {
+                from_version: version - 1,
+                to_version: version,
+                description: get_migration(version)?.description,
+            });
+        }
+        for version in (target_version + 1..=self.current_version).rev() {
+            let migration = get_migration(version)?;
+            if migration.down.is_none() {
+                return Err(Error::BadArguments(format!(
+                    "Migration {version} ({}) cannot be rolled back",
+                    migration.description
+                )));
+            }
+            steps.push(MigrationStep {
+                from_version: version,
+                to_version: version - 1,
+                description: migration.description,
+            });
+        }
+        Ok(steps)
+    }
+
+    /// Migrates to `target_version`, or to the latest version if it is
+    /// `None`, in a single transaction. Returns the steps that were applied.
+    pub async fn run_migrations(
+        &mut self,
+        target_version: Option<i64>,
+    ) -> DBResult<Vec<MigrationStep>> {
         let starting_version = self.current_version;
 
         debug!(
@@ -40,20 +213,46 @@ impl<'a> Migrator<'a> {
                 {starting_version}"
         );
 
-        let migrations = get_migrations();
+        let steps = self.plan(target_version)?;
 
+        let mut version = starting_version;
         let mut transaction = self.connection_pool.begin().await?;
-        while let Some(migration) = migrations.get(&self.current_version) {
-            debug!("Applying migration from version {}", self.current_version);
-            let new_version = migration(&mut transaction).await?;
-            assert!(new_version > self.current_version);
-            debug!("After applying migration, version is now {new_version}");
-            self.current_version = new_version;
+        for step in &steps {
+            if step.to_version > step.from_version {
+                debug!("Applying migration to version {}", step.to_version);
+                let migration = get_migration(step.to_version)?;
+                (&mut *transaction).execute(migration.up).await?;
+                sqlx::query(
+                    "INSERT INTO schema_migrations \
+                     (version, description, checksum, applied) \
+                     VALUES ($1, $2, $3, $4)",
+                )
+                .bind(migration.version)
+                .bind(migration.description)
+                .bind(migration.checksum)
+                .bind(Utc::now().timestamp())
+                .execute(&mut *transaction)
+                .await?;
+            } else {
+                debug!(
+                    "Rolling back migration to version {}",
+                    step.from_version
+                );
+                let migration = get_migration(step.from_version)?;
+                if let Some(down) = migration.down {
+                    (&mut *transaction).execute(down).await?;
+                }
+                sqlx::query("DELETE FROM schema_migrations WHERE version = $1")
+                    .bind(migration.version)
+                    .execute(&mut *transaction)
+                    .await?;
+            }
+            version = step.to_version;
         }
         let version_update_result = sqlx::query(
             "UPDATE db_version SET version = $1 WHERE version = $2",
         )
-        .bind(self.current_version)
+        .bind(version)
         .bind(starting_version)
         .execute(&mut *transaction)
         .await?;
@@ -63,10 +262,47 @@ impl<'a> Migrator<'a> {
 
         transaction.commit().await?;
 
-        Ok(())
+        // Only recorded once committed, as a failed migration is rolled back.
+        self.current_version = version;
+        debug!("Database version is now {version}");
+
+        Ok(steps)
     }
 
     pub fn get_current_version(&self) -> i64 {
         self.current_version
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+
+    #[tokio::test]
+    async fn test_dry_run_before_migrations_were_tracked() {
+        // A database set up by the schema script alone, as databases were
+        // before migrations were tracked.
+        let connection_pool =
+            Database::connect_pool("sqlite::memory:", 1).await.unwrap();
+        connection_pool.execute(SCHEMA_SQL).await.unwrap();
+        assert!(!has_table(&connection_pool, "schema_migrations")
+            .await
+            .unwrap());
+
+        let mut database =
+            Database::from_pool(connection_pool, "sqlite::memory:", false)
+                .await
+                .unwrap();
+        assert_eq!(database.get_version(), 0);
+        let (steps, backup_path) = database.migrate(None, true).await.unwrap();
+        assert_eq!(steps.len(), usize::try_from(latest_version()).unwrap());
+        assert!(backup_path.is_none());
+
+        // Nothing was changed.
+        assert_eq!(database.read_version().await.unwrap(), 0);
+        assert!(!has_table(&database.connection_pool, "schema_migrations")
+            .await
+            .unwrap());
+    }
+}
@@ -0,0 +1,17 @@
+mod cooklang;
+mod ingredientline;
+mod mealmaster;
+mod parsedrecipe;
+mod pdf;
+mod plaintext;
+mod textdecode;
+mod textrender;
+
+pub use cooklang::{parse_cooklang, to_cooklang};
+pub use ingredientline::{name_similarity, parse_ingredient_line};
+pub use mealmaster::{is_mealmaster, parse_mealmaster};
+pub use parsedrecipe::{BulkEntry, ParsedIngredient, ParsedRecipe};
+pub use pdf::to_pdf;
+pub use plaintext::parse_plain_text;
+pub use textdecode::decode_text;
+pub use textrender::{ingredient_entry, to_markdown, to_plain_text};
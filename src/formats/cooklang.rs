@@ -0,0 +1,637 @@
+//! Conversion between [Cooklang](https://cooklang.org) text and
+//! `ParsedRecipe`s.
+
+use std::fmt::Write;
+
+use chrono::Duration;
+
+use super::{ParsedIngredient, ParsedRecipe};
+use crate::models::MeasurementType;
+use crate::units::{
+    format_amount, format_duration, lookup_unit, parse_amount, parse_duration,
+    to_human, to_si,
+};
+
+/// Metadata keys that hold the name of the recipe.
+const NAME_KEYS: &[&str] = &["title", "name"];
+
+/// Metadata keys that hold (comma-separated) category names.
+const CATEGORY_KEYS: &[&str] =
+    &["tags", "tag", "categories", "category", "course"];
+
+/// Metadata keys that hold the total time needed to make the recipe.
+const DURATION_KEYS: &[&str] =
+    &["time", "duration", "total time", "time required"];
+
+/// The metadata key that lists all of the recipe's ingredients, in order, as
+/// `@ingredient{}` markup.
+///
+/// Exports use this to keep the order of the ingredients, and ingredients
+/// that no step mentions.
+const INGREDIENTS_KEY: &str = "ingredients";
+
+/// A piece of Cooklang markup (`@ingredient{}`, `#cookware{}` or `~timer{}`).
+struct Component<'a> {
+    name: &'a str,
+    amount: Option<&'a str>,
+    note: Option<&'a str>,
+}
+
+/// Removes all `[- block comments -]` from `text`.
+fn strip_block_comments(text: &str) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("[-") {
+        result.push_str(&rest[..start]);
+        rest = rest[start..]
+            .find("-]")
+            .map_or("", |end| &rest[start + end + 2..]);
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Removes a `-- line comment` from the end of `line`.
+///
+/// Comments only start at the start of the line or after whitespace, so that
+/// e.g. "bake 20--25 minutes" is kept.
+fn strip_line_comment(line: &str) -> &str {
+    line.match_indices("--")
+        .map(|(start, _)| start)
+        .find(|&start| {
+            line[..start]
+                .chars()
+                .next_back()
+                .is_none_or(char::is_whitespace)
+        })
+        .map_or(line, |start| &line[..start])
+}
+
+/// Removes the backslashes from `text` that escape the character after them.
+fn unescape(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => result.extend(chars.next()),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// Splits `text` at each `separator` that isn't escaped with a backslash.
+fn split_unescaped(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut start = 0;
+    let mut escaped = false;
+    for (index, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == separator {
+            parts.push(&text[start..index]);
+            start = index + c.len_utf8();
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+/// Parses the markup that follows a `@`, `#` or `~` at the start of `text`.
+///
+/// Returns the parsed component and the text after it, or `None` if `text`
+/// does not start with valid markup.
+fn parse_component(text: &str) -> Option<(Component<'_>, &str)> {
+    // Multi-word names extend up to an opening brace, as long as no other
+    // markup starts in between.
+    let multi_word = text.find('{').and_then(|open| {
+        let name = &text[..open];
+        let close = open + text[open..].find('}')?;
+        (!name.contains(['@', '#', '~', '}', '\n'])).then_some((
+            name.trim(),
+            Some(&text[open + 1..close]),
+            &text[close + 1..],
+        ))
+    });
+
+    let (name, amount, mut rest) = multi_word.or_else(|| {
+        let end = text
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+            .unwrap_or(text.len());
+        (end > 0).then_some((&text[..end], None, &text[end..]))
+    })?;
+
+    let mut note = None;
+    if amount.is_some() && rest.starts_with('(') {
+        if let Some(close) = rest.find(')') {
+            note = Some(&rest[1..close]);
+            rest = &rest[close + 1..];
+        }
+    }
+
+    Some((Component { name, amount, note }, rest))
+}
+
+/// Splits a Cooklang amount (e.g. `"250%g"`) into a quantity and a unit.
+fn split_amount(amount: Option<&str>) -> (&str, &str) {
+    let amount = amount.unwrap_or("");
+    let (quantity, unit) = amount.split_once('%').unwrap_or((amount, ""));
+    (quantity.trim().trim_end_matches('*').trim(), unit.trim())
+}
+
+/// Converts the amount of an ingredient component into a `ParsedIngredient`.
+///
+/// Ingredients without a numeric quantity (e.g. `@salt{}`) have a quantity of
+/// zero. Ingredients with an unknown unit are treated as counts.
+fn to_ingredient(component: &Component<'_>) -> ParsedIngredient {
+    let (quantity, unit) = split_amount(component.amount);
+    let amount = parse_amount(quantity).unwrap_or(0.0);
+    let (quantity, measurement) = match lookup_unit(unit) {
+        Some(unit) => (to_si(amount, unit), unit.measurement),
+        None => (amount, MeasurementType::Count),
+    };
+    ParsedIngredient {
+        name: component.name.to_owned(),
+        quantity,
+        measurement,
+    }
+}
+
+/// Parses a step's text, adding its ingredients to `recipe` and the total
+/// length of its timers to `timers`.
+///
+/// Returns the step's text with all markup replaced by plain words, and
+/// escaped characters (e.g. `\@`) unescaped.
+fn parse_step(
+    step: &str,
+    recipe: &mut ParsedRecipe,
+    timers: &mut Duration,
+) -> Result<String, String> {
+    let mut text = String::new();
+    let mut rest = step;
+
+    while let Some(position) = rest.find(['@', '#', '~', '\\']) {
+        text.push_str(&rest[..position]);
+        let marker = &rest[position..=position];
+        let after = &rest[position + 1..];
+
+        if marker == "\\" {
+            let mut chars = after.chars();
+            text.extend(chars.next());
+            rest = chars.as_str();
+            continue;
+        }
+
+        let Some((component, remaining)) =
+            parse_component(after).filter(|(component, _)| {
+                marker == "~" || !component.name.is_empty()
+            })
+        else {
+            text.push_str(marker);
+            rest = after;
+            continue;
+        };
+        rest = remaining;
+
+        match marker {
+            "@" => {
+                text.push_str(component.name);
+                if let Some(note) = component.note {
+                    text.push_str(" (");
+                    text.push_str(note);
+                    text.push(')');
+                }
+                recipe.ingredients.push(to_ingredient(&component));
+            }
+            "#" => text.push_str(component.name),
+            _ => {
+                let (quantity, unit) = split_amount(component.amount);
+                let written = format!("{quantity} {unit}").trim().to_owned();
+                let duration = parse_duration(&written)
+                    .ok_or_else(|| format!("Invalid timer \"{written}\""))?;
+                *timers += duration;
+                text.push_str(&written);
+            }
+        }
+    }
+
+    text.push_str(rest);
+    Ok(text)
+}
+
+/// Applies the metadata entry `key: value` to `recipe`, or to
+/// `listed_ingredients` if it lists the recipe's ingredients.
+///
+/// Unknown keys (e.g. `servings`) are ignored.
+fn apply_metadata(
+    recipe: &mut ParsedRecipe,
+    listed_ingredients: &mut Option<Vec<ParsedIngredient>>,
+    key: &str,
+    value: &str,
+) -> Result<(), String> {
+    let key = key.trim().to_lowercase();
+    let value = value.trim();
+
+    if NAME_KEYS.contains(&key.as_str()) {
+        recipe.name = unescape(value);
+    } else if key == INGREDIENTS_KEY {
+        // Other tools write plain lists (e.g. "flour, sugar") here, which
+        // mark up no ingredients and so don't replace those in the steps.
+        let mut listed = ParsedRecipe::new(String::new());
+        parse_step(value, &mut listed, &mut Duration::zero())?;
+        if !listed.ingredients.is_empty() {
+            *listed_ingredients = Some(listed.ingredients);
+        }
+    } else if CATEGORY_KEYS.contains(&key.as_str()) {
+        let value = value.trim_start_matches('[').trim_end_matches(']');
+        recipe.categories.extend(
+            split_unescaped(value, ',')
+                .into_iter()
+                .map(str::trim)
+                .filter(|category| !category.is_empty())
+                .map(unescape),
+        );
+    } else if DURATION_KEYS.contains(&key.as_str()) {
+        recipe.duration = parse_duration(value)
+            .ok_or_else(|| format!("Invalid {key} \"{value}\""))?;
+    }
+    Ok(())
+}
+
+/// Parses the Cooklang recipe `text`.
+///
+/// The recipe's name is taken from its `title` metadata, or is `default_name`
+/// if there is none. If there is no `time` metadata, the recipe's duration is
+/// the total length of all its timers. The ingredients are those marked up in
+/// `ingredients` metadata, or those marked up in the steps if there are none.
+///
+/// Returns the parsed recipe or a string describing the error.
+pub fn parse_cooklang(
+    text: &str,
+    default_name: &str,
+) -> Result<ParsedRecipe, String> {
+    let text = strip_block_comments(text);
+    let mut recipe = ParsedRecipe::new(default_name.to_owned());
+    let mut has_duration = false;
+    let mut listed_ingredients = None;
+    let mut lines = text.lines().peekable();
+
+    // Skip over YAML-style front matter, reading its simple key-value pairs.
+    while lines.peek().is_some_and(|line| line.trim().is_empty()) {
+        lines.next();
+    }
+    if lines.peek().is_some_and(|line| line.trim() == "---") {
+        lines.next();
+        for line in lines.by_ref() {
+            if line.trim() == "---" {
+                break;
+            }
+            if let Some((key, value)) = line.split_once(':') {
+                has_duration |=
+                    DURATION_KEYS.contains(&key.trim().to_lowercase().as_str());
+                apply_metadata(
+                    &mut recipe,
+                    &mut listed_ingredients,
+                    key,
+                    value,
+                )?;
+            }
+        }
+    }
+
+    // Each paragraph is a step.
+    let mut paragraphs: Vec<String> = vec![String::new()];
+    for line in lines {
+        let line = strip_line_comment(line).trim();
+        if let Some(metadata) = line.strip_prefix(">>") {
+            if let Some((key, value)) = metadata.split_once(':') {
+                has_duration |=
+                    DURATION_KEYS.contains(&key.trim().to_lowercase().as_str());
+                apply_metadata(
+                    &mut recipe,
+                    &mut listed_ingredients,
+                    key,
+                    value,
+                )?;
+            }
+        } else if line.is_empty() || line.starts_with('=') {
+            // Blank lines and section headers both end the current step.
+            paragraphs.push(String::new());
+        } else {
+            let paragraph = paragraphs.last_mut().unwrap();
+            if !paragraph.is_empty() {
+                paragraph.push(' ');
+            }
+            paragraph.push_str(line);
+        }
+    }
+
+    let mut timers = Duration::zero();
+    for paragraph in paragraphs.into_iter().filter(|p| !p.is_empty()) {
+        let step = parse_step(&paragraph, &mut recipe, &mut timers)?;
+        recipe.instructions.push(step);
+    }
+
+    if !has_duration {
+        recipe.duration = timers;
+    }
+    if let Some(ingredients) = listed_ingredients {
+        recipe.ingredients = ingredients;
+    }
+
+    Ok(recipe)
+}
+
+/// Formats `ingredient` as Cooklang markup, spelling its name as `name`.
+fn ingredient_markup(name: &str, ingredient: &ParsedIngredient) -> String {
+    if ingredient.quantity == 0.0 {
+        return format!("@{name}{{}}");
+    }
+    let (amount, unit) = to_human(ingredient.quantity, ingredient.measurement);
+    if unit.is_empty() {
+        format!("@{name}{{{}}}", format_amount(amount))
+    } else {
+        format!("@{name}{{{}%{unit}}}", format_amount(amount))
+    }
+}
+
+/// Returns whether the byte at `index` of `text` is not part of a word.
+fn is_boundary(text: &str, index: usize) -> bool {
+    text[index..]
+        .chars()
+        .next()
+        .is_none_or(|c| !c.is_alphanumeric())
+}
+
+/// Finds the first mention of `name` in `text` that is a whole word and does
+/// not overlap any range in `claimed`.
+fn find_mention(
+    text: &str,
+    name: &str,
+    claimed: &[(usize, usize, usize)],
+) -> Option<usize> {
+    let lowercase_text = text.to_ascii_lowercase();
+    let lowercase_name = name.to_ascii_lowercase();
+    if lowercase_name.is_empty() {
+        return None;
+    }
+
+    lowercase_text
+        .match_indices(&lowercase_name)
+        .map(|(start, _)| start)
+        .find(|&start| {
+            let end = start + name.len();
+            let before_ok = text[..start]
+                .chars()
+                .next_back()
+                .is_none_or(|c| !c.is_alphanumeric());
+            before_ok
+                && is_boundary(text, end)
+                && claimed.iter().all(|&(s, e, _)| end <= s || start >= e)
+        })
+}
+
+/// Returns `text` with a backslash before each character that Cooklang would
+/// otherwise read as markup or as the start of a comment. If `line_start` is
+/// true, `text` starts a line, so a leading `>` or `=` is escaped too.
+fn escape(text: &str, line_start: bool) -> String {
+    let mut escaped = String::new();
+    let mut previous = None;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let needs_escape = match c {
+            '@' | '#' | '~' | '\\' => true,
+            '-' => chars.peek() == Some(&'-') || previous == Some('['),
+            '>' | '=' => line_start && previous.is_none(),
+            _ => false,
+        };
+        if needs_escape {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+        previous = Some(c);
+    }
+    escaped
+}
+
+/// Returns whether `name` can be written as the name in `@name{}` markup.
+fn is_valid_ingredient_name(name: &str) -> bool {
+    !name.trim().is_empty()
+        && !name.contains(['@', '#', '~', '{', '}', '\\', '\n'])
+        && !name.contains("--")
+        && !name.contains("[-")
+}
+
+/// Converts `recipe` into Cooklang text.
+///
+/// All of the ingredients are listed in order in `ingredients` metadata, and
+/// each is also marked up at its first mention in the recipe's steps.
+///
+/// Returns a string describing the error if an ingredient's name can't be
+/// written in Cooklang markup.
+pub fn to_cooklang(recipe: &ParsedRecipe) -> Result<String, String> {
+    if let Some(ingredient) = recipe
+        .ingredients
+        .iter()
+        .find(|ingredient| !is_valid_ingredient_name(&ingredient.name))
+    {
+        return Err(format!(
+            "The ingredient name \"{}\" can't be written in Cooklang",
+            ingredient.name
+        ));
+    }
+
+    let mut output = format!(">> title: {}\n", escape(&recipe.name, false));
+    if !recipe.categories.is_empty() {
+        // Commas separate tags, so those in categories are escaped.
+        let tags = recipe
+            .categories
+            .iter()
+            .map(|category| escape(category, false).replace(',', "\\,"))
+            .collect::<Vec<_>>();
+        let _ = writeln!(output, ">> tags: {}", tags.join(", "));
+    }
+    if recipe.duration > Duration::zero() {
+        let _ =
+            writeln!(output, ">> time: {}", format_duration(recipe.duration));
+    }
+    if !recipe.ingredients.is_empty() {
+        let markup = recipe
+            .ingredients
+            .iter()
+            .map(|ingredient| ingredient_markup(&ingredient.name, ingredient))
+            .collect::<Vec<_>>();
+        let _ = writeln!(output, ">> {INGREDIENTS_KEY}: {}", markup.join(", "));
+    }
+
+    // For each step, the (start, end, ingredient index) of marked-up mentions.
+    let mut claims: Vec<Vec<(usize, usize, usize)>> =
+        vec![vec![]; recipe.instructions.len()];
+
+    for (index, ingredient) in recipe.ingredients.iter().enumerate() {
+        let mention =
+            recipe
+                .instructions
+                .iter()
+                .enumerate()
+                .find_map(|(step, text)| {
+                    find_mention(text, &ingredient.name, &claims[step])
+                        .map(|start| (step, start))
+                });
+        if let Some((step, start)) = mention {
+            claims[step].push((start, start + ingredient.name.len(), index));
+        }
+    }
+
+    for (text, mut step_claims) in recipe.instructions.iter().zip(claims) {
+        step_claims.sort_unstable();
+        let mut step = String::new();
+        let mut position = 0;
+        for (start, end, index) in step_claims {
+            step.push_str(&escape(&text[position..start], position == 0));
+            step.push_str(&ingredient_markup(
+                &text[start..end],
+                &recipe.ingredients[index],
+            ));
+            // Text in brackets straight after markup would be read as a note.
+            if text[end..].starts_with('(') {
+                step.push('\\');
+            }
+            position = end;
+        }
+        step.push_str(&escape(&text[position..], position == 0));
+
+        output.push('\n');
+        output.push_str(&step);
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PANCAKES: &str = "\
+>> title: Pancakes
+>> tags: Breakfast, Quick
+>> servings: 4
+
+-- A classic.
+Crack @eggs{3} into a #bowl{}, then add @flour{125%g} and
+@milk{250%ml}.
+
+Whisk until smooth. [- no lumps! -]Let rest for ~{10%minutes}.
+
+Fry in @butter{} using a #frying pan{} and serve with @maple syrup{2%tbsp}.
+";
+
+    #[test]
+    fn test_parse() {
+        let recipe = parse_cooklang(PANCAKES, "Untitled").unwrap();
+        assert_eq!(recipe.name, "Pancakes");
+        assert_eq!(recipe.categories, vec!["Breakfast", "Quick"]);
+        assert_eq!(recipe.duration, Duration::minutes(10));
+
+        let names = recipe
+            .ingredients
+            .iter()
+            .map(|ingredient| ingredient.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec!["eggs", "flour", "milk", "butter", "maple syrup"]
+        );
+
+        assert_eq!(recipe.ingredients[0].measurement, MeasurementType::Count);
+        assert!((recipe.ingredients[0].quantity - 3.0).abs() < 1e-9);
+        assert_eq!(recipe.ingredients[1].measurement, MeasurementType::Mass);
+        assert!((recipe.ingredients[1].quantity - 0.125).abs() < 1e-9);
+        assert_eq!(recipe.ingredients[2].measurement, MeasurementType::Volume);
+        assert!((recipe.ingredients[3].quantity).abs() < 1e-9);
+
+        assert_eq!(
+            recipe.instructions,
+            vec![
+                "Crack eggs into a bowl, then add flour and milk.",
+                "Whisk until smooth. Let rest for 10 minutes.",
+                "Fry in butter using a frying pan and serve with maple syrup.",
+            ]
+        );
+    }
+
+    /// Asserts that exporting `recipe` and importing it again gives back the
+    /// same recipe.
+    fn assert_round_trip(recipe: &ParsedRecipe) {
+        let reparsed =
+            parse_cooklang(&to_cooklang(recipe).unwrap(), "Untitled").unwrap();
+
+        assert_eq!(reparsed.name, recipe.name);
+        assert_eq!(reparsed.categories, recipe.categories);
+        assert_eq!(reparsed.duration, recipe.duration);
+        assert_eq!(reparsed.instructions, recipe.instructions);
+        assert_eq!(reparsed.ingredients.len(), recipe.ingredients.len());
+        for (before, after) in
+            recipe.ingredients.iter().zip(&reparsed.ingredients)
+        {
+            assert_eq!(before.name, after.name);
+            assert_eq!(before.measurement, after.measurement);
+            assert!((before.quantity - after.quantity).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        assert_round_trip(&parse_cooklang(PANCAKES, "Untitled").unwrap());
+
+        let ingredient = |name: &str, quantity| ParsedIngredient {
+            name: name.to_owned(),
+            quantity,
+            measurement: MeasurementType::Count,
+        };
+        let mut recipe = ParsedRecipe::new("Salad -- #1 @ home".to_owned());
+        recipe.categories =
+            vec!["Salads, sides".to_owned(), "Quick \\ easy".to_owned()];
+        recipe.ingredients = vec![
+            ingredient("tomatoes", 2.0),
+            ingredient("pepper", 0.0),
+            ingredient("lettuce", 1.0),
+            ingredient("croutons", 0.0),
+        ];
+        recipe.instructions = vec![
+            "Tear the lettuce(washed) into a bowl.".to_owned(),
+            "Add tomatoes -- chopped -- and pepper @ 20--25 ~g [-ish-]."
+                .to_owned(),
+            "= Season \\ serve.".to_owned(),
+            ">> time: 1 hour".to_owned(),
+        ];
+        assert_round_trip(&recipe);
+    }
+
+    #[test]
+    fn test_plain_ingredients_metadata() {
+        let recipe = parse_cooklang(
+            ">> ingredients: flour, sugar\n\nMix @flour{100%g} and @sugar{}.",
+            "Untitled",
+        )
+        .unwrap();
+        let names = recipe
+            .ingredients
+            .iter()
+            .map(|ingredient| ingredient.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["flour", "sugar"]);
+    }
+
+    #[test]
+    fn test_strip_line_comment() {
+        assert_eq!(strip_line_comment("-- A comment"), "");
+        assert_eq!(strip_line_comment("Stir. -- Gently"), "Stir. ");
+        assert_eq!(
+            strip_line_comment("Bake for 20--25 minutes."),
+            "Bake for 20--25 minutes."
+        );
+    }
+}
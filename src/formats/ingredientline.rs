@@ -0,0 +1,485 @@
+//! Parsing of natural-language ingredient lines such as
+//! "2 1/2 cups all-purpose flour, sifted".
+
+use std::collections::HashSet;
+
+use super::ParsedIngredient;
+use crate::models::MeasurementType;
+use crate::units::{lookup_unit, parse_amount, to_si, Unit};
+
+/// Words that can be used in place of a numeric amount.
+const NUMBER_WORDS: &[(&str, f64)] = &[
+    ("a", 1.0),
+    ("an", 1.0),
+    ("one", 1.0),
+    ("two", 2.0),
+    ("three", 3.0),
+    ("four", 4.0),
+    ("five", 5.0),
+    ("six", 6.0),
+    ("seven", 7.0),
+    ("eight", 8.0),
+    ("nine", 9.0),
+    ("ten", 10.0),
+    ("eleven", 11.0),
+    ("twelve", 12.0),
+    ("half", 0.5),
+    ("dozen", 12.0),
+];
+
+/// Units that count whole things, such as "3 cloves garlic".
+const COUNT_UNITS: &[&str] = &[
+    "bag", "bags", "bottle", "bottles", "box", "boxes", "bunch", "bunches",
+    "can", "cans", "carton", "cartons", "clove", "cloves", "handful",
+    "handfuls", "head", "heads", "jar", "jars", "package", "packages", "pkg",
+    "sheet", "sheets", "slice", "slices", "sprig", "sprigs", "stalk", "stalks",
+    "stick", "sticks",
+];
+
+/// Count units that are containers, whose size may be given in parentheses,
+/// as in "1 (14 oz) can tomatoes".
+const CONTAINER_UNITS: &[&str] = &[
+    "bag", "bags", "bottle", "bottles", "box", "boxes", "can", "cans",
+    "carton", "cartons", "jar", "jars", "package", "packages", "pkg",
+];
+
+/// Words describing the size or measuring of an ingredient, rather than the
+/// ingredient itself.
+const SIZE_WORDS: &[&str] = &[
+    "small",
+    "medium",
+    "large",
+    "extra-large",
+    "big",
+    "heaping",
+    "heaped",
+    "level",
+    "scant",
+    "generous",
+];
+
+/// Words describing how an ingredient is prepared.
+const PREPARATION_WORDS: &[&str] = &[
+    "beaten",
+    "chilled",
+    "chopped",
+    "coarsely",
+    "cooked",
+    "crushed",
+    "cubed",
+    "diced",
+    "divided",
+    "drained",
+    "finely",
+    "firmly",
+    "freshly",
+    "grated",
+    "halved",
+    "julienned",
+    "lightly",
+    "mashed",
+    "melted",
+    "minced",
+    "packed",
+    "peeled",
+    "quartered",
+    "rinsed",
+    "roughly",
+    "shredded",
+    "sifted",
+    "sliced",
+    "softened",
+    "thinly",
+    "toasted",
+    "warmed",
+];
+
+/// Phrases at the end of a line that describe how an ingredient is used.
+const PREPARATION_SUFFIXES: &[&str] = &[
+    "or to taste",
+    "to taste",
+    "for garnish",
+    "for serving",
+    "as needed",
+    "optional",
+];
+
+/// An ingredient line that has been split into its parts.
+pub struct IngredientLine {
+    /// The ingredient's name, quantity (in SI standard units) and
+    /// measurement type.
+    pub ingredient: ParsedIngredient,
+
+    /// The unit as written in the line, if there was one.
+    pub unit: Option<String>,
+
+    /// How the ingredient is prepared or used (e.g. "sifted" or "to taste").
+    pub preparation: Option<String>,
+
+    /// How confident the parser is in its interpretation, from 0 to 1.
+    pub confidence: f64,
+}
+
+/// Removes all parenthesized text from `text`, returning the remaining text
+/// and the contents of each pair of parentheses.
+fn split_parentheticals(text: &str) -> (String, Vec<String>) {
+    let mut main = String::new();
+    let mut notes = vec![];
+    let mut rest = text;
+    while let Some(open) = rest.find('(') {
+        let Some(close) = rest[open..].find(')') else {
+            break;
+        };
+        main.push_str(&rest[..open]);
+        main.push(' ');
+        notes.push(rest[open + 1..open + close].trim().to_owned());
+        rest = &rest[open + close + 1..];
+    }
+    main.push_str(rest);
+    (main, notes)
+}
+
+/// Splits whitespace-separated words, also separating numbers that are
+/// directly followed by letters (e.g. "250g" or "14-ounce").
+fn split_words(text: &str) -> Vec<String> {
+    let mut words = vec![];
+    for word in text.split_whitespace() {
+        let split = word
+            .char_indices()
+            .find(|&(_, c)| c.is_alphabetic())
+            .map(|(index, _)| index)
+            .filter(|&index| {
+                index > 0
+                    && word[..index]
+                        .trim_end_matches('-')
+                        .ends_with(|c: char| c.is_ascii_digit())
+            });
+        match split {
+            Some(index) => {
+                words.push(word[..index].trim_end_matches('-').to_owned());
+                words.push(word[index..].to_owned());
+            }
+            None => words.push(word.to_owned()),
+        }
+    }
+    words
+}
+
+/// Parses the amount at the start of `words`.
+///
+/// Returns the amount and the number of words it used. Ranges such as
+/// "2 to 3" are parsed as their lower bound.
+fn parse_leading_amount(words: &[String]) -> Option<(f64, usize)> {
+    if let Some(first) = words.first() {
+        let lowercase = first.to_lowercase();
+        if let Some((_, value)) =
+            NUMBER_WORDS.iter().find(|(word, _)| *word == lowercase)
+        {
+            return Some((*value, 1));
+        }
+    }
+
+    let count = words
+        .iter()
+        .take_while(|word| parse_amount(word).is_some())
+        .count();
+    let amount = parse_amount(&words[..count].join(" "))?;
+
+    // Skip the upper bound of a range.
+    let is_range = words.get(count).is_some_and(|word| {
+        matches!(word.to_lowercase().as_str(), "to" | "or" | "-")
+    }) && words
+        .get(count + 1)
+        .is_some_and(|word| parse_amount(word).is_some());
+    Some((amount, if is_range { count + 2 } else { count }))
+}
+
+/// Parses a unit at the start of `words`, leaving at least one word after
+/// it.
+///
+/// Returns the unit (if it is a measurable unit), the unit as written, and
+/// the number of words it used.
+fn parse_leading_unit(
+    words: &[String],
+) -> Option<(Option<Unit>, String, usize)> {
+    for count in [2, 1] {
+        if words.len() <= count {
+            continue;
+        }
+        let written = words[..count].join(" ");
+        if written.trim_end_matches('.').is_empty() {
+            continue;
+        }
+        if let Some(unit) = lookup_unit(&written) {
+            if unit.measurement != MeasurementType::Count {
+                return Some((Some(unit), written, count));
+            }
+        }
+        if count == 1 && COUNT_UNITS.contains(&written.to_lowercase().as_str())
+        {
+            return Some((None, written, count));
+        }
+    }
+    None
+}
+
+/// Parses a package size such as "14 oz" or "14-ounce".
+fn parse_package_size(note: &str) -> Option<(f64, Unit, String)> {
+    let words = split_words(note);
+    let (amount, used) = parse_leading_amount(&words)?;
+    let written = words[used..].join(" ");
+    let unit = lookup_unit(&written)
+        .filter(|unit| unit.measurement != MeasurementType::Count)?;
+    Some((amount, unit, written))
+}
+
+/// Finds the ingredient name in `words` (the words after the amount and
+/// unit), moving any size or preparation words around it into `notes`.
+///
+/// Returns `None` if there is no name.
+fn split_name(words: &[String], notes: &mut Vec<String>) -> Option<String> {
+    let mut position = usize::from(
+        words
+            .first()
+            .is_some_and(|word| word.eq_ignore_ascii_case("of")),
+    );
+
+    // Leading size and preparation words are notes, not part of the name.
+    let mut leading_notes = vec![];
+    while let Some(word) = words.get(position) {
+        let lowercase = word.to_lowercase();
+        if words.len() - position > 1
+            && (SIZE_WORDS.contains(&lowercase.as_str())
+                || PREPARATION_WORDS.contains(&lowercase.as_str()))
+        {
+            leading_notes.push(word.clone());
+            position += 1;
+        } else {
+            break;
+        }
+    }
+    if !leading_notes.is_empty() {
+        notes.insert(0, leading_notes.join(" "));
+    }
+
+    // Trailing phrases such as "to taste" are notes, too.
+    let mut name = words[position..].join(" ");
+    for suffix in PREPARATION_SUFFIXES {
+        let lowercase = name.to_lowercase();
+        if let Some(stripped) = lowercase.strip_suffix(suffix) {
+            let length = stripped.trim_end().len();
+            if length > 0 {
+                notes.push((*suffix).to_owned());
+                name.truncate(length);
+                break;
+            }
+        }
+    }
+
+    let name = name.trim();
+    (!name.is_empty()).then(|| name.to_owned())
+}
+
+/// Parses a natural-language ingredient line such as
+/// "2 1/2 cups all-purpose flour, sifted", "3 large eggs", "salt to taste"
+/// or "1 (14 oz) can diced tomatoes".
+///
+/// Returns `None` if no ingredient name could be found.
+pub fn parse_ingredient_line(line: &str) -> Option<IngredientLine> {
+    let line = line
+        .trim()
+        .trim_start_matches(['-', '*', '•'])
+        .trim()
+        .trim_end_matches('.');
+    let (main, mut notes) = split_parentheticals(line);
+
+    // Everything after the first comma is a preparation note.
+    let (main, after_comma) = main.split_once(',').unwrap_or((&main, ""));
+    let after_comma = after_comma.trim();
+    if !after_comma.is_empty() {
+        notes.push(after_comma.to_owned());
+    }
+
+    let words = split_words(main);
+    let mut confidence: f64 = 1.0;
+
+    let (mut amount, mut position) = match parse_leading_amount(&words) {
+        Some((amount, used)) => (Some(amount), used),
+        None => (None, 0),
+    };
+
+    let mut unit = None;
+    let mut written_unit = None;
+    if let Some((found, written, used)) = parse_leading_unit(&words[position..])
+    {
+        // Without an amount, only accept full unit names ("pinch of salt"),
+        // not abbreviations that could be part of the name.
+        if amount.is_some() || written.len() >= 3 {
+            amount = amount.or(Some(1.0));
+            unit = found;
+            written_unit = Some(written);
+            position += used;
+        }
+    }
+
+    // Use a package size in parentheses, e.g. "1 (14 oz) can tomatoes".
+    let is_container = written_unit.as_ref().is_none_or(|written| {
+        CONTAINER_UNITS.contains(&written.to_lowercase().as_str())
+    });
+    if unit.is_none() && is_container {
+        let package_size =
+            notes.iter().enumerate().find_map(|(index, note)| {
+                parse_package_size(note).map(|size| (index, size))
+            });
+        if let Some((index, (size, size_unit, written))) = package_size {
+            notes.remove(index);
+            amount = Some(amount.unwrap_or(1.0) * size);
+            unit = Some(size_unit);
+            written_unit = Some(written);
+        }
+    }
+
+    let name = split_name(&words[position..], &mut notes)?;
+
+    if amount.is_none() {
+        confidence *= if notes.iter().any(|note| note.contains("taste")) {
+            0.9
+        } else {
+            0.7
+        };
+    }
+    if name.split_whitespace().count() > 4 {
+        confidence *= 0.8;
+    }
+
+    let amount = amount.unwrap_or(0.0);
+    let (quantity, measurement) = match unit {
+        Some(unit) => (to_si(amount, unit), unit.measurement),
+        None => (amount, MeasurementType::Count),
+    };
+    let preparation = notes
+        .into_iter()
+        .filter(|note| !note.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    Some(IngredientLine {
+        ingredient: ParsedIngredient {
+            name,
+            quantity,
+            measurement,
+        },
+        unit: written_unit,
+        preparation: (!preparation.is_empty()).then_some(preparation),
+        confidence,
+    })
+}
+
+/// Converts a word to a lowercase, singular form for comparison.
+fn normalize_word(word: &str) -> String {
+    let word = word.to_lowercase();
+    if let Some(stem) = word.strip_suffix("ies") {
+        format!("{stem}y")
+    } else if let Some(stem) = word.strip_suffix("oes") {
+        format!("{stem}o")
+    } else if ["ches", "shes", "sses", "xes"]
+        .iter()
+        .any(|suffix| word.ends_with(suffix))
+    {
+        word[..word.len() - 2].to_owned()
+    } else if word.ends_with('s') && !word.ends_with("ss") && word.len() > 3 {
+        word[..word.len() - 1].to_owned()
+    } else {
+        word
+    }
+}
+
+/// Splits `name` into a set of normalized words.
+fn name_words(name: &str) -> HashSet<String> {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(normalize_word)
+        .collect()
+}
+
+/// Estimates how likely it is that the ingredient names `a` and `b` refer to
+/// the same ingredient, from 0 (unrelated) to 1 (the same name).
+///
+/// Names are compared word by word, ignoring case, punctuation and plurals. A
+/// name whose words are all contained in the other (e.g. "flour" and
+/// "all-purpose flour") is considered a close match.
+pub fn name_similarity(a: &str, b: &str) -> f64 {
+    let a = name_words(a);
+    let b = name_words(b);
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    if a == b {
+        return 1.0;
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    let (common, a_len, b_len) = (
+        a.intersection(&b).count() as f64,
+        a.len() as f64,
+        b.len() as f64,
+    );
+    let dice = 2.0 * common / (a_len + b_len);
+
+    let contained = if a.is_subset(&b) || b.is_subset(&a) {
+        0.6 + 0.3 * a_len.min(b_len) / a_len.max(b_len)
+    } else {
+        0.0
+    };
+    dice.max(contained)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ingredient_line() {
+        let line =
+            parse_ingredient_line("2 1/2 cups all-purpose flour, sifted")
+                .unwrap();
+        assert_eq!(line.ingredient.name, "all-purpose flour");
+        assert_eq!(line.ingredient.measurement, MeasurementType::Volume);
+        assert!((line.ingredient.quantity - 5.914_705_9e-4).abs() < 1e-9);
+        assert_eq!(line.unit.as_deref(), Some("cups"));
+        assert_eq!(line.preparation.as_deref(), Some("sifted"));
+
+        let line = parse_ingredient_line("3 large eggs").unwrap();
+        assert_eq!(line.ingredient.name, "eggs");
+        assert_eq!(line.ingredient.measurement, MeasurementType::Count);
+        assert_eq!(line.preparation.as_deref(), Some("large"));
+
+        let line = parse_ingredient_line("Salt to taste").unwrap();
+        assert_eq!(line.ingredient.name, "Salt");
+        assert_eq!(line.preparation.as_deref(), Some("to taste"));
+        assert!(line.confidence < 1.0);
+
+        let line =
+            parse_ingredient_line("1 (14 oz) can diced tomatoes").unwrap();
+        assert_eq!(line.ingredient.name, "tomatoes");
+        assert_eq!(line.ingredient.measurement, MeasurementType::Mass);
+        assert_eq!(line.preparation.as_deref(), Some("diced"));
+
+        let line = parse_ingredient_line("250g butter").unwrap();
+        assert_eq!(line.ingredient.name, "butter");
+        assert!((line.ingredient.quantity - 0.25).abs() < 1e-9);
+
+        assert!(parse_ingredient_line("  ").is_none());
+    }
+
+    #[test]
+    fn test_name_similarity() {
+        assert!((name_similarity("Eggs", "egg") - 1.0).abs() < 1e-9);
+        assert!(name_similarity("all-purpose flour", "flour") > 0.6);
+        assert!(
+            name_similarity("brown sugar", "sugar")
+                > name_similarity("brown sugar", "brown rice")
+        );
+        assert!(name_similarity("butter", "milk") < 1e-9);
+    }
+}
@@ -0,0 +1,308 @@
+//! Parsing of Meal-Master (`MMMMM-----`) recipe exports.
+
+use super::{BulkEntry, ParsedIngredient, ParsedRecipe};
+use crate::models::MeasurementType;
+use crate::units::{lookup_unit, parse_amount, parse_duration, to_si};
+
+/// The column at which the second ingredient starts in two-column ingredient
+/// lists.
+const SECOND_COLUMN: usize = 41;
+
+/// Returns whether `line` starts a Meal-Master recipe.
+fn is_header(line: &str) -> bool {
+    let line = line.trim();
+    line.starts_with("MMMMM-----")
+        || (line.starts_with("-----")
+            && line.to_lowercase().contains("meal-master"))
+}
+
+/// Returns whether `line` ends a Meal-Master recipe.
+fn is_footer(line: &str) -> bool {
+    matches!(line.trim(), "MMMMM" | "-----")
+}
+
+/// Returns whether `line` is a section heading within an ingredient list
+/// (e.g. `MMMMM--------SAUCE--------`).
+fn is_section_heading(line: &str) -> bool {
+    let line = line.trim();
+    (line.starts_with("MMMMM-") || line.starts_with("-----"))
+        && !is_header(line)
+}
+
+/// Returns whether `text` contains at least one Meal-Master recipe.
+pub fn is_mealmaster(text: &str) -> bool {
+    text.lines().any(is_header)
+}
+
+/// Splits an ingredient line into its amount, unit and text columns, if it
+/// is laid out like one.
+///
+/// Meal-Master ingredient lines have a 7-character amount column, a space, a
+/// 2-character unit column, a space, and then the ingredient text.
+fn split_ingredient_columns(line: &str) -> Option<(&str, &str, &str)> {
+    if !line.is_ascii() || line.trim().is_empty() {
+        return None;
+    }
+    let column = |start: usize, end: usize| {
+        line.get(start..end.min(line.len())).unwrap_or("")
+    };
+
+    let amount = column(0, 7);
+    let separator = column(7, 8);
+    let unit = column(8, 10);
+    let gap = column(10, 11);
+    let text = column(11, line.len());
+
+    let amount_ok = amount
+        .chars()
+        .all(|c| c.is_ascii_digit() || matches!(c, ' ' | '/' | '.' | '-'));
+    let unit_ok = unit.chars().all(|c| c.is_ascii_alphabetic() || c == ' ');
+    let separators_ok = separator.trim().is_empty() && gap.trim().is_empty();
+
+    (amount_ok && unit_ok && separators_ok)
+        .then(|| (amount.trim(), unit.trim(), text.trim()))
+}
+
+/// Converts the columns of an ingredient line into a `ParsedIngredient`.
+///
+/// Preparation notes after a `;` or `,` are dropped from the ingredient's
+/// name. Unknown units (e.g. `cn` for "can") are treated as counts.
+fn to_ingredient(amount: &str, unit: &str, text: &str) -> ParsedIngredient {
+    let name = text.split([';', ',']).next().unwrap_or(text).trim();
+    let amount = parse_amount(amount).unwrap_or(0.0);
+    let (quantity, measurement) = match lookup_unit(unit) {
+        Some(unit) => (to_si(amount, unit), unit.measurement),
+        None => (amount, MeasurementType::Count),
+    };
+    ParsedIngredient {
+        name: name.to_owned(),
+        quantity,
+        measurement,
+    }
+}
+
+/// Parses the lines of a single Meal-Master recipe (between its header and
+/// footer), adding a warning to `warnings` for each part that is ignored.
+fn parse_recipe(
+    lines: &[&str],
+    warnings: &mut Vec<String>,
+) -> Result<ParsedRecipe, String> {
+    let mut recipe = ParsedRecipe::new(String::new());
+    let mut index = 0;
+
+    // Header fields (Title, Categories, Yield, ...).
+    while index < lines.len() {
+        let line = lines[index].trim();
+        if line.is_empty() {
+            index += 1;
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            break;
+        };
+        match key.trim().to_lowercase().as_str() {
+            "title" => value.trim().clone_into(&mut recipe.name),
+            "categories" | "category" => recipe.categories.extend(
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|category| {
+                        !category.is_empty() && *category != "None"
+                    })
+                    .map(str::to_owned),
+            ),
+            "time" | "total time" | "preparation time" => {
+                match parse_duration(value) {
+                    Some(duration) => recipe.duration = duration,
+                    None => warnings.push(format!(
+                        "Ignored invalid time \"{}\"",
+                        value.trim()
+                    )),
+                }
+            }
+            "yield" | "servings" => {}
+            _ => break,
+        }
+        index += 1;
+    }
+
+    if recipe.name.is_empty() {
+        return Err("Recipe has no title".to_owned());
+    }
+
+    // Ingredients, possibly in two columns.
+    let mut ingredient_lines: Vec<(&str, &str, &str)> = vec![];
+    while index < lines.len() {
+        let line = lines[index];
+        if line.trim().is_empty() || is_section_heading(line) {
+            index += 1;
+            continue;
+        }
+        let Some(first) = split_ingredient_columns(line) else {
+            break;
+        };
+        if line.len() > SECOND_COLUMN {
+            if let Some(second) =
+                split_ingredient_columns(&line[SECOND_COLUMN..])
+            {
+                let first = split_ingredient_columns(&line[..SECOND_COLUMN])
+                    .unwrap_or(first);
+                ingredient_lines.push(first);
+                ingredient_lines.push(second);
+                index += 1;
+                continue;
+            }
+        }
+        ingredient_lines.push(first);
+        index += 1;
+    }
+
+    for (amount, unit, text) in ingredient_lines {
+        // A line starting with "-" continues the previous ingredient's
+        // preparation notes, which are not kept.
+        let is_continuation =
+            amount.is_empty() && unit.is_empty() && text.starts_with('-');
+        if !text.is_empty() && !is_continuation {
+            recipe.ingredients.push(to_ingredient(amount, unit, text));
+        }
+    }
+
+    // Directions, split into steps at blank lines.
+    let mut step = String::new();
+    for line in &lines[index..] {
+        let line = line.trim();
+        if line.is_empty() {
+            if !step.is_empty() {
+                recipe.instructions.push(std::mem::take(&mut step));
+            }
+        } else if !is_section_heading(line) {
+            if !step.is_empty() {
+                step.push(' ');
+            }
+            step.push_str(line);
+        }
+    }
+    if !step.is_empty() {
+        recipe.instructions.push(step);
+    }
+
+    Ok(recipe)
+}
+
+/// Parses every Meal-Master recipe in `text`.
+///
+/// Each recipe is parsed independently, so an error in one recipe does not
+/// prevent the others from being parsed.
+pub fn parse_mealmaster(text: &str) -> Vec<BulkEntry> {
+    let mut entries = vec![];
+    let mut current: Option<Vec<&str>> = None;
+
+    for line in text.lines() {
+        if is_header(line) {
+            if let Some(lines) = current.take() {
+                entries.push(lines);
+            }
+            current = Some(vec![]);
+        } else if is_footer(line) {
+            if let Some(lines) = current.take() {
+                entries.push(lines);
+            }
+        } else if let Some(ref mut lines) = current {
+            lines.push(line);
+        }
+    }
+    if let Some(lines) = current {
+        entries.push(lines);
+    }
+
+    entries
+        .iter()
+        .enumerate()
+        .map(|(index, lines)| {
+            let mut warnings = vec![];
+            let recipe = parse_recipe(lines, &mut warnings);
+            let title = recipe.as_ref().map_or_else(
+                |_| format!("Recipe {}", index + 1),
+                |recipe| recipe.name.clone(),
+            );
+            BulkEntry {
+                title,
+                recipe,
+                warnings,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORT: &str = "\
+MMMMM----- Recipe via Meal-Master (tm) v8.05
+
+      Title: Chocolate Cake
+ Categories: Desserts, Cakes
+      Yield: 12 servings
+       Time: A while
+
+      2 c  Flour                               1 ts Baking soda
+  1 1/2 c  Sugar
+    1/2 lb Butter; softened
+      2    Eggs
+           Salt
+
+  Preheat the oven to 350F. Mix everything
+  together.
+
+  Bake for 30 minutes.
+
+MMMMM
+
+MMMMM----- Recipe via Meal-Master (tm) v8.05
+
+ Categories: Broken
+
+      1 c  Water
+
+MMMMM
+";
+
+    #[test]
+    fn test_parse() {
+        let entries = parse_mealmaster(EXPORT);
+        assert_eq!(entries.len(), 2);
+
+        let recipe = entries[0].recipe.as_ref().unwrap();
+        assert_eq!(recipe.name, "Chocolate Cake");
+        assert_eq!(recipe.categories, vec!["Desserts", "Cakes"]);
+        assert_eq!(
+            entries[0].warnings,
+            vec!["Ignored invalid time \"A while\""]
+        );
+
+        let names = recipe
+            .ingredients
+            .iter()
+            .map(|ingredient| ingredient.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec!["Flour", "Baking soda", "Sugar", "Butter", "Eggs", "Salt"]
+        );
+        assert_eq!(recipe.ingredients[2].measurement, MeasurementType::Volume);
+        assert_eq!(recipe.ingredients[3].measurement, MeasurementType::Mass);
+        assert!((recipe.ingredients[4].quantity - 2.0).abs() < 1e-9);
+
+        assert_eq!(
+            recipe.instructions,
+            vec![
+                "Preheat the oven to 350F. Mix everything together.",
+                "Bake for 30 minutes.",
+            ]
+        );
+
+        assert_eq!(entries[1].title, "Recipe 2");
+        assert!(entries[1].recipe.is_err());
+    }
+}
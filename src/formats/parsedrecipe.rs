@@ -0,0 +1,189 @@
+use chrono::{offset::Utc, Duration};
+use sqlx::{Any, Transaction};
+
+use crate::database::{self, DBResult};
+use crate::models::{
+    Category, Ingredient, Instruction, MeasurementType, Model,
+    QuantifiedIngredient, Recipe, RecipeVersion, RecipeVersionID, Ref,
+};
+
+/// An ingredient as written in an external recipe format, referenced by name
+/// rather than by ID.
+pub struct ParsedIngredient {
+    /// The human-readable name of the ingredient.
+    pub name: String,
+
+    /// The quantity of the ingredient, in SI standard units.
+    pub quantity: f64,
+
+    /// The kind of quantity of the ingredient.
+    pub measurement: MeasurementType,
+}
+
+/// A recipe (with a single version) as written in an external recipe format.
+///
+/// This is the common representation that all importers produce and all
+/// exporters consume.
+pub struct ParsedRecipe {
+    /// The human-readable name of the recipe.
+    pub name: String,
+
+    /// The names of the categories that the recipe is a part of.
+    pub categories: Vec<String>,
+
+    /// The total time needed to make the recipe.
+    pub duration: Duration,
+
+    /// The recipe's ingredients, in list order.
+    pub ingredients: Vec<ParsedIngredient>,
+
+    /// The text of each of the recipe's steps, in order.
+    pub instructions: Vec<String>,
+}
+
+impl ParsedRecipe {
+    /// Creates an empty recipe named `name`.
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            categories: vec![],
+            duration: Duration::zero(),
+            ingredients: vec![],
+            instructions: vec![],
+        }
+    }
+
+    /// Stores this recipe as a new `Recipe` with a single `RecipeVersion`
+    /// using `transaction`.
+    ///
+    /// Ingredients and categories are matched to existing ones by name
+    /// (ignoring case). Any that do not exist yet are created. Ingredients that
+    /// appear more than once are merged, keeping the position of their first
+    /// appearance.
+    pub async fn store_new(
+        self,
+        transaction: &mut Transaction<'_, Any>,
+    ) -> DBResult<RecipeVersionID> {
+        if self.name.trim().is_empty() {
+            return Err(database::Error::BadArguments(
+                "Recipe has no name".to_owned(),
+            ));
+        }
+
+        let mut categories: Vec<Category> = vec![];
+        for name in self.categories {
+            let id = match Category::find_by_name(transaction, &name).await? {
+                Some(id) => id,
+                None => Category::store_new(transaction, &name).await?,
+            };
+            if !categories.iter().any(|category| category.id == id) {
+                categories.push(Category { id, name });
+            }
+        }
+
+        let mut ingredients: Vec<QuantifiedIngredient> = vec![];
+        for ingredient in self.ingredients {
+            let existing_id =
+                Ingredient::find_by_name(transaction, &ingredient.name).await?;
+            let id = match existing_id {
+                Some(id) => id,
+                None => {
+                    Ingredient::store_new(transaction, &ingredient.name, 0.0)
+                        .await?
+                }
+            };
+
+            if let Some(existing) = ingredients
+                .iter_mut()
+                .find(|existing| existing.ingredient.id == id)
+            {
+                if existing.measurement != ingredient.measurement {
+                    return Err(database::Error::BadArguments(format!(
+                        "Ingredient \"{}\" is measured in incompatible ways",
+                        ingredient.name
+                    )));
+                }
+                existing.quantity += ingredient.quantity;
+            } else {
+                ingredients.push(QuantifiedIngredient {
+                    ingredient: Ref::new(id),
+                    quantity: ingredient.quantity,
+                    measurement: ingredient.measurement,
+                });
+            }
+        }
+
+        let instructions = self
+            .instructions
+            .into_iter()
+            .map(|text| Instruction { text })
+            .collect();
+
+        let recipe_id =
+            Recipe::store_new(transaction, &self.name, categories).await?;
+        RecipeVersion::store_new(
+            transaction,
+            recipe_id,
+            Utc::now(),
+            ingredients,
+            instructions,
+            self.duration,
+        )
+        .await
+    }
+
+    /// Retrieves the recipe version with ID `id`, along with the name and
+    /// categories of its recipe, using `transaction`.
+    pub async fn load(
+        transaction: &mut Transaction<'_, Any>,
+        id: RecipeVersionID,
+    ) -> DBResult<Self> {
+        let mut recipe = Recipe::get(transaction, id.recipe_id).await?;
+        let version = RecipeVersion::get_filled(transaction, id).await?;
+
+        let mut categories = vec![];
+        for category in &mut recipe.categories {
+            categories.push(category.query(transaction).await?.name.clone());
+        }
+
+        let ingredients = version
+            .ingredients
+            .iter()
+            .map(|ingredient| ParsedIngredient {
+                name: ingredient
+                    .ingredient
+                    .get()
+                    .map(|value| value.name.clone())
+                    .unwrap_or_default(),
+                quantity: ingredient.quantity,
+                measurement: ingredient.measurement,
+            })
+            .collect();
+
+        Ok(Self {
+            name: recipe.name,
+            categories,
+            duration: version.duration,
+            ingredients,
+            instructions: version
+                .instructions
+                .into_iter()
+                .map(|instruction| instruction.text)
+                .collect(),
+        })
+    }
+}
+
+/// One recipe found in a file that can contain many recipes.
+pub struct BulkEntry {
+    /// The recipe's title, or a placeholder such as "Recipe 3" if its title
+    /// could not be determined.
+    pub title: String,
+
+    /// The parsed recipe, or a string describing why it could not be parsed.
+    pub recipe: Result<ParsedRecipe, String>,
+
+    /// Descriptions of parts of the recipe that were ignored because they
+    /// could not be parsed.
+    pub warnings: Vec<String>,
+}
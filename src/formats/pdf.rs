@@ -0,0 +1,395 @@
+//! Rendering of `ParsedRecipe`s as one-page PDF recipe cards.
+//!
+//! The PDF is written by hand using the standard Helvetica fonts, which every
+//! PDF reader provides, so no fonts need to be embedded.
+
+use std::fmt::Write;
+
+use chrono::Duration;
+
+use super::textrender::ingredient_entry;
+use super::ParsedRecipe;
+use crate::units::format_duration;
+
+/// The size of the page (A5 portrait), in points.
+const PAGE_WIDTH: f64 = 419.53;
+const PAGE_HEIGHT: f64 = 595.28;
+
+/// The space between the edge of the page and the text, in points.
+const MARGIN: f64 = 36.0;
+
+/// The space between the two ingredient columns, in points.
+const COLUMN_GAP: f64 = 18.0;
+
+/// The largest and smallest body font sizes to try, in points. The largest
+/// size at which the whole recipe fits on the page is used.
+const MAX_FONT_SIZE: f64 = 11.0;
+const MIN_FONT_SIZE: f64 = 5.0;
+
+/// The widths of the printable ASCII characters (from space to tilde) in
+/// Helvetica, in thousandths of the font size.
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278,
+    278, 556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584,
+    584, 556, 1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556,
+    833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278,
+    278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222,
+    500, 222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500,
+    500, 334, 260, 334, 584,
+];
+
+/// The widths of the printable ASCII characters in Helvetica-Bold.
+const HELVETICA_BOLD_WIDTHS: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278,
+    278, 556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584,
+    584, 611, 975, 722, 722, 722, 722, 667, 611, 778, 722, 278, 556, 722, 611,
+    833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 333,
+    278, 333, 584, 556, 333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278,
+    556, 278, 889, 611, 611, 611, 611, 389, 556, 333, 611, 556, 778, 556, 556,
+    500, 389, 280, 389, 584,
+];
+
+/// The width used for characters outside printable ASCII.
+const DEFAULT_WIDTH: u16 = 556;
+
+/// A font that text can be drawn in.
+#[derive(Clone, Copy)]
+enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    /// The name of the font in the page's resources.
+    fn resource_name(self) -> &'static str {
+        match self {
+            Self::Regular => "F1",
+            Self::Bold => "F2",
+        }
+    }
+
+    /// Returns the width of `text` in this font at `size` points.
+    fn text_width(self, text: &str, size: f64) -> f64 {
+        let widths = match self {
+            Self::Regular => &HELVETICA_WIDTHS,
+            Self::Bold => &HELVETICA_BOLD_WIDTHS,
+        };
+        let units: u32 = text
+            .chars()
+            .map(|c| {
+                let index = (c as usize).wrapping_sub(32);
+                u32::from(widths.get(index).copied().unwrap_or(DEFAULT_WIDTH))
+            })
+            .sum();
+        f64::from(units) * size / 1000.0
+    }
+}
+
+/// Splits `text` into lines no wider than `width` points. Words that are too
+/// wide on their own are put on a line by themselves.
+fn wrap(text: &str, font: Font, size: f64, width: f64) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let candidate = if line.is_empty() {
+            word.to_owned()
+        } else {
+            format!("{line} {word}")
+        };
+        if font.text_width(&candidate, size) <= width || line.is_empty() {
+            line = candidate;
+        } else {
+            lines.push(std::mem::replace(&mut line, word.to_owned()));
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Encodes `text` as a PDF string literal in `WinAnsiEncoding`.
+///
+/// Characters outside Latin-1 are replaced with "?".
+fn pdf_string(text: &str) -> Vec<u8> {
+    let mut output = vec![b'('];
+    for c in text.chars() {
+        let byte = u8::try_from(u32::from(c))
+            .ok()
+            .filter(|&byte| byte >= 0x20 && !(0x7f..0xa0).contains(&byte))
+            .unwrap_or(b'?');
+        if matches!(byte, b'(' | b')' | b'\\') {
+            output.push(b'\\');
+        }
+        output.push(byte);
+    }
+    output.push(b')');
+    output
+}
+
+/// A line of text positioned on the page.
+struct PlacedText {
+    x: f64,
+    y: f64, // The baseline, measured up from the bottom of the page.
+    font: Font,
+    size: f64,
+    text: String,
+}
+
+/// Lays out lines of text from the top of the page downwards.
+struct Layout {
+    placed: Vec<PlacedText>,
+    y: f64,
+    size: f64, // The body font size.
+}
+
+impl Layout {
+    /// The width available for text between the margins.
+    const CONTENT_WIDTH: f64 = PAGE_WIDTH - 2.0 * MARGIN;
+
+    /// The height of a line of body text.
+    fn line_height(&self) -> f64 {
+        self.size * 1.3
+    }
+
+    /// Places `lines` below each other at `x`, starting below `y`. Returns
+    /// the baseline of the last line.
+    fn place(
+        &mut self,
+        x: f64,
+        mut y: f64,
+        lines: Vec<String>,
+        font: Font,
+        size: f64,
+    ) -> f64 {
+        for text in lines {
+            y -= size * 1.3;
+            self.placed.push(PlacedText {
+                x,
+                y,
+                font,
+                size,
+                text,
+            });
+        }
+        y
+    }
+
+    /// Wraps `text` to `width` and places it at `x`, below everything placed
+    /// so far.
+    fn add(&mut self, x: f64, width: f64, text: &str, font: Font, size: f64) {
+        let lines = wrap(text, font, size, width);
+        self.y = self.place(x, self.y, lines, font, size);
+    }
+
+    /// Places a section heading, leaving some space above it.
+    fn add_heading(&mut self, heading: &str) {
+        self.y -= self.line_height() * 0.5;
+        let size = self.size * 1.2;
+        self.add(MARGIN, Self::CONTENT_WIDTH, heading, Font::Bold, size);
+    }
+
+    /// Places `entries` in two columns. The first column gets the extra
+    /// entry if there is an odd number.
+    fn add_columns(&mut self, entries: &[String]) {
+        let column_width = (Self::CONTENT_WIDTH - COLUMN_GAP) / 2.0;
+        let (left, right) = entries.split_at(entries.len().div_ceil(2));
+        let mut bottom = self.y;
+        for (x, column) in
+            [(MARGIN, left), (MARGIN + column_width + COLUMN_GAP, right)]
+        {
+            let mut y = self.y;
+            for entry in column {
+                let lines = wrap(entry, Font::Regular, self.size, column_width);
+                y = self.place(x, y, lines, Font::Regular, self.size);
+            }
+            bottom = bottom.min(y);
+        }
+        self.y = bottom;
+    }
+
+    /// Places numbered `steps`, with wrapped lines indented past the number.
+    fn add_steps(&mut self, steps: &[String]) {
+        let indent = Font::Regular.text_width("00. ", self.size);
+        let width = Self::CONTENT_WIDTH - indent;
+        for (index, step) in steps.iter().enumerate() {
+            let number = vec![format!("{}.", index + 1)];
+            self.place(MARGIN, self.y, number, Font::Bold, self.size);
+            self.add(MARGIN + indent, width, step, Font::Regular, self.size);
+            self.y -= self.line_height() * 0.25;
+        }
+    }
+}
+
+/// Lays out `recipe` with a body font size of `size` points.
+///
+/// Returns the lines of text, and whether everything fit on the page.
+fn lay_out(recipe: &ParsedRecipe, size: f64) -> (Vec<PlacedText>, bool) {
+    let mut layout = Layout {
+        placed: vec![],
+        y: PAGE_HEIGHT - MARGIN,
+        size,
+    };
+    let width = Layout::CONTENT_WIDTH;
+
+    layout.add(MARGIN, width, &recipe.name, Font::Bold, size * 1.8);
+
+    let mut details = vec![];
+    if !recipe.categories.is_empty() {
+        details.push(recipe.categories.join(", "));
+    }
+    if recipe.duration > Duration::zero() {
+        details.push(format_duration(recipe.duration));
+    }
+    if !details.is_empty() {
+        layout.add(MARGIN, width, &details.join(" | "), Font::Regular, size);
+    }
+
+    layout.add_heading("Ingredients");
+    let entries = recipe
+        .ingredients
+        .iter()
+        .map(|ingredient| ingredient_entry(ingredient, &ingredient.name))
+        .collect::<Vec<_>>();
+    layout.add_columns(&entries);
+
+    layout.add_heading("Steps");
+    layout.add_steps(&recipe.instructions);
+
+    let fits = layout.y >= MARGIN;
+    (layout.placed, fits)
+}
+
+/// Writes the PDF content stream that draws `placed`.
+fn content_stream(placed: &[PlacedText]) -> Vec<u8> {
+    let mut stream = vec![];
+    for text in placed {
+        let mut operators = String::new();
+        let _ = write!(
+            operators,
+            "BT /{} {:.2} Tf {:.2} {:.2} Td ",
+            text.font.resource_name(),
+            text.size,
+            text.x,
+            text.y
+        );
+        stream.extend_from_slice(operators.as_bytes());
+        stream.extend(pdf_string(&text.text));
+        stream.extend_from_slice(b" Tj ET\n");
+    }
+    stream
+}
+
+/// Converts `recipe` into a one-page PDF recipe card.
+///
+/// The largest font size at which the whole recipe fits on the page is used.
+/// If it does not fit even at the smallest size, text beyond the bottom of the
+/// page is left out.
+pub fn to_pdf(recipe: &ParsedRecipe) -> Vec<u8> {
+    let mut size = MAX_FONT_SIZE;
+    let placed = loop {
+        let (placed, fits) = lay_out(recipe, size);
+        if fits || size <= MIN_FONT_SIZE {
+            break placed;
+        }
+        size -= 0.5;
+    };
+    let placed = placed
+        .into_iter()
+        .filter(|text| text.y >= MARGIN / 2.0)
+        .collect::<Vec<_>>();
+
+    let stream = content_stream(&placed);
+    let mut title = b"<< /Title ".to_vec();
+    title.extend(pdf_string(&recipe.name));
+    title.extend_from_slice(b" /Producer (recipes) >>");
+
+    let mut contents =
+        format!("<< /Length {} >>\nstream\n", stream.len()).into_bytes();
+    contents.extend(stream);
+    contents.extend_from_slice(b"\nendstream");
+
+    let objects: Vec<Vec<u8>> = vec![
+        b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+        b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_vec(),
+        format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {PAGE_WIDTH} \
+             {PAGE_HEIGHT}] /Contents 4 0 R /Resources << /Font << \
+             /F1 5 0 R /F2 6 0 R >> >> >>"
+        )
+        .into_bytes(),
+        contents,
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica \
+          /Encoding /WinAnsiEncoding >>"
+            .to_vec(),
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold \
+          /Encoding /WinAnsiEncoding >>"
+            .to_vec(),
+        title,
+    ];
+
+    let mut pdf = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+    let mut offsets = vec![];
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend(format!("{} 0 obj\n", index + 1).into_bytes());
+        pdf.extend_from_slice(object);
+        pdf.extend_from_slice(b"\nendobj\n");
+    }
+
+    let xref_offset = pdf.len();
+    let mut trailer =
+        format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+    for offset in offsets {
+        let _ = writeln!(trailer, "{offset:010} 00000 n ");
+    }
+    let _ = write!(
+        trailer,
+        "trailer\n<< /Size {} /Root 1 0 R /Info {} 0 R >>\nstartxref\n\
+         {xref_offset}\n%%EOF\n",
+        objects.len() + 1,
+        objects.len()
+    );
+    pdf.extend(trailer.into_bytes());
+    pdf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::ParsedIngredient;
+    use crate::models::MeasurementType;
+
+    #[test]
+    fn test_to_pdf() {
+        let mut recipe = ParsedRecipe::new("Crêpes (thin)".to_owned());
+        for name in ["Flour", "Milk", "Eggs"] {
+            recipe.ingredients.push(ParsedIngredient {
+                name: name.to_owned(),
+                quantity: 1.0,
+                measurement: MeasurementType::Count,
+            });
+        }
+        recipe.instructions.push("Whisk everything. ".repeat(200));
+
+        let pdf = to_pdf(&recipe);
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.starts_with("%PDF-1.4"));
+        assert!(text.ends_with("%%EOF\n"));
+        assert_eq!(text.matches("/Type /Page ").count(), 1);
+
+        // The title is Latin-1 encoded and its parentheses are escaped.
+        let title = b"(Cr\xeapes \\(thin\\))";
+        assert!(pdf.windows(title.len()).any(|window| window == title));
+
+        // Each cross-reference entry points at its object.
+        let xref_start = text.find("xref\n").unwrap();
+        for (index, line) in
+            text[xref_start..].lines().skip(3).take(7).enumerate()
+        {
+            let offset: usize = line[..10].parse().unwrap();
+            let header = format!("{} 0 obj", index + 1);
+            assert_eq!(&pdf[offset..offset + header.len()], header.as_bytes());
+        }
+    }
+}
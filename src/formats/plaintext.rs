@@ -0,0 +1,312 @@
+//! Parsing of plain-text recipes with "Ingredients:" and "Directions:"
+//! sections.
+
+use super::{parse_ingredient_line, BulkEntry, ParsedRecipe};
+use crate::units::parse_duration;
+
+/// Headings that start a recipe's ingredient list.
+const INGREDIENT_HEADINGS: &[&str] = &["ingredients"];
+
+/// Headings that start a recipe's directions.
+const DIRECTION_HEADINGS: &[&str] = &[
+    "directions",
+    "instructions",
+    "method",
+    "steps",
+    "preparation",
+];
+
+/// Metadata keys that may appear between a recipe's title and its
+/// ingredients.
+const METADATA_KEYS: &[&str] = &[
+    "categories",
+    "category",
+    "tags",
+    "time",
+    "total time",
+    "prep time",
+    "cook time",
+    "serves",
+    "servings",
+    "yield",
+];
+
+/// Returns whether `line` is (case-insensitively) one of `headings`, with or
+/// without a trailing colon.
+fn is_heading(line: &str, headings: &[&str]) -> bool {
+    let line = line.trim().trim_end_matches(':').trim().to_lowercase();
+    headings.contains(&line.as_str())
+}
+
+/// Returns the key and value of `line` if it is a metadata line such as
+/// "Categories: Desserts".
+fn metadata(line: &str) -> Option<(String, &str)> {
+    let (key, value) = line.split_once(':')?;
+    let key = key.trim().to_lowercase();
+    METADATA_KEYS
+        .contains(&key.as_str())
+        .then_some((key, value.trim()))
+}
+
+/// Returns whether `line` is decoration, such as a row of dashes.
+fn is_separator(line: &str) -> bool {
+    let line = line.trim();
+    !line.is_empty() && line.chars().all(|c| "-=*_~#".contains(c))
+}
+
+/// Strips a step number such as "1.", "2)" or "Step 3:" from the start of
+/// `line`, if there is one.
+fn strip_step_number(line: &str) -> Option<&str> {
+    let line = line.trim();
+    let without_prefix = line
+        .get(..5)
+        .filter(|prefix| prefix.eq_ignore_ascii_case("step "))
+        .map_or(line, |_| &line[5..]);
+    let digits = without_prefix
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(without_prefix.len());
+    if digits == 0 {
+        return None;
+    }
+    let rest = &without_prefix[digits..];
+    let rest = rest.strip_prefix(['.', ')', ':'])?;
+    Some(rest.trim_start())
+}
+
+/// Splits directions into steps.
+///
+/// Numbered lines start new steps. Without numbering, blank lines separate
+/// steps, or every line is a step if there are no blank lines.
+fn parse_directions(lines: &[&str]) -> Vec<String> {
+    let lines = lines
+        .iter()
+        .filter(|line| !is_separator(line))
+        .map(|line| line.trim())
+        .collect::<Vec<_>>();
+    let numbered = lines.iter().any(|line| strip_step_number(line).is_some());
+    let has_blank_lines = lines
+        .iter()
+        .skip_while(|line| line.is_empty())
+        .any(|line| line.is_empty());
+
+    let mut steps: Vec<String> = vec![];
+    let mut step = String::new();
+    for line in lines {
+        let number_stripped = strip_step_number(line).filter(|_| numbered);
+        let starts_step = number_stripped.is_some()
+            || (!numbered && !has_blank_lines)
+            || (!numbered && line.is_empty());
+        if starts_step && !step.is_empty() {
+            steps.push(std::mem::take(&mut step));
+        }
+        let text = number_stripped.unwrap_or(line);
+        if !text.is_empty() {
+            if !step.is_empty() {
+                step.push(' ');
+            }
+            step.push_str(text);
+        }
+    }
+    if !step.is_empty() {
+        steps.push(step);
+    }
+    steps
+}
+
+/// Parses a single recipe, given its title and the lines following it, adding
+/// a warning to `warnings` for each part that is ignored.
+fn parse_recipe(
+    title: &str,
+    lines: &[&str],
+    warnings: &mut Vec<String>,
+) -> Result<ParsedRecipe, String> {
+    let mut recipe = ParsedRecipe::new(title.trim().to_owned());
+
+    let ingredients_start = lines
+        .iter()
+        .position(|line| is_heading(line, INGREDIENT_HEADINGS))
+        .ok_or_else(|| "Recipe has no ingredients section".to_owned())?;
+
+    for line in &lines[..ingredients_start] {
+        let Some((key, value)) = metadata(line) else {
+            continue;
+        };
+        match key.as_str() {
+            "categories" | "category" | "tags" => recipe.categories.extend(
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|category| !category.is_empty())
+                    .map(str::to_owned),
+            ),
+            "time" | "total time" => match parse_duration(value) {
+                Some(duration) => recipe.duration = duration,
+                None => {
+                    warnings.push(format!("Ignored invalid time \"{value}\""));
+                }
+            },
+            _ => {}
+        }
+    }
+
+    let body = &lines[ingredients_start + 1..];
+    let directions_start = body
+        .iter()
+        .position(|line| is_heading(line, DIRECTION_HEADINGS));
+
+    // Without a directions heading, the ingredients end at the first blank
+    // line after them.
+    let (ingredient_lines, direction_lines) =
+        if let Some(start) = directions_start {
+            (&body[..start], &body[start + 1..])
+        } else {
+            let first = body
+                .iter()
+                .position(|line| !line.trim().is_empty())
+                .unwrap_or(body.len());
+            let end = body[first..]
+                .iter()
+                .position(|line| line.trim().is_empty())
+                .map_or(body.len(), |end| first + end);
+            (&body[..end], &body[end..])
+        };
+
+    recipe.ingredients = ingredient_lines
+        .iter()
+        .filter(|line| !is_separator(line))
+        .filter_map(|line| parse_ingredient_line(line))
+        .map(|line| line.ingredient)
+        .collect();
+    recipe.instructions = parse_directions(direction_lines);
+
+    Ok(recipe)
+}
+
+/// Parses every plain-text recipe in `text`.
+///
+/// Each recipe starts with a title line, optionally followed by metadata
+/// lines (e.g. "Categories: Desserts"), and then an "Ingredients:" heading. An
+/// optional "Directions:" heading separates the ingredients from the steps.
+///
+/// Each recipe is parsed independently, so an error in one recipe does not
+/// prevent the others from being parsed.
+pub fn parse_plain_text(text: &str) -> Vec<BulkEntry> {
+    let lines = text.lines().collect::<Vec<_>>();
+    let headings = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| is_heading(line, INGREDIENT_HEADINGS))
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+
+    // Find the title line of each recipe by working backwards from its
+    // ingredients heading.
+    let mut titles: Vec<Option<usize>> = vec![];
+    let mut search_start = 0;
+    for &heading in &headings {
+        titles.push((search_start..heading).rev().find(|&index| {
+            let line = lines[index];
+            !line.trim().is_empty()
+                && !is_separator(line)
+                && metadata(line).is_none()
+        }));
+        search_start = heading + 1;
+    }
+
+    headings
+        .iter()
+        .enumerate()
+        .map(|(index, _)| {
+            // Each recipe ends where the next one's title (or heading) starts.
+            let end = headings
+                .get(index + 1)
+                .map_or(lines.len(), |&next| titles[index + 1].unwrap_or(next));
+            let mut warnings = vec![];
+            let recipe = match titles[index] {
+                Some(title) => parse_recipe(
+                    lines[title],
+                    &lines[title + 1..end],
+                    &mut warnings,
+                ),
+                None => Err("Recipe has no title".to_owned()),
+            };
+            let title = recipe.as_ref().map_or_else(
+                |_| format!("Recipe {}", index + 1),
+                |recipe| recipe.name.clone(),
+            );
+            BulkEntry {
+                title,
+                recipe,
+                warnings,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::MeasurementType;
+
+    #[test]
+    fn test_parse() {
+        let text = "\
+Banana Bread
+Categories: Breads, Snacks
+Time: 1 hour 10 minutes
+
+Ingredients:
+- 3 ripe bananas, mashed
+- 1/3 cup melted butter
+- 1 1/2 cups flour
+- pinch of salt
+
+Directions:
+1. Preheat the oven.
+2. Mix everything together
+   in a bowl.
+3. Bake.
+
+=====
+
+Toast
+Time: A while
+Ingredients:
+1 baguette
+
+Toast the bread.
+Eat it.
+";
+        let entries = parse_plain_text(text);
+        assert_eq!(entries.len(), 2);
+
+        let bread = entries[0].recipe.as_ref().unwrap();
+        assert_eq!(bread.name, "Banana Bread");
+        assert_eq!(bread.categories, vec!["Breads", "Snacks"]);
+        assert_eq!(bread.duration.num_minutes(), 70);
+        let names = bread
+            .ingredients
+            .iter()
+            .map(|ingredient| ingredient.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["ripe bananas", "butter", "flour", "salt"]);
+        assert_eq!(bread.ingredients[1].measurement, MeasurementType::Volume);
+        assert_eq!(
+            bread.instructions,
+            vec![
+                "Preheat the oven.",
+                "Mix everything together in a bowl.",
+                "Bake.",
+            ]
+        );
+
+        let toast = entries[1].recipe.as_ref().unwrap();
+        assert_eq!(toast.name, "Toast");
+        assert_eq!(
+            entries[1].warnings,
+            vec!["Ignored invalid time \"A while\""]
+        );
+        assert_eq!(toast.ingredients[0].name, "baguette");
+        assert_eq!(toast.instructions, vec!["Toast the bread.", "Eat it."]);
+    }
+}
@@ -0,0 +1,51 @@
+//! Decoding of recipe files that may not be UTF-8, such as Meal-Master
+//! exports written by older programs.
+
+/// The characters of the Windows-1252 bytes `0x80` to `0x9F`. Bytes that
+/// Windows-1252 doesn't define are mapped to the control characters with the
+/// same code points.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ',
+    '\u{8D}', 'Ž', '\u{8F}', '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜',
+    '™', 'š', '›', 'œ', '\u{9D}', 'ž', 'Ÿ',
+];
+
+/// Decodes `bytes` as Windows-1252.
+fn decode_windows_1252(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&byte| match byte {
+            0x80..=0x9F => WINDOWS_1252_HIGH[usize::from(byte - 0x80)],
+            _ => char::from(byte),
+        })
+        .collect()
+}
+
+/// Decodes the text in `bytes` line by line: each line is read as UTF-8 if
+/// it is valid UTF-8, and as Windows-1252 otherwise.
+///
+/// Files that mix encodings (e.g. recipes collected from different programs)
+/// are common, so one line in a legacy encoding doesn't affect the others.
+pub fn decode_text(bytes: &[u8]) -> String {
+    bytes
+        .split_inclusive(|&byte| byte == b'\n')
+        .map(|line| {
+            std::str::from_utf8(line)
+                .map_or_else(|_| decode_windows_1252(line), str::to_owned)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_text() {
+        assert_eq!(decode_text("Crème brûlée\n".as_bytes()), "Crème brûlée\n");
+        assert_eq!(
+            decode_text(b"Cr\xe8me br\xfbl\xe9e \x96 \x80\nna\xc3\xafve"),
+            "Crème brûlée – €\nnaïve"
+        );
+    }
+}
@@ -0,0 +1,141 @@
+//! Rendering of `ParsedRecipe`s as Markdown and plain text, for reading,
+//! pasting into chat, and printing.
+
+use std::fmt::Write;
+
+use chrono::Duration;
+
+use super::{ParsedIngredient, ParsedRecipe};
+use crate::units::{format_duration, format_quantity};
+
+/// Escapes characters in `text` that Markdown would otherwise interpret.
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\`*_[]#<>|".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Formats an ingredient list entry, e.g. "250 g Flour". Ingredients without
+/// a quantity are listed by name only.
+pub fn ingredient_entry(ingredient: &ParsedIngredient, name: &str) -> String {
+    if ingredient.quantity > 0.0 {
+        format!(
+            "{} {name}",
+            format_quantity(ingredient.quantity, ingredient.measurement)
+        )
+    } else {
+        name.to_owned()
+    }
+}
+
+/// Converts `recipe` into a Markdown document.
+pub fn to_markdown(recipe: &ParsedRecipe) -> String {
+    let mut output = format!("# {}\n\n", escape_markdown(&recipe.name));
+
+    if !recipe.categories.is_empty() {
+        let categories = recipe
+            .categories
+            .iter()
+            .map(|category| escape_markdown(category))
+            .collect::<Vec<_>>();
+        let _ = writeln!(output, "**Categories:** {}  ", categories.join(", "));
+    }
+    if recipe.duration > Duration::zero() {
+        let _ =
+            writeln!(output, "**Time:** {}", format_duration(recipe.duration));
+    }
+    if !recipe.categories.is_empty() || recipe.duration > Duration::zero() {
+        output.push('\n');
+    }
+
+    output.push_str("## Ingredients\n\n");
+    for ingredient in &recipe.ingredients {
+        let name = escape_markdown(&ingredient.name);
+        let _ = writeln!(output, "- {}", ingredient_entry(ingredient, &name));
+    }
+
+    output.push_str("\n## Instructions\n\n");
+    for (index, step) in recipe.instructions.iter().enumerate() {
+        let _ = writeln!(output, "{}. {}", index + 1, escape_markdown(step));
+    }
+
+    output
+}
+
+/// Converts `recipe` into plain text, using underlines for headings.
+pub fn to_plain_text(recipe: &ParsedRecipe) -> String {
+    let underline = |text: &str, c: char| {
+        std::iter::repeat_n(c, text.chars().count()).collect::<String>()
+    };
+
+    let mut output =
+        format!("{}\n{}\n\n", recipe.name, underline(&recipe.name, '='));
+
+    if !recipe.categories.is_empty() {
+        let _ =
+            writeln!(output, "Categories: {}", recipe.categories.join(", "));
+    }
+    if recipe.duration > Duration::zero() {
+        let _ = writeln!(output, "Time: {}", format_duration(recipe.duration));
+    }
+    if !recipe.categories.is_empty() || recipe.duration > Duration::zero() {
+        output.push('\n');
+    }
+
+    output.push_str("Ingredients\n-----------\n");
+    for ingredient in &recipe.ingredients {
+        let _ = writeln!(
+            output,
+            "- {}",
+            ingredient_entry(ingredient, &ingredient.name)
+        );
+    }
+
+    output.push_str("\nInstructions\n------------\n");
+    for (index, step) in recipe.instructions.iter().enumerate() {
+        let _ = writeln!(output, "{}. {step}", index + 1);
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::MeasurementType;
+
+    #[test]
+    fn test_to_markdown() {
+        let mut recipe = ParsedRecipe::new("Pancakes".to_owned());
+        recipe.categories.push("Breakfast".to_owned());
+        recipe.duration = Duration::minutes(20);
+        recipe.ingredients.push(ParsedIngredient {
+            name: "Flour".to_owned(),
+            quantity: 0.25,
+            measurement: MeasurementType::Mass,
+        });
+        recipe.ingredients.push(ParsedIngredient {
+            name: "Eggs".to_owned(),
+            quantity: 2.0,
+            measurement: MeasurementType::Count,
+        });
+        recipe.instructions.push("Mix *well*.".to_owned());
+
+        assert_eq!(
+            to_markdown(&recipe),
+            "# Pancakes\n\n\
+             **Categories:** Breakfast  \n\
+             **Time:** 20 minutes\n\n\
+             ## Ingredients\n\n\
+             - 250 g Flour\n\
+             - 2 Eggs\n\n\
+             ## Instructions\n\n\
+             1. Mix \\*well\\*.\n"
+        );
+    }
+}
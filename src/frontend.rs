@@ -1,3 +1,8 @@
+mod assets;
+mod paths;
 mod routes;
+mod utils;
 
+pub use assets::asset_url;
+pub use paths::{base_path, set_base_path};
 pub use routes::create_router;
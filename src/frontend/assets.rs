@@ -0,0 +1,206 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use axum::{
+    extract::Path,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use log::{debug, warn};
+
+use crate::frontend::paths::url;
+
+/// How long browsers may cache assets requested by their hashed paths. The
+/// content at a hashed path never changes, so this is as long as possible.
+const IMMUTABLE_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+/// How browsers may cache assets requested by their original paths. They
+/// must revalidate (using the `ETag`) before each use.
+const REVALIDATE_CACHE_CONTROL: &str = "no-cache";
+
+/// A file from the `static/` directory, embedded into the binary by the build
+/// script.
+struct Asset {
+    /// The path of the file, relative to `static/`.
+    path: &'static str,
+
+    /// The path with a hash of the file's contents inserted before its
+    /// extension.
+    hashed_path: &'static str,
+
+    content_type: &'static str,
+
+    /// A hash of the file's contents.
+    hash: &'static str,
+
+    body: &'static [u8],
+
+    /// The gzip-compressed body, if compressing it made it smaller.
+    gzip: Option<&'static [u8]>,
+
+    /// The Brotli-compressed body, if compressing it made it smaller.
+    brotli: Option<&'static [u8]>,
+}
+
+/// All embedded assets.
+static ASSETS: &[Asset] =
+    include!(concat!(env!("OUT_DIR"), "/static_assets.rs"));
+
+/// Returns the URL of the asset at `path` (relative to `static/`), including
+/// its content hash so that it can be cached indefinitely.
+pub fn asset_url(path: &str) -> String {
+    if let Some(asset) = ASSETS.iter().find(|asset| asset.path == path) {
+        url(&format!("/static/{}", asset.hashed_path))
+    } else {
+        warn!("Reference to unknown static asset {path}");
+        url(&format!("/static/{path}"))
+    }
+}
+
+/// Returns the hashed URLs of all assets.
+pub fn asset_urls() -> Vec<String> {
+    ASSETS
+        .iter()
+        .map(|asset| url(&format!("/static/{}", asset.hashed_path)))
+        .collect()
+}
+
+/// Returns a string that changes whenever the contents of any asset change.
+pub fn assets_version() -> String {
+    let mut hasher = DefaultHasher::new();
+    for asset in ASSETS {
+        asset.hash.hash(&mut hasher);
+    }
+    format!("{:016x}", hasher.finish())
+}
+
+/// Returns whether the request's `Accept-Encoding` header accepts the content
+/// coding `coding`.
+///
+/// An entry for `coding` itself takes precedence over a `*` entry, so that
+/// e.g. `gzip;q=0, *` refuses gzip.
+fn accepts_encoding(headers: &HeaderMap, coding: &str) -> bool {
+    let mut explicit_quality = None;
+    let mut wildcard_quality = None;
+    for entry in headers
+        .get_all(header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+    {
+        let mut parts = entry.split(';').map(str::trim);
+        let name = parts.next().unwrap_or("");
+        let quality = parts
+            .filter_map(|parameter| parameter.strip_prefix("q="))
+            .find_map(|quality| quality.parse::<f64>().ok())
+            .unwrap_or(1.0);
+        if name.eq_ignore_ascii_case(coding) {
+            explicit_quality = Some(quality);
+        } else if name == "*" {
+            wildcard_quality = Some(quality);
+        }
+    }
+    explicit_quality
+        .or(wildcard_quality)
+        .is_some_and(|quality| quality > 0.0)
+}
+
+/// Returns whether the request's `If-None-Match` header matches `etag`.
+fn matches_etag(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == etag || tag == "*")
+}
+
+/// Serves the asset at `path`, which may be either its hashed or original
+/// path.
+///
+/// The smallest encoding that the client accepts is chosen. Each encoding
+/// has its own `ETag`, so that conditional requests work with caches that
+/// store several encodings.
+async fn serve_asset(Path(path): Path<String>, headers: HeaderMap) -> Response {
+    let (asset, cache_control) = if let Some(asset) =
+        ASSETS.iter().find(|asset| asset.hashed_path == path)
+    {
+        (asset, IMMUTABLE_CACHE_CONTROL)
+    } else if let Some(asset) = ASSETS.iter().find(|asset| asset.path == path) {
+        (asset, REVALIDATE_CACHE_CONTROL)
+    } else {
+        debug!("Static asset {path} not found");
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let (body, encoding, etag) = match (asset.brotli, asset.gzip) {
+        (Some(body), _) if accepts_encoding(&headers, "br") => {
+            (body, Some("br"), format!("\"{}-br\"", asset.hash))
+        }
+        (_, Some(body)) if accepts_encoding(&headers, "gzip") => {
+            (body, Some("gzip"), format!("\"{}-gz\"", asset.hash))
+        }
+        _ => (asset.body, None, format!("\"{}\"", asset.hash)),
+    };
+
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static(cache_control),
+    );
+    response_headers
+        .insert(header::VARY, HeaderValue::from_static("Accept-Encoding"));
+    if let Ok(etag) = HeaderValue::from_str(&etag) {
+        response_headers.insert(header::ETAG, etag);
+    }
+
+    if matches_etag(&headers, &etag) {
+        return (StatusCode::NOT_MODIFIED, response_headers).into_response();
+    }
+
+    response_headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(asset.content_type),
+    );
+    if let Some(encoding) = encoding {
+        response_headers.insert(
+            header::CONTENT_ENCODING,
+            HeaderValue::from_static(encoding),
+        );
+    }
+
+    (response_headers, body).into_response()
+}
+
+/// Creates a router that serves the embedded static assets.
+pub fn create_router<S: Clone + Send + Sync + 'static>() -> Router<S> {
+    Router::new().route("/*path", get(serve_asset))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn test_accepts_encoding() {
+        let accepts = |accept_encoding: &'static str, coding| {
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                header::ACCEPT_ENCODING,
+                HeaderValue::from_static(accept_encoding),
+            );
+            accepts_encoding(&headers, coding)
+        };
+        assert!(accepts("gzip, br", "br"));
+        assert!(accepts("*", "gzip"));
+        assert!(!accepts("gzip;q=0, *", "gzip"));
+        assert!(!accepts("*, gzip;q=0", "gzip"));
+        assert!(accepts("br;q=0.5, *;q=0", "br"));
+        assert!(!accepts("identity", "br"));
+        assert!(!accepts_encoding(&HeaderMap::new(), "gzip"));
+    }
+}
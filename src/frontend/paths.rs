@@ -0,0 +1,25 @@
+//! The path prefix under which the site is served, for building links when
+//! the site is mounted below the root by a reverse proxy.
+
+use std::sync::OnceLock;
+
+/// The path prefix under which the site is served, without a trailing slash.
+static BASE_PATH: OnceLock<String> = OnceLock::new();
+
+/// Sets the path prefix under which the site is served, e.g. `/recipes`, or
+/// an empty string for the root. Only the first call has any effect, so this
+/// must be called before any links are built.
+pub fn set_base_path(base_path: &str) {
+    let _ = BASE_PATH.set(base_path.to_owned());
+}
+
+/// Returns the path prefix under which the site is served, without a trailing
+/// slash. Links in templates start with this.
+pub fn base_path() -> &'static str {
+    BASE_PATH.get().map_or("", String::as_str)
+}
+
+/// Returns the URL of `path`, which must start with "/", under the base path.
+pub fn url(path: &str) -> String {
+    format!("{}{path}", base_path())
+}
@@ -1,15 +1,34 @@
+mod admin;
+mod categories;
+mod ingredients;
+mod pwa;
+mod recipes;
+
 use std::sync::Arc;
 
-use axum::{routing::get, Router};
+use axum::{response::Redirect, routing::get, Router};
 
+use crate::config::AdminConfig;
 use crate::database::Database;
+use crate::frontend::assets;
+use crate::frontend::paths::url;
 
-#[allow(clippy::needless_pass_by_value)]
-pub fn create_router(_database: Arc<Database>) -> Router {
-    Router::new().route(
-        "/",
-        get(|| async {
-            "Welcome to recipes. The front end is not yet available."
-        }),
-    )
+/// Creates a router that serves all HTML pages, including the admin area if
+/// `admin_config` is present.
+pub fn create_router(
+    database: Arc<Database>,
+    admin_config: Option<AdminConfig>,
+) -> Router {
+    let mut router = Router::new()
+        .route("/", get(|| async { Redirect::to(&url("/recipes")) }))
+        .nest("/categories", categories::create_router(database.clone()))
+        .nest("/ingredients", ingredients::create_router(database.clone()))
+        .nest("/recipes", recipes::create_router(database.clone()))
+        .nest("/static", assets::create_router())
+        .merge(pwa::create_router());
+    if let Some(admin_config) = admin_config {
+        router =
+            router.nest("/admin", admin::create_router(database, admin_config));
+    }
+    router
 }
@@ -0,0 +1,312 @@
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use askama::Template;
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Response},
+    routing::{get, post},
+    Extension, Router,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use log::{debug, info, warn};
+
+use crate::api;
+use crate::config::AdminConfig;
+use crate::database::{self, Database, PoolStatistics};
+use crate::frontend::utils::{render, Error};
+use crate::logging::{self, LoggedError};
+use crate::metrics;
+use crate::proxy::ClientInfo;
+use crate::units::format_duration;
+
+/// The realm sent to browsers when asking for admin credentials.
+const REALM: &str = "Recipes admin";
+
+/// The admin dashboard, showing the state of the server.
+#[derive(Template)]
+#[template(path = "admin.html")]
+struct AdminPage {
+    /// The result of an action taken from the page, if any.
+    notice: Option<String>,
+
+    version: &'static str,
+    uptime: String,
+    schema_version: i64,
+    latest_schema_version: i64,
+    file_size: String,
+    pool: PoolStatistics,
+
+    /// The name of each table, with the number of rows in it.
+    row_counts: Vec<(String, i64)>,
+
+    recent_errors: Vec<LoggedError>,
+}
+
+/// The state shared by the admin routes.
+#[derive(Clone)]
+struct AdminState {
+    database: Arc<Database>,
+    config: Arc<AdminConfig>,
+}
+
+/// Formats a size in bytes for display, e.g. "1.5 MB".
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+
+    if bytes < 1000 {
+        return format!("{bytes} bytes");
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let mut size = bytes as f64 / 1000.0;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// Returns whether `given` equals `expected`, taking the same time whatever
+/// the contents of `given`, so that the time taken to reject a password
+/// reveals nothing about it.
+fn constant_time_eq(given: &[u8], expected: &[u8]) -> bool {
+    given.len() == expected.len()
+        && given
+            .iter()
+            .zip(expected)
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+/// Returns whether `headers` hold HTTP Basic credentials matching `config`.
+fn is_authorized(headers: &HeaderMap, config: &AdminConfig) -> bool {
+    let Some(credentials) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|encoded| BASE64.decode(encoded.trim()).ok())
+        .and_then(|decoded| String::from_utf8(decoded).ok())
+    else {
+        return false;
+    };
+    let Some((username, password)) = credentials.split_once(':') else {
+        return false;
+    };
+    // Both are compared, so that the time taken doesn't reveal which was
+    // wrong.
+    let username_matches =
+        constant_time_eq(username.as_bytes(), config.username.as_bytes());
+    let password_matches =
+        constant_time_eq(password.as_bytes(), config.password.as_bytes());
+    username_matches && password_matches
+}
+
+/// Returns whether a request with `headers`, from `client`, was sent by a
+/// page on this site rather than by another site.
+///
+/// Browsers send saved credentials with every request to the admin area, even
+/// forms submitted from other sites, so changes must only be made from the
+/// admin area itself.
+fn is_same_origin(headers: &HeaderMap, client: Option<&ClientInfo>) -> bool {
+    if headers
+        .get("sec-fetch-site")
+        .is_some_and(|site| site != "same-origin")
+    {
+        return false;
+    }
+    let Some(origin) = headers.get(header::ORIGIN) else {
+        // Modern browsers send `Origin` with every form submission, so this
+        // request isn't from a form on another site.
+        return true;
+    };
+    let host = client.and_then(|client| client.host.as_deref());
+    origin
+        .to_str()
+        .ok()
+        .and_then(|origin| origin.split_once("://"))
+        .is_some_and(|(_, authority)| Some(authority) == host)
+}
+
+/// Lets a request through to the admin area only if it has the admin
+/// credentials and, if it makes changes, comes from the admin area.
+///
+/// Responses are never cached, as they show the live state of the server.
+async fn require_admin(
+    State(state): State<AdminState>,
+    client: Option<Extension<ClientInfo>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let client = client.map(|Extension(client)| client);
+    if !is_authorized(request.headers(), &state.config) {
+        if request.headers().contains_key(header::AUTHORIZATION) {
+            warn!(
+                "Rejected admin credentials from {}",
+                client.map_or_else(
+                    || "unknown client".to_owned(),
+                    |client| client.to_string()
+                )
+            );
+        }
+        return (
+            StatusCode::UNAUTHORIZED,
+            [(
+                header::WWW_AUTHENTICATE,
+                format!("Basic realm=\"{REALM}\", charset=\"UTF-8\""),
+            )],
+            "Admin credentials are required.",
+        )
+            .into_response();
+    }
+    if !request.method().is_safe()
+        && !is_same_origin(request.headers(), client.as_ref())
+    {
+        warn!("Rejected cross-site admin request");
+        return (
+            StatusCode::FORBIDDEN,
+            "Cross-site requests are not allowed.",
+        )
+            .into_response();
+    }
+
+    let mut response = next.run(request).await;
+    response
+        .headers_mut()
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    response
+}
+
+/// Shows the admin dashboard, with `notice` describing the result of an
+/// action taken from it.
+async fn render_dashboard(
+    database: &Database,
+    notice: Option<String>,
+) -> Result<Html<String>, Error> {
+    let uptime = metrics::start_time()
+        .and_then(|start_time| {
+            SystemTime::now().duration_since(start_time).ok()
+        })
+        .and_then(|uptime| chrono::Duration::from_std(uptime).ok())
+        .map_or_else(|| "Unknown".to_owned(), format_duration);
+    let row_counts =
+        database.table_row_counts().await.map_err(Error::from_db)?;
+
+    render(&AdminPage {
+        notice,
+        version: env!("CARGO_PKG_VERSION"),
+        uptime,
+        schema_version: database.get_version(),
+        latest_schema_version: database::latest_version(),
+        file_size: database
+            .file_size()
+            .map_or_else(|| "Unknown".to_owned(), format_size),
+        pool: database.pool_statistics(),
+        row_counts,
+        recent_errors: logging::recent_errors(),
+    })
+}
+
+/// Shows the admin dashboard.
+async fn show_dashboard(
+    State(state): State<AdminState>,
+) -> Result<Html<String>, Error> {
+    debug!("Showing admin dashboard");
+
+    render_dashboard(&state.database, None).await
+}
+
+/// Backs up the database, then shows the admin dashboard.
+async fn back_up(
+    State(state): State<AdminState>,
+) -> Result<Html<String>, Error> {
+    info!("Backing up database from admin dashboard");
+
+    let notice = match state.database.back_up().await {
+        Ok(Some(path)) => {
+            format!("Backed up the database to {}.", path.display())
+        }
+        Ok(None) => {
+            "The database is not stored in a file, so it was not backed up."
+                .to_owned()
+        }
+        Err(error) => {
+            warn!("Could not back up database: {error}");
+            format!("Could not back up the database: {error}")
+        }
+    };
+    render_dashboard(&state.database, Some(notice)).await
+}
+
+/// Vacuums the database, then shows the admin dashboard.
+async fn vacuum(
+    State(state): State<AdminState>,
+) -> Result<Html<String>, Error> {
+    info!("Vacuuming database from admin dashboard");
+
+    let notice = match state.database.vacuum().await {
+        Ok(()) => "Vacuumed the database.".to_owned(),
+        Err(error) => {
+            warn!("Could not vacuum database: {error}");
+            format!("Could not vacuum the database: {error}")
+        }
+    };
+    render_dashboard(&state.database, Some(notice)).await
+}
+
+/// Creates a router for the admin area, which is only available to those
+/// with the credentials in `config`.
+///
+/// The admin area includes `/archive`, which exports and imports the whole
+/// database, including hidden recipes.
+pub fn create_router(database: Arc<Database>, config: AdminConfig) -> Router {
+    let state = AdminState {
+        database: database.clone(),
+        config: Arc::new(config),
+    };
+    Router::new()
+        .route("/", get(show_dashboard))
+        .route("/backup", post(back_up))
+        .route("/vacuum", post(vacuum))
+        .nest("/archive", api::create_archive_router(database))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            require_admin,
+        ))
+        .with_state(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_authorized() {
+        let config = AdminConfig {
+            username: "admin".to_owned(),
+            password: "secret".to_owned(),
+        };
+        let headers = |authorization: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                header::AUTHORIZATION,
+                HeaderValue::from_static(authorization),
+            );
+            headers
+        };
+        // "admin:secret" and "admin:wrong"
+        assert!(is_authorized(&headers("Basic YWRtaW46c2VjcmV0"), &config));
+        assert!(!is_authorized(&headers("Basic YWRtaW46d3Jvbmc="), &config));
+        assert!(!is_authorized(&headers("Bearer YWRtaW46c2VjcmV0"), &config));
+        assert!(!is_authorized(&HeaderMap::new(), &config));
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(999), "999 bytes");
+        assert_eq!(format_size(1500), "1.5 KB");
+        assert_eq!(format_size(2_500_000), "2.5 MB");
+    }
+}
@@ -0,0 +1,170 @@
+use std::sync::Arc;
+
+use askama::Template;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::get,
+    Form, Router,
+};
+use log::debug;
+use serde::Deserialize;
+
+use crate::api::LISTING_LIMIT;
+use crate::database::Database;
+use crate::frontend::paths::url;
+use crate::frontend::utils::{form_error, render, Error, Link, ListPage};
+use crate::models::{Category, Model};
+
+/// A page showing a category and the recipes in it.
+#[derive(Template)]
+#[template(path = "category.html")]
+struct CategoryPage {
+    name: String,
+    recipes: Vec<Link>,
+}
+
+/// A page with a form for creating a category.
+#[derive(Template)]
+#[template(path = "categoryform.html")]
+struct CategoryFormPage {
+    name: String,
+    errors: Vec<String>,
+}
+
+/// The fields of the form for creating a category.
+#[derive(Deserialize)]
+struct CategoryForm {
+    name: String,
+}
+
+/// Shows a list of all categories.
+async fn list_categories(
+    State(database): State<Arc<Database>>,
+) -> Result<Html<String>, Error> {
+    debug!("Showing all categories");
+
+    let categories: Vec<(i64, String)> = database
+        .with_transaction(move |transaction| {
+            Box::pin(async move {
+                Ok(sqlx::query_as(
+                    "SELECT id, name FROM categories ORDER BY name LIMIT $1",
+                )
+                .bind(LISTING_LIMIT)
+                .fetch_all(&mut **transaction)
+                .await?)
+            })
+        })
+        .await
+        .map_err(Error::from_db)?;
+
+    render(&ListPage {
+        title: "Categories",
+        items: categories
+            .into_iter()
+            .map(|(id, name)| Link {
+                href: url(&format!("/categories/{id}")),
+                name,
+            })
+            .collect(),
+        new_link: Some(Link {
+            href: url("/categories/new"),
+            name: "New category".to_owned(),
+        }),
+    })
+}
+
+/// Shows the form for creating a category.
+async fn new_category() -> Result<Html<String>, Error> {
+    debug!("Showing new category form");
+
+    render(&CategoryFormPage {
+        name: String::new(),
+        errors: vec![],
+    })
+}
+
+/// Creates a category from a submitted form, then redirects to the new
+/// category's page. If the category is invalid, the form is shown again with
+/// errors.
+async fn create_category(
+    State(database): State<Arc<Database>>,
+    Form(form): Form<CategoryForm>,
+) -> Result<Response, Error> {
+    debug!("Creating category with name {} from form", form.name);
+
+    let name = form.name.trim().to_owned();
+    let result = database
+        .with_transaction(move |transaction| {
+            Box::pin(async move {
+                Category::check_name_unused(transaction, &name).await?;
+                Category::store_new(transaction, &name).await
+            })
+        })
+        .await;
+
+    match result {
+        Ok(id) => Ok(
+            Redirect::to(&url(&format!("/categories/{id}"))).into_response()
+        ),
+        Err(error) => {
+            let page = CategoryFormPage {
+                name: form.name,
+                errors: vec![form_error(error)?],
+            };
+            Ok((StatusCode::BAD_REQUEST, render(&page)?).into_response())
+        }
+    }
+}
+
+/// Shows the category with ID `category_id` and its recipes.
+async fn show_category(
+    State(database): State<Arc<Database>>,
+    Path(category_id): Path<i64>,
+) -> Result<Html<String>, Error> {
+    debug!("Showing category {category_id}");
+
+    let (category, recipes): (Category, Vec<(i64, String)>) = database
+        .with_transaction(move |transaction| {
+            Box::pin(async move {
+                let category =
+                    Category::get_filled(transaction, category_id).await?;
+                let recipes = sqlx::query_as(
+                    "SELECT recipes.id, recipes.name FROM recipes \
+                     JOIN recipes_categories \
+                     ON recipes_categories.recipe_id = recipes.id \
+                     WHERE recipes_categories.category_id = $1 \
+                     AND NOT recipes.hidden \
+                     ORDER BY recipes.name LIMIT $2",
+                )
+                .bind(category_id)
+                .bind(LISTING_LIMIT)
+                .fetch_all(&mut **transaction)
+                .await?;
+                Ok((category, recipes))
+            })
+        })
+        .await
+        .map_err(Error::from_db)?;
+
+    render(&CategoryPage {
+        name: category.name,
+        recipes: recipes
+            .into_iter()
+            .map(|(id, name)| Link {
+                href: url(&format!("/recipes/{id}")),
+                name,
+            })
+            .collect(),
+    })
+}
+
+/// Creates a router that serves all category pages.
+pub fn create_router<S>(database: Arc<Database>) -> Router<S> {
+    Router::new()
+        .route("/", get(list_categories).post(create_category))
+        .route("/new", get(new_category))
+        .route("/:category_id", get(show_category))
+        .with_state(database)
+}
@@ -0,0 +1,201 @@
+use std::sync::Arc;
+
+use askama::Template;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::get,
+    Form, Router,
+};
+use log::debug;
+use serde::Deserialize;
+
+use crate::api::LISTING_LIMIT;
+use crate::database::Database;
+use crate::frontend::paths::url;
+use crate::frontend::utils::{form_error, render, Error, Link, ListPage};
+use crate::models::{Ingredient, Model};
+use crate::units::{format_amount, parse_amount};
+
+/// A page showing an ingredient and the recipes that use it.
+#[derive(Template)]
+#[template(path = "ingredient.html")]
+struct IngredientPage {
+    name: String,
+    energy_density: String, // In kJ per 100 g.
+    recipes: Vec<Link>,
+}
+
+/// A page with a form for creating an ingredient.
+#[derive(Template)]
+#[template(path = "ingredientform.html")]
+struct IngredientFormPage {
+    name: String,
+    energy_density: String,
+    errors: Vec<String>,
+}
+
+/// The fields of the form for creating an ingredient.
+#[derive(Deserialize)]
+struct IngredientForm {
+    name: String,
+    energy_density: String, // In kJ per 100 g.
+}
+
+/// Shows a list of all ingredients.
+async fn list_ingredients(
+    State(database): State<Arc<Database>>,
+) -> Result<Html<String>, Error> {
+    debug!("Showing all ingredients");
+
+    let ingredients: Vec<(i64, String)> = database
+        .with_transaction(move |transaction| {
+            Box::pin(async move {
+                Ok(sqlx::query_as(
+                    "SELECT id, name FROM ingredients ORDER BY name LIMIT $1",
+                )
+                .bind(LISTING_LIMIT)
+                .fetch_all(&mut **transaction)
+                .await?)
+            })
+        })
+        .await
+        .map_err(Error::from_db)?;
+
+    render(&ListPage {
+        title: "Ingredients",
+        items: ingredients
+            .into_iter()
+            .map(|(id, name)| Link {
+                href: url(&format!("/ingredients/{id}")),
+                name,
+            })
+            .collect(),
+        new_link: Some(Link {
+            href: url("/ingredients/new"),
+            name: "New ingredient".to_owned(),
+        }),
+    })
+}
+
+/// Shows the form for creating an ingredient.
+async fn new_ingredient() -> Result<Html<String>, Error> {
+    debug!("Showing new ingredient form");
+
+    render(&IngredientFormPage {
+        name: String::new(),
+        energy_density: String::new(),
+        errors: vec![],
+    })
+}
+
+/// Creates an ingredient from a submitted form, then redirects to the new
+/// ingredient's page. If the ingredient is invalid, the form is shown again
+/// with errors.
+async fn create_ingredient(
+    State(database): State<Arc<Database>>,
+    Form(form): Form<IngredientForm>,
+) -> Result<Response, Error> {
+    debug!("Creating ingredient with name {} from form", form.name);
+
+    let energy_density = form.energy_density.trim();
+    let result = if energy_density.is_empty() {
+        Ok(0.0)
+    } else {
+        parse_amount(energy_density)
+            .ok_or_else(|| "Energy density must be a number".to_owned())
+    };
+
+    let result = match result {
+        Ok(energy_density) => {
+            let name = form.name.trim().to_owned();
+            database
+                .with_transaction(move |transaction| {
+                    Box::pin(async move {
+                        Ingredient::check_name_unused(transaction, &name)
+                            .await?;
+                        // kJ/(100 g) to J/kg
+                        Ingredient::store_new(
+                            transaction,
+                            &name,
+                            energy_density * 10_000.0,
+                        )
+                        .await
+                    })
+                })
+                .await
+                .map_err(form_error)
+        }
+        Err(message) => Err(Ok(message)),
+    };
+
+    match result {
+        Ok(id) => {
+            Ok(Redirect::to(&url(&format!("/ingredients/{id}")))
+                .into_response())
+        }
+        Err(message) => {
+            let page = IngredientFormPage {
+                name: form.name,
+                energy_density: form.energy_density,
+                errors: vec![message?],
+            };
+            Ok((StatusCode::BAD_REQUEST, render(&page)?).into_response())
+        }
+    }
+}
+
+/// Shows the ingredient with ID `ingredient_id` and the recipes that use it
+/// in any of their versions.
+async fn show_ingredient(
+    State(database): State<Arc<Database>>,
+    Path(ingredient_id): Path<i64>,
+) -> Result<Html<String>, Error> {
+    debug!("Showing ingredient {ingredient_id}");
+
+    let (ingredient, recipes): (Ingredient, Vec<(i64, String)>) = database
+        .with_transaction(move |transaction| {
+            Box::pin(async move {
+                let ingredient =
+                    Ingredient::get_filled(transaction, ingredient_id).await?;
+                let recipes = sqlx::query_as(
+                    "SELECT DISTINCT recipes.id, recipes.name FROM recipes \
+                     JOIN recipes_ingredients \
+                     ON recipes_ingredients.recipe_id = recipes.id \
+                     WHERE recipes_ingredients.ingredient_id = $1 \
+                     AND NOT recipes.hidden \
+                     ORDER BY recipes.name LIMIT $2",
+                )
+                .bind(ingredient_id)
+                .bind(LISTING_LIMIT)
+                .fetch_all(&mut **transaction)
+                .await?;
+                Ok((ingredient, recipes))
+            })
+        })
+        .await
+        .map_err(Error::from_db)?;
+
+    render(&IngredientPage {
+        name: ingredient.name,
+        // J/kg to kJ/(100 g)
+        energy_density: format_amount(ingredient.energy_density / 10_000.0),
+        recipes: recipes
+            .into_iter()
+            .map(|(id, name)| Link {
+                href: url(&format!("/recipes/{id}")),
+                name,
+            })
+            .collect(),
+    })
+}
+
+/// Creates a router that serves all ingredient pages.
+pub fn create_router<S>(database: Arc<Database>) -> Router<S> {
+    Router::new()
+        .route("/", get(list_ingredients).post(create_ingredient))
+        .route("/new", get(new_ingredient))
+        .route("/:ingredient_id", get(show_ingredient))
+        .with_state(database)
+}
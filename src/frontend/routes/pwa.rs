@@ -0,0 +1,128 @@
+use askama::Template;
+use axum::{
+    http::header,
+    response::{Html, IntoResponse},
+    routing::get,
+    Json, Router,
+};
+use log::debug;
+use serde::Serialize;
+
+use crate::frontend::assets::{asset_url, asset_urls, assets_version};
+use crate::frontend::paths::{base_path, url};
+use crate::frontend::utils::{render, render_text, Error};
+
+/// The path of the page shown when navigating to a page that is not cached
+/// while offline.
+const OFFLINE_PATH: &str = "/offline";
+
+/// An icon listed in the web app manifest.
+#[derive(Serialize)]
+struct ManifestIcon {
+    src: String,
+    sizes: &'static str,
+    #[serde(rename = "type")]
+    media_type: &'static str,
+}
+
+/// The web app manifest, which lets browsers install the site as an app.
+#[derive(Serialize)]
+struct Manifest {
+    name: &'static str,
+    short_name: &'static str,
+    description: &'static str,
+    start_url: String,
+    scope: String,
+    display: &'static str,
+    background_color: &'static str,
+    theme_color: &'static str,
+    icons: Vec<ManifestIcon>,
+}
+
+/// The service worker script, which makes the site usable offline.
+#[derive(Template)]
+#[template(path = "serviceworker.js", escape = "none")]
+struct ServiceWorker {
+    /// Changes whenever any static asset changes, so that outdated caches
+    /// are replaced.
+    version: String,
+
+    /// The URLs to cache as soon as the service worker is installed.
+    precache_urls: Vec<String>,
+
+    /// The path prefix under which the site is served.
+    base_path: &'static str,
+
+    offline_path: String,
+}
+
+/// The page shown for uncached pages while offline.
+#[derive(Template)]
+#[template(path = "offline.html")]
+struct OfflinePage;
+
+/// Serves the web app manifest.
+async fn manifest() -> impl IntoResponse {
+    debug!("Serving web app manifest");
+
+    (
+        [(header::CONTENT_TYPE, "application/manifest+json")],
+        Json(Manifest {
+            name: "Recipes",
+            short_name: "Recipes",
+            description: "Create, edit, and view your recipes",
+            start_url: url("/recipes"),
+            scope: url("/"),
+            display: "standalone",
+            background_color: "#ffffff",
+            theme_color: "#ffffff",
+            icons: vec![ManifestIcon {
+                src: asset_url("icon.svg"),
+                sizes: "any",
+                media_type: "image/svg+xml",
+            }],
+        }),
+    )
+}
+
+/// Serves the service worker script.
+///
+/// The script must be served from the root of the site (below the base path)
+/// so that it can control every page, and must be revalidated before each use
+/// so that browsers pick up new versions promptly.
+async fn service_worker() -> Result<impl IntoResponse, Error> {
+    debug!("Serving service worker");
+
+    let mut precache_urls = asset_urls();
+    precache_urls.push(url(OFFLINE_PATH));
+    let script = render_text(&ServiceWorker {
+        version: assets_version(),
+        precache_urls,
+        base_path: base_path(),
+        offline_path: url(OFFLINE_PATH),
+    })?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/javascript; charset=utf-8"),
+            (header::CACHE_CONTROL, "no-cache"),
+        ],
+        script,
+    ))
+}
+
+/// Shows the page used in place of uncached pages while offline.
+async fn offline() -> Result<Html<String>, Error> {
+    debug!("Showing offline page");
+
+    render(&OfflinePage)
+}
+
+/// Creates a router that serves the files that make the site an installable,
+/// offline-capable web app.
+pub fn create_router<S: Clone + Send + Sync + 'static>() -> Router<S> {
+    Router::new()
+        .route("/manifest.webmanifest", get(manifest))
+        .route("/service-worker.js", get(service_worker))
+        .route(OFFLINE_PATH, get(offline))
+}
@@ -0,0 +1,330 @@
+use std::sync::Arc;
+
+use askama::Template;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::get,
+    Form, Router,
+};
+use chrono::Duration;
+use log::debug;
+
+use crate::api::LISTING_LIMIT;
+use crate::database::Database;
+use crate::frontend::paths::url;
+use crate::frontend::utils::{form_error, render, Error, Link, ListPage};
+use crate::models::{Category, Model, Recipe, RecipeVersion, RecipeVersionID};
+use crate::units::{format_duration, format_quantity};
+
+mod versions;
+
+/// An ingredient as shown in a recipe's ingredient list.
+struct IngredientEntry {
+    href: String,
+    name: String,
+    quantity: String,
+}
+
+/// A recipe version as shown on a recipe page.
+struct VersionView {
+    id: i64,
+    created: String,
+    duration: Option<String>,
+    ingredients: Vec<IngredientEntry>,
+    instructions: Vec<String>,
+}
+
+impl From<RecipeVersion> for VersionView {
+    /// Converts a filled `RecipeVersion` into a form ready to be shown.
+    fn from(version: RecipeVersion) -> Self {
+        Self {
+            id: version.id,
+            created: version.created.format("%Y-%m-%d %H:%M UTC").to_string(),
+            duration: (version.duration > Duration::zero())
+                .then(|| format_duration(version.duration)),
+            ingredients: version
+                .ingredients
+                .iter()
+                .map(|ingredient| IngredientEntry {
+                    href: url(&format!(
+                        "/ingredients/{}",
+                        ingredient.ingredient.id
+                    )),
+                    name: ingredient
+                        .ingredient
+                        .get()
+                        .map(|value| value.name.clone())
+                        .unwrap_or_default(),
+                    quantity: format_quantity(
+                        ingredient.quantity,
+                        ingredient.measurement,
+                    ),
+                })
+                .collect(),
+            instructions: version
+                .instructions
+                .into_iter()
+                .map(|instruction| instruction.text)
+                .collect(),
+        }
+    }
+}
+
+/// A page showing one version of a recipe.
+#[derive(Template)]
+#[template(path = "recipe.html")]
+struct RecipePage {
+    id: i64,
+    name: String,
+    categories: Vec<Link>,
+    version: Option<VersionView>,
+    versions: Vec<Link>,
+}
+
+/// A category that can be chosen for a new recipe.
+struct CategoryChoice {
+    id: i64,
+    name: String,
+    checked: bool,
+}
+
+/// A page with a form for creating a recipe.
+#[derive(Template)]
+#[template(path = "recipeform.html")]
+struct RecipeFormPage {
+    name: String,
+    categories: Vec<CategoryChoice>,
+    errors: Vec<String>,
+}
+
+/// Shows a list of all recipes.
+async fn list_recipes(
+    State(database): State<Arc<Database>>,
+) -> Result<Html<String>, Error> {
+    debug!("Showing all recipes");
+
+    let recipes: Vec<(i64, String)> = database
+        .with_transaction(move |transaction| {
+            Box::pin(async move {
+                Ok(sqlx::query_as(
+                    "SELECT id, name FROM recipes WHERE NOT hidden \
+                     ORDER BY name LIMIT $1",
+                )
+                .bind(LISTING_LIMIT)
+                .fetch_all(&mut **transaction)
+                .await?)
+            })
+        })
+        .await
+        .map_err(Error::from_db)?;
+
+    render(&ListPage {
+        title: "Recipes",
+        items: recipes
+            .into_iter()
+            .map(|(id, name)| Link {
+                href: url(&format!("/recipes/{id}")),
+                name,
+            })
+            .collect(),
+        new_link: Some(Link {
+            href: url("/recipes/new"),
+            name: "New recipe".to_owned(),
+        }),
+    })
+}
+
+/// Shows the form for creating a recipe.
+async fn new_recipe(
+    State(database): State<Arc<Database>>,
+) -> Result<Html<String>, Error> {
+    debug!("Showing new recipe form");
+
+    recipe_form_page(&database, String::new(), &[], vec![])
+        .await
+        .map(|(_, page)| page)
+}
+
+/// Creates a recipe from a submitted form, then redirects to the form for
+/// adding the recipe's first version. If the recipe is invalid, the form is
+/// shown again with errors.
+///
+/// The form's fields are a `name` and any number of `category` IDs.
+async fn create_recipe(
+    State(database): State<Arc<Database>>,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> Result<Response, Error> {
+    let mut name = String::new();
+    let mut category_ids = vec![];
+    for (key, value) in fields {
+        match key.as_str() {
+            "name" => name = value,
+            "category" => category_ids.extend(value.parse::<i64>().ok()),
+            _ => {}
+        }
+    }
+
+    debug!("Creating recipe with name {name} from form");
+
+    let trimmed_name = name.trim().to_owned();
+    let categories = category_ids
+        .iter()
+        .map(|&id| Category {
+            id,
+            name: String::new(),
+        })
+        .collect();
+    let result = database
+        .with_transaction(move |transaction| {
+            Box::pin(async move {
+                Recipe::store_new(transaction, &trimmed_name, categories).await
+            })
+        })
+        .await;
+
+    match result {
+        Ok(id) => {
+            Ok(Redirect::to(&url(&format!("/recipes/{id}/versions/new")))
+                .into_response())
+        }
+        Err(error) => {
+            let errors = vec![form_error(error)?];
+            let (status, page) =
+                recipe_form_page(&database, name, &category_ids, errors)
+                    .await?;
+            Ok((status, page).into_response())
+        }
+    }
+}
+
+/// Renders the form for creating a recipe, with the fields filled in with
+/// `name` and `checked_category_ids`, and with `errors` shown above it.
+///
+/// Returns the status code that the page should be shown with.
+async fn recipe_form_page(
+    database: &Database,
+    name: String,
+    checked_category_ids: &[i64],
+    errors: Vec<String>,
+) -> Result<(StatusCode, Html<String>), Error> {
+    let categories: Vec<(i64, String)> = database
+        .with_transaction(move |transaction| {
+            Box::pin(async move {
+                Ok(sqlx::query_as(
+                    "SELECT id, name FROM categories ORDER BY name LIMIT $1",
+                )
+                .bind(LISTING_LIMIT)
+                .fetch_all(&mut **transaction)
+                .await?)
+            })
+        })
+        .await
+        .map_err(Error::from_db)?;
+
+    let status = if errors.is_empty() {
+        StatusCode::OK
+    } else {
+        StatusCode::BAD_REQUEST
+    };
+    let page = render(&RecipeFormPage {
+        name,
+        categories: categories
+            .into_iter()
+            .map(|(id, name)| CategoryChoice {
+                id,
+                name,
+                checked: checked_category_ids.contains(&id),
+            })
+            .collect(),
+        errors,
+    })?;
+    Ok((status, page))
+}
+
+/// Shows version `version_id` of the recipe with ID `recipe_id`, or its
+/// latest version if `version_id` is `None`.
+async fn recipe_page(
+    database: &Database,
+    recipe_id: i64,
+    version_id: Option<i64>,
+) -> Result<Html<String>, Error> {
+    let (recipe, version) = database
+        .with_transaction(move |transaction| {
+            Box::pin(async move {
+                let recipe = Recipe::get_filled(transaction, recipe_id).await?;
+                let version_id = version_id
+                    .or_else(|| recipe.versions.keys().max().copied());
+                let version = match version_id {
+                    Some(version_id) => Some(
+                        RecipeVersion::get_filled(
+                            transaction,
+                            RecipeVersionID {
+                                recipe_id,
+                                version_id,
+                            },
+                        )
+                        .await?,
+                    ),
+                    None => None,
+                };
+                Ok((recipe, version))
+            })
+        })
+        .await
+        .map_err(Error::from_db)?;
+
+    let mut version_ids = recipe.versions.keys().copied().collect::<Vec<_>>();
+    version_ids.sort_unstable();
+
+    render(&RecipePage {
+        id: recipe.id,
+        name: recipe.name,
+        categories: recipe
+            .categories
+            .iter()
+            .map(|category| Link {
+                href: url(&format!("/categories/{}", category.id)),
+                name: category
+                    .get()
+                    .map(|value| value.name.clone())
+                    .unwrap_or_default(),
+            })
+            .collect(),
+        version: version.map(VersionView::from),
+        versions: version_ids
+            .into_iter()
+            .rev()
+            .map(|version_id| Link {
+                href: url(&format!(
+                    "/recipes/{recipe_id}/versions/{version_id}"
+                )),
+                name: format!("Version {version_id}"),
+            })
+            .collect(),
+    })
+}
+
+/// Shows the latest version of the recipe with ID `recipe_id`.
+async fn show_recipe(
+    State(database): State<Arc<Database>>,
+    Path(recipe_id): Path<i64>,
+) -> Result<Html<String>, Error> {
+    debug!("Showing recipe {recipe_id}");
+
+    recipe_page(&database, recipe_id, None).await
+}
+
+/// Creates a router that serves all recipe pages.
+pub fn create_router<S>(database: Arc<Database>) -> Router<S> {
+    Router::new()
+        .route("/", get(list_recipes).post(create_recipe))
+        .route("/new", get(new_recipe))
+        .route("/:recipe_id", get(show_recipe))
+        .nest(
+            "/:recipe_id/versions",
+            versions::create_router(database.clone()),
+        )
+        .with_state(database)
+}
@@ -0,0 +1,458 @@
+use std::sync::Arc;
+
+use askama::Template;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Form, Router,
+};
+use chrono::{offset::Utc, Duration};
+use log::debug;
+
+use super::recipe_page;
+use crate::api::LISTING_LIMIT;
+use crate::cooking::{find_timers, SuggestedTimer};
+use crate::database::Database;
+use crate::formats::{ingredient_entry, ParsedRecipe};
+use crate::frontend::paths::url;
+use crate::frontend::utils::{form_error, render, Error};
+use crate::models::{
+    Instruction, Model, QuantifiedIngredient, Recipe, RecipeVersion,
+    RecipeVersionID, Ref,
+};
+use crate::units::{
+    format_amount, format_duration, lookup_unit, parse_amount, parse_duration,
+    to_human, to_si,
+};
+
+/// Units suggested when entering an ingredient's amount.
+const SUGGESTED_UNITS: &[&str] = &[
+    "g", "kg", "oz", "lb", "ml", "l", "tsp", "tbsp", "cup", "fl oz", "pinch",
+];
+
+/// A recipe version laid out as a printable recipe card.
+#[derive(Template)]
+#[template(path = "recipecard.html")]
+struct RecipeCardPage {
+    recipe_id: i64,
+    version_id: i64,
+    name: String,
+    categories: Vec<String>,
+    duration: Option<String>,
+    ingredients: Vec<String>,
+    instructions: Vec<String>,
+}
+
+/// An instruction shown in cooking mode, along with the timers suggested by
+/// its text.
+struct CookingStep {
+    text: String,
+    timers: Vec<SuggestedTimer>,
+}
+
+/// A recipe version shown one step at a time, for following while cooking.
+#[derive(Template)]
+#[template(path = "cook.html")]
+struct CookingPage {
+    recipe_id: i64,
+    version_id: i64,
+    name: String,
+    steps: Vec<CookingStep>,
+}
+
+/// An ingredient that can be chosen in the form.
+struct IngredientChoice {
+    id: i64,
+    name: String,
+}
+
+/// A row of the form's ingredient list, as entered.
+#[derive(Default)]
+struct IngredientRow {
+    ingredient_id: Option<i64>,
+    amount: String,
+    unit: String,
+    error: Option<String>,
+}
+
+impl IngredientRow {
+    /// Returns whether `choice` is the ingredient chosen in this row.
+    fn is_selected(&self, choice: &IngredientChoice) -> bool {
+        self.ingredient_id == Some(choice.id)
+    }
+
+    /// Returns whether nothing has been entered in this row.
+    fn is_blank(&self) -> bool {
+        self.ingredient_id.is_none()
+            && self.amount.trim().is_empty()
+            && self.unit.trim().is_empty()
+    }
+
+    /// Converts this row into a `QuantifiedIngredient`.
+    ///
+    /// Returns a message describing the problem if the row is incomplete or
+    /// cannot be understood. An empty amount means a quantity of zero (e.g.
+    /// for "salt to taste"), and an empty unit means a count.
+    fn to_ingredient(&self) -> Result<QuantifiedIngredient, String> {
+        let id = self
+            .ingredient_id
+            .ok_or_else(|| "Choose an ingredient".to_owned())?;
+        let amount = if self.amount.trim().is_empty() {
+            0.0
+        } else {
+            parse_amount(&self.amount).ok_or_else(|| {
+                format!("\"{}\" is not an amount", self.amount)
+            })?
+        };
+        let unit = lookup_unit(&self.unit)
+            .ok_or_else(|| format!("\"{}\" is not a known unit", self.unit))?;
+
+        Ok(QuantifiedIngredient {
+            ingredient: Ref::new(id),
+            quantity: to_si(amount, unit),
+            measurement: unit.measurement,
+        })
+    }
+}
+
+/// A page with a form for adding a new version of a recipe.
+#[derive(Template)]
+#[template(path = "versionform.html")]
+struct VersionFormPage {
+    recipe_id: i64,
+    recipe_name: String,
+    units: &'static [&'static str],
+    ingredients: Vec<IngredientChoice>,
+    rows: Vec<IngredientRow>,
+    steps: Vec<String>,
+    duration: String,
+    errors: Vec<String>,
+}
+
+/// The entered values of the version form.
+#[derive(Default)]
+struct VersionForm {
+    rows: Vec<IngredientRow>,
+    steps: Vec<String>,
+    duration: String,
+}
+
+impl VersionForm {
+    /// Collects the form's fields, which are repeated once per ingredient row
+    /// (`amount`, `unit` and `ingredient`) or step (`step`), in order.
+    fn from_fields(fields: Vec<(String, String)>) -> Self {
+        let mut form = Self::default();
+        let mut amounts = vec![];
+        let mut units = vec![];
+        let mut ingredient_ids = vec![];
+        for (key, value) in fields {
+            match key.as_str() {
+                "amount" => amounts.push(value),
+                "unit" => units.push(value),
+                "ingredient" => ingredient_ids.push(value.parse::<i64>().ok()),
+                "step" => form.steps.push(value),
+                "duration" => form.duration = value,
+                _ => {}
+            }
+        }
+
+        let row_count =
+            amounts.len().max(units.len()).max(ingredient_ids.len());
+        form.rows = (0..row_count)
+            .map(|index| IngredientRow {
+                ingredient_id: ingredient_ids.get(index).copied().flatten(),
+                amount: amounts.get(index).cloned().unwrap_or_default(),
+                unit: units.get(index).cloned().unwrap_or_default(),
+                error: None,
+            })
+            .collect();
+        form
+    }
+
+    /// Fills in the form with the contents of `version`.
+    fn from_version(version: &RecipeVersion) -> Self {
+        Self {
+            rows: version
+                .ingredients
+                .iter()
+                .map(|ingredient| {
+                    let (amount, unit) =
+                        to_human(ingredient.quantity, ingredient.measurement);
+                    IngredientRow {
+                        ingredient_id: Some(ingredient.ingredient.id),
+                        amount: format_amount(amount),
+                        unit: unit.to_owned(),
+                        error: None,
+                    }
+                })
+                .collect(),
+            steps: version
+                .instructions
+                .iter()
+                .map(|instruction| instruction.text.clone())
+                .collect(),
+            duration: if version.duration > Duration::zero() {
+                format_duration(version.duration)
+            } else {
+                String::new()
+            },
+        }
+    }
+}
+
+/// Shows version `version_id` of the recipe with ID `recipe_id`.
+async fn show_version(
+    State(database): State<Arc<Database>>,
+    Path((recipe_id, version_id)): Path<(i64, i64)>,
+) -> Result<Html<String>, Error> {
+    debug!("Showing recipe {recipe_id} version {version_id}");
+
+    recipe_page(&database, recipe_id, Some(version_id)).await
+}
+
+/// Shows version `version_id` of the recipe with ID `recipe_id` as a recipe
+/// card, laid out for printing.
+async fn print_version(
+    State(database): State<Arc<Database>>,
+    Path((recipe_id, version_id)): Path<(i64, i64)>,
+) -> Result<Html<String>, Error> {
+    debug!("Showing printable recipe {recipe_id} version {version_id}");
+
+    let id = RecipeVersionID {
+        recipe_id,
+        version_id,
+    };
+    let recipe = database
+        .with_transaction(move |transaction| {
+            Box::pin(async move { ParsedRecipe::load(transaction, id).await })
+        })
+        .await
+        .map_err(Error::from_db)?;
+
+    render(&RecipeCardPage {
+        recipe_id,
+        version_id,
+        name: recipe.name,
+        categories: recipe.categories,
+        duration: (recipe.duration > Duration::zero())
+            .then(|| format_duration(recipe.duration)),
+        ingredients: recipe
+            .ingredients
+            .iter()
+            .map(|ingredient| ingredient_entry(ingredient, &ingredient.name))
+            .collect(),
+        instructions: recipe.instructions,
+    })
+}
+
+/// Shows version `version_id` of the recipe with ID `recipe_id` in cooking
+/// mode.
+///
+/// The page keeps its current step and timers in sync with other devices
+/// through the cooking API.
+async fn cook_version(
+    State(database): State<Arc<Database>>,
+    Path((recipe_id, version_id)): Path<(i64, i64)>,
+) -> Result<Html<String>, Error> {
+    debug!("Showing cooking mode for recipe {recipe_id} version {version_id}");
+
+    let id = RecipeVersionID {
+        recipe_id,
+        version_id,
+    };
+    let recipe = database
+        .with_transaction(move |transaction| {
+            Box::pin(async move { ParsedRecipe::load(transaction, id).await })
+        })
+        .await
+        .map_err(Error::from_db)?;
+
+    render(&CookingPage {
+        recipe_id,
+        version_id,
+        name: recipe.name,
+        steps: recipe
+            .instructions
+            .into_iter()
+            .map(|text| CookingStep {
+                timers: find_timers(&text),
+                text,
+            })
+            .collect(),
+    })
+}
+
+/// Shows the form for adding a new version of the recipe with ID
+/// `recipe_id`, filled in with the recipe's latest version.
+async fn new_version(
+    State(database): State<Arc<Database>>,
+    Path(recipe_id): Path<i64>,
+) -> Result<Html<String>, Error> {
+    debug!("Showing new version form for recipe {recipe_id}");
+
+    let latest_version = database
+        .with_transaction(move |transaction| {
+            Box::pin(async move {
+                let recipe = Recipe::get(transaction, recipe_id).await?;
+                match recipe.versions.keys().max() {
+                    Some(&version_id) => Ok(Some(
+                        RecipeVersion::get(
+                            transaction,
+                            RecipeVersionID {
+                                recipe_id,
+                                version_id,
+                            },
+                        )
+                        .await?,
+                    )),
+                    None => Ok(None),
+                }
+            })
+        })
+        .await
+        .map_err(Error::from_db)?;
+
+    let form = latest_version
+        .as_ref()
+        .map(VersionForm::from_version)
+        .unwrap_or_default();
+    version_form_page(&database, recipe_id, form, vec![]).await
+}
+
+/// Creates a new version of the recipe with ID `recipe_id` from a submitted
+/// form, then redirects to the new version's page. If the version is invalid,
+/// the form is shown again with errors.
+async fn create_version(
+    State(database): State<Arc<Database>>,
+    Path(recipe_id): Path<i64>,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> Result<Response, Error> {
+    debug!("Creating new version of recipe {recipe_id} from form");
+
+    let mut form = VersionForm::from_fields(fields);
+    form.rows.retain(|row| !row.is_blank());
+    form.steps.retain(|step| !step.trim().is_empty());
+
+    let mut errors = vec![];
+    let mut ingredients = vec![];
+    for row in &mut form.rows {
+        match row.to_ingredient() {
+            Ok(ingredient) => ingredients.push(ingredient),
+            Err(message) => row.error = Some(message),
+        }
+    }
+    if form.rows.iter().any(|row| row.error.is_some()) {
+        errors.push("Some ingredients could not be understood".to_owned());
+    }
+
+    let duration = if form.duration.trim().is_empty() {
+        Duration::zero()
+    } else {
+        parse_duration(&form.duration).unwrap_or_else(|| {
+            errors.push(format!("\"{}\" is not a duration", form.duration));
+            Duration::zero()
+        })
+    };
+
+    if !errors.is_empty() {
+        let page =
+            version_form_page(&database, recipe_id, form, errors).await?;
+        return Ok((StatusCode::BAD_REQUEST, page).into_response());
+    }
+
+    let instructions = form
+        .steps
+        .iter()
+        .map(|step| Instruction {
+            text: step.trim().to_owned(),
+        })
+        .collect();
+    let result = database
+        .with_transaction(move |transaction| {
+            Box::pin(async move {
+                RecipeVersion::store_new(
+                    transaction,
+                    recipe_id,
+                    Utc::now(),
+                    ingredients,
+                    instructions,
+                    duration,
+                )
+                .await
+            })
+        })
+        .await;
+
+    match result {
+        Ok(id) => Ok(Redirect::to(&url(&format!(
+            "/recipes/{recipe_id}/versions/{}",
+            id.version_id
+        )))
+        .into_response()),
+        Err(error) => {
+            let errors = vec![form_error(error)?];
+            let page =
+                version_form_page(&database, recipe_id, form, errors).await?;
+            Ok((StatusCode::BAD_REQUEST, page).into_response())
+        }
+    }
+}
+
+/// Renders the form for adding a new version of the recipe with ID
+/// `recipe_id`, filled in with `form`, and with `errors` shown above it.
+///
+/// A blank ingredient row and step are always added at the end.
+async fn version_form_page(
+    database: &Database,
+    recipe_id: i64,
+    mut form: VersionForm,
+    errors: Vec<String>,
+) -> Result<Html<String>, Error> {
+    let (recipe_name, ingredients) = database
+        .with_transaction(move |transaction| {
+            Box::pin(async move {
+                let recipe = Recipe::get(transaction, recipe_id).await?;
+                let ingredients: Vec<(i64, String)> = sqlx::query_as(
+                    "SELECT id, name FROM ingredients ORDER BY name LIMIT $1",
+                )
+                .bind(LISTING_LIMIT)
+                .fetch_all(&mut **transaction)
+                .await?;
+                Ok((recipe.name, ingredients))
+            })
+        })
+        .await
+        .map_err(Error::from_db)?;
+
+    form.rows.push(IngredientRow::default());
+    form.steps.push(String::new());
+
+    render(&VersionFormPage {
+        recipe_id,
+        recipe_name,
+        units: SUGGESTED_UNITS,
+        ingredients: ingredients
+            .into_iter()
+            .map(|(id, name)| IngredientChoice { id, name })
+            .collect(),
+        rows: form.rows,
+        steps: form.steps,
+        duration: form.duration,
+        errors,
+    })
+}
+
+/// Creates a router that serves version pages and forms.
+///
+/// This router must be nested under a path that provides `:recipe_id`.
+pub fn create_router<S>(database: Arc<Database>) -> Router<S> {
+    Router::new()
+        .route("/", post(create_version))
+        .route("/new", get(new_version))
+        .route("/:version_id", get(show_version))
+        .route("/:version_id/print", get(print_version))
+        .route("/:version_id/cook", get(cook_version))
+        .with_state(database)
+}
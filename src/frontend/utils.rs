@@ -0,0 +1,111 @@
+use askama::Template;
+use axum::{
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+};
+use log::error;
+
+use crate::database;
+
+/// A link to a page, shown with a human-readable name.
+pub struct Link {
+    pub href: String,
+    pub name: String,
+}
+
+/// A page that lists links to other pages (e.g. all recipes).
+#[derive(Template)]
+#[template(path = "list.html")]
+pub struct ListPage {
+    pub title: &'static str,
+    pub items: Vec<Link>,
+
+    /// A link to a page for creating a new entry, if there is one.
+    pub new_link: Option<Link>,
+}
+
+/// A page describing an error.
+#[derive(Template)]
+#[template(path = "error.html")]
+struct ErrorPage<'a> {
+    status: StatusCode,
+    message: &'a str,
+}
+
+/// Represents an error that can be converted into an HTML error page.
+#[derive(Debug)]
+pub struct Error {
+    status_code: StatusCode,
+    message: String,
+}
+
+impl Error {
+    /// Creates a frontend error from a database error.
+    ///
+    /// `RowNotFound` errors are converted into 404 errors.
+    ///
+    /// All other errors are converted into 500 errors.
+    pub fn from_db(error: database::Error) -> Self {
+        let (status_code, message) = match error {
+            database::Error::BadArguments(message) => {
+                (StatusCode::BAD_REQUEST, message)
+            }
+            database::Error::Sql(sqlx::Error::RowNotFound) => (
+                StatusCode::NOT_FOUND,
+                "The page you requested does not exist.".to_owned(),
+            ),
+            _ => {
+                error!("Internal error during query: {error}");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Something went wrong while loading this page.".to_owned(),
+                )
+            }
+        };
+        Self {
+            status_code,
+            message,
+        }
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let page = ErrorPage {
+            status: self.status_code,
+            message: &self.message,
+        };
+        match page.render() {
+            Ok(html) => (self.status_code, Html(html)).into_response(),
+            Err(_) => (self.status_code, self.message).into_response(),
+        }
+    }
+}
+
+/// Converts an error from storing a form's data into a message that can be
+/// shown next to the form.
+///
+/// Errors caused by invalid data are returned as messages. Other errors are
+/// returned as `Err`, so that they are shown as error pages.
+pub fn form_error(error: database::Error) -> Result<String, Error> {
+    match error {
+        database::Error::BadArguments(message) => Ok(message),
+        error => Err(Error::from_db(error)),
+    }
+}
+
+/// Renders `template` into an HTML response.
+pub fn render(template: &impl Template) -> Result<Html<String>, Error> {
+    render_text(template).map(Html)
+}
+
+/// Renders `template` into a string, for templates that are not HTML.
+pub fn render_text(template: &impl Template) -> Result<String, Error> {
+    template.render().map_err(|error| {
+        error!("Error while rendering template: {error}");
+        Error {
+            status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            message: "Something went wrong while loading this page.".to_owned(),
+        }
+    })
+}
@@ -0,0 +1,155 @@
+//! Health and readiness checks for uptime checkers and container
+//! orchestrators.
+
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use log::warn;
+use serde::Serialize;
+
+use crate::database::{self, Database};
+
+/// How long the database has to answer a readiness check.
+const DATABASE_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The result of checking one component of the server.
+#[derive(Serialize)]
+struct ComponentStatus {
+    /// Whether the component is working.
+    ok: bool,
+
+    /// What is wrong with the component, if anything.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl ComponentStatus {
+    fn ok() -> Self {
+        Self {
+            ok: true,
+            error: None,
+        }
+    }
+
+    fn failing(error: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            error: Some(error.into()),
+        }
+    }
+}
+
+/// The results of the readiness checks.
+#[derive(Serialize)]
+struct Readiness {
+    /// `"ready"` if every component is working, `"starting"` while the server
+    /// connects to the database and migrates it, or `"not ready"`.
+    status: &'static str,
+
+    /// Whether the database answers queries.
+    database: ComponentStatus,
+
+    /// Whether the database's schema is the version that this server expects.
+    migrations: ComponentStatus,
+
+    /// The database's schema version, if it could be read.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    schema_version: Option<i64>,
+
+    /// The schema version that this server expects.
+    expected_schema_version: i64,
+}
+
+/// Responds if the server is running.
+async fn check_health() -> Response {
+    Json(serde_json::json!({ "status": "ok" })).into_response()
+}
+
+/// Responds with the status of each component that the server needs to
+/// handle requests, with a 503 status if any of them isn't working.
+///
+/// The schema version is read from the database rather than from `database`,
+/// so that changes made by other processes are noticed.
+async fn check_readiness(
+    State(database): State<Arc<OnceLock<Arc<Database>>>>,
+) -> Response {
+    let expected_schema_version = database::latest_version();
+    let Some(database) = database.get() else {
+        let readiness = Readiness {
+            status: "starting",
+            database: ComponentStatus::failing("Connecting"),
+            migrations: ComponentStatus::failing("In progress"),
+            schema_version: None,
+            expected_schema_version,
+        };
+        return (StatusCode::SERVICE_UNAVAILABLE, Json(readiness))
+            .into_response();
+    };
+
+    let schema_version = match tokio::time::timeout(
+        DATABASE_CHECK_TIMEOUT,
+        database.read_version(),
+    )
+    .await
+    {
+        Ok(Ok(schema_version)) => Ok(schema_version),
+        Ok(Err(error)) => {
+            warn!("Database readiness check failed: {error}");
+            Err(ComponentStatus::failing("Database query failed"))
+        }
+        Err(_) => {
+            warn!("Database readiness check timed out");
+            Err(ComponentStatus::failing("Database query timed out"))
+        }
+    };
+
+    let read_version = schema_version.as_ref().ok().copied();
+    let (database_status, migrations_status) = match schema_version {
+        Ok(version) if version == expected_schema_version => {
+            (ComponentStatus::ok(), ComponentStatus::ok())
+        }
+        Ok(version) => (
+            ComponentStatus::ok(),
+            ComponentStatus::failing(format!(
+                "Database schema is at version {version}, not \
+                 {expected_schema_version}"
+            )),
+        ),
+        Err(database_status) => (
+            database_status,
+            ComponentStatus::failing("Schema version could not be read"),
+        ),
+    };
+
+    let ready = database_status.ok && migrations_status.ok;
+    let readiness = Readiness {
+        status: if ready { "ready" } else { "not ready" },
+        database: database_status,
+        migrations: migrations_status,
+        schema_version: read_version,
+        expected_schema_version,
+    };
+    let status_code = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status_code, Json(readiness)).into_response()
+}
+
+/// Creates a router that serves `/healthz`, which responds whenever the
+/// server is running, and `/readyz`, which checks that the server can handle
+/// requests using `database` once it has been connected to and migrated.
+pub fn create_router(database: Arc<OnceLock<Arc<Database>>>) -> Router {
+    Router::new()
+        .route("/healthz", get(check_health))
+        .route("/readyz", get(check_readiness))
+        .with_state(database)
+}
@@ -0,0 +1,409 @@
+//! Logging, with the ID of the request being handled added to every message
+//! logged while handling it.
+
+mod access_log;
+mod rotation;
+
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError};
+use std::thread::{self, ThreadId};
+
+pub use access_log::{log_access, AccessLog};
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde_json::json;
+
+use self::rotation::RotatingFile;
+use crate::config::{LogFormat, LoggingConfig};
+use crate::util::stringify_err;
+
+/// The header that carries request IDs, both from clients and proxies and in
+/// responses.
+static REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// The longest request ID accepted from a client.
+const MAX_REQUEST_ID_LENGTH: usize = 64;
+
+/// The log target of the SQL statements logged by `SQLx`.
+const STATEMENT_TARGET: &str = "sqlx::query";
+
+/// The start of the statement that marks a database connection as being used
+/// for a request. See `RequestMarkers`.
+const MARKER_PREFIX: &str = "SELECT 'request-id:";
+
+/// How many of the most recent errors and warnings are kept to be shown in
+/// the admin area.
+const RECENT_ERROR_COUNT: usize = 50;
+
+/// An error or warning that was logged.
+#[derive(Clone)]
+pub struct LoggedError {
+    pub time: DateTime<Utc>,
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+
+    /// The ID of the request during which it was logged, if any.
+    pub request_id: Option<Arc<str>>,
+}
+
+/// The most recent errors and warnings, oldest first.
+static RECENT_ERRORS: Mutex<VecDeque<LoggedError>> =
+    Mutex::new(VecDeque::new());
+
+/// Returns the most recently logged errors and warnings, newest first.
+pub fn recent_errors() -> Vec<LoggedError> {
+    RECENT_ERRORS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .rev()
+        .cloned()
+        .collect()
+}
+
+/// Keeps `error` as one of the most recent errors.
+fn keep_recent_error(error: LoggedError) {
+    let mut recent_errors =
+        RECENT_ERRORS.lock().unwrap_or_else(PoisonError::into_inner);
+    if recent_errors.len() == RECENT_ERROR_COUNT {
+        recent_errors.pop_front();
+    }
+    recent_errors.push_back(error);
+}
+
+tokio::task_local! {
+    /// The ID of the request being handled by the current task.
+    static REQUEST_ID: Arc<str>;
+}
+
+/// Returns the ID of the request being handled by the current task, if any.
+pub fn current_request_id() -> Option<Arc<str>> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Returns a new request ID: 16 hexadecimal digits, unique within the
+/// process and hard to guess.
+fn generate_request_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    static HASHER: OnceLock<RandomState> = OnceLock::new();
+
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!(
+        "{:016x}",
+        HASHER.get_or_init(RandomState::new).hash_one(count)
+    )
+}
+
+/// Returns whether `id` is acceptable as a request ID from a client: short,
+/// and made only of letters, digits, `-`, `_` and `.`, so that it is safe to
+/// put in logs and SQL.
+fn is_valid_request_id(id: &str) -> bool {
+    (1..=MAX_REQUEST_ID_LENGTH).contains(&id.len())
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Handles each request with an ID, which is added to every message logged
+/// while handling it and returned in the `X-Request-Id` response header.
+///
+/// The ID is taken from the request's `X-Request-Id` header if it has a
+/// valid one (e.g. from a reverse proxy), and generated otherwise.
+pub async fn assign_request_id(request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|id| id.to_str().ok())
+        .filter(|id| is_valid_request_id(id))
+        .map_or_else(generate_request_id, str::to_owned);
+
+    let mut response = REQUEST_ID
+        .scope(Arc::from(request_id.as_str()), next.run(request))
+        .await;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response
+            .headers_mut()
+            .insert(REQUEST_ID_HEADER.clone(), value);
+    }
+    response
+}
+
+/// The requests for which the database connections' threads are running
+/// statements, as marked by `RequestMarkers`.
+static STATEMENT_REQUEST_IDS: OnceLock<Mutex<HashMap<ThreadId, Arc<str>>>> =
+    OnceLock::new();
+
+/// Returns `STATEMENT_REQUEST_IDS`, locked.
+fn statement_request_ids() -> MutexGuard<'static, HashMap<ThreadId, Arc<str>>> {
+    STATEMENT_REQUEST_IDS
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Statements that mark the database connection that runs them as being used
+/// for a request, from the start statement until the end statement.
+///
+/// `SQLx` logs SQL statements from a thread for each connection rather than
+/// from the task handling the request, so the logger uses these marks to
+/// work out which request each statement belongs to. If the end statement
+/// isn't run, e.g. because the task was cancelled, the request's marks are
+/// cleared when this is dropped.
+pub struct RequestMarkers {
+    request_id: Arc<str>,
+    ended: bool,
+}
+
+impl RequestMarkers {
+    /// Returns the statement that marks a connection as used for the request.
+    pub fn start_statement(&self) -> String {
+        // Request IDs are validated, so they can't contain quotes.
+        format!("{MARKER_PREFIX}{}'", self.request_id)
+    }
+
+    /// Returns the statement that unmarks a connection.
+    pub fn end_statement() -> String {
+        format!("{MARKER_PREFIX}'")
+    }
+
+    /// Records that the end statement has been run.
+    pub fn ended(mut self) {
+        self.ended = true;
+    }
+}
+
+impl Drop for RequestMarkers {
+    fn drop(&mut self) {
+        if !self.ended {
+            statement_request_ids().retain(|_, id| *id != self.request_id);
+        }
+    }
+}
+
+/// Returns markers for database connections used by the current request, or
+/// `None` if there is no need to mark connections. They are only needed while
+/// handling requests and logging SQL statements.
+pub fn request_markers() -> Option<RequestMarkers> {
+    let request_id = current_request_id()?;
+    if !log::log_enabled!(target: STATEMENT_TARGET, log::Level::Debug) {
+        return None;
+    }
+    Some(RequestMarkers {
+        request_id,
+        ended: false,
+    })
+}
+
+/// Returns the minimum verbosity of messages from `target`: that of the most
+/// specific module in `module_verbosity` that `target` is in, or `verbosity`
+/// if there is none.
+fn verbosity_for(
+    target: &str,
+    verbosity: LevelFilter,
+    module_verbosity: &BTreeMap<String, LevelFilter>,
+) -> LevelFilter {
+    module_verbosity
+        .iter()
+        .filter(|(module, _)| {
+            target
+                .strip_prefix(module.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+        })
+        .max_by_key(|(module, _)| module.len())
+        .map_or(verbosity, |(_, &verbosity)| verbosity)
+}
+
+/// Formats `record`, logged at `time`, as a line in `format`, with the ID of
+/// the request during which it was logged, if any.
+fn format_record(
+    format: LogFormat,
+    time: DateTime<Utc>,
+    record: &Record,
+    request_id: Option<&str>,
+) -> String {
+    let time = time.to_rfc3339_opts(SecondsFormat::Nanos, true);
+    match format {
+        LogFormat::Text => {
+            let request_id = request_id
+                .map(|request_id| format!("[{request_id}] "))
+                .unwrap_or_default();
+            format!(
+                "{time} [{}] {}: {request_id}{}",
+                record.level(),
+                record.target(),
+                record.args()
+            )
+        }
+        LogFormat::Json => {
+            let mut object = json!({
+                "time": time,
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            });
+            if let Some(request_id) = request_id {
+                object["request_id"] = json!(request_id);
+            }
+            object.to_string()
+        }
+    }
+}
+
+/// Writes messages to the log file, and to standard error if configured,
+/// with the current request's ID added.
+struct Logger {
+    verbosity: LevelFilter,
+    module_verbosity: BTreeMap<String, LevelFilter>,
+    format: LogFormat,
+    file: Mutex<RotatingFile>,
+    stderr: bool,
+}
+
+/// Returns the ID of the request that a SQL statement `record` belongs to.
+///
+/// Returns `Err` if `record` is for a marker statement, which changes the
+/// request of the logging thread and isn't worth logging itself.
+fn statement_request_id(record: &Record) -> Result<Option<Arc<str>>, ()> {
+    let mut request_ids = statement_request_ids();
+    let message = record.args().to_string();
+    let marked_id = message
+        .split_once(MARKER_PREFIX)
+        .and_then(|(_, rest)| rest.split_once('\''))
+        .map(|(id, _)| id);
+    match marked_id {
+        Some("") => {
+            request_ids.remove(&thread::current().id());
+            Err(())
+        }
+        Some(id) => {
+            request_ids.insert(thread::current().id(), Arc::from(id));
+            Err(())
+        }
+        None => Ok(request_ids.get(&thread::current().id()).cloned()),
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level()
+            <= verbosity_for(
+                metadata.target(),
+                self.verbosity,
+                &self.module_verbosity,
+            )
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let request_id = match current_request_id() {
+            Some(request_id) => Some(request_id),
+            None if record.target() == STATEMENT_TARGET => {
+                let Ok(request_id) = statement_request_id(record) else {
+                    return;
+                };
+                request_id
+            }
+            None => None,
+        };
+
+        let time = Utc::now();
+        let line =
+            format_record(self.format, time, record, request_id.as_deref());
+        if self.stderr {
+            eprintln!("{line}");
+        }
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Err(error) = file.write_line(&line) {
+            // There's nowhere else to report this.
+            eprintln!("Could not write to log file: {error}");
+        }
+        drop(file);
+
+        if record.level() <= Level::Warn {
+            keep_recent_error(LoggedError {
+                time,
+                level: record.level(),
+                target: record.target().to_owned(),
+                message: record.args().to_string(),
+                request_id,
+            });
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Initializes logging based on `config`.
+pub fn init_logging(config: &LoggingConfig) -> Result<(), String> {
+    println!(
+        "Writing logs to path {} with verbosity {}",
+        config.log_file_path.display(),
+        config.verbosity
+    );
+
+    let logger = Logger {
+        verbosity: config.verbosity,
+        module_verbosity: config.module_verbosity.clone(),
+        format: config.format,
+        file: Mutex::new(RotatingFile::open(
+            &config.log_file_path,
+            config.rotation.clone(),
+        )?),
+        stderr: config.stderr,
+    };
+    stringify_err(log::set_boxed_logger(Box::new(logger)))?;
+    log::set_max_level(
+        config
+            .module_verbosity
+            .values()
+            .copied()
+            .fold(config.verbosity, Ord::max),
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_ids() {
+        let id = generate_request_id();
+        assert_eq!(id.len(), 16);
+        assert!(is_valid_request_id(&id));
+        assert_ne!(id, generate_request_id());
+
+        assert!(is_valid_request_id("f81d4fae-7dec-11d0-a765-00a0c91e6bf6"));
+        assert!(!is_valid_request_id(""));
+        assert!(!is_valid_request_id("'; DROP TABLE recipes; --"));
+        assert!(!is_valid_request_id(&"a".repeat(MAX_REQUEST_ID_LENGTH + 1)));
+    }
+
+    #[test]
+    fn test_verbosity_for() {
+        let module_verbosity = BTreeMap::from([
+            ("recipes".to_owned(), LevelFilter::Warn),
+            ("recipes::api".to_owned(), LevelFilter::Debug),
+        ]);
+        let verbosity_for = |target| {
+            verbosity_for(target, LevelFilter::Info, &module_verbosity)
+        };
+        assert_eq!(verbosity_for("sqlx::query"), LevelFilter::Info);
+        assert_eq!(verbosity_for("recipes"), LevelFilter::Warn);
+        assert_eq!(verbosity_for("recipes::database"), LevelFilter::Warn);
+        assert_eq!(verbosity_for("recipes::api::recipes"), LevelFilter::Debug);
+        assert_eq!(verbosity_for("recipes_other"), LevelFilter::Info);
+    }
+}
@@ -0,0 +1,122 @@
+//! An access log of the requests handled by the server, in the Combined Log
+//! Format used by Apache and nginx.
+
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Instant;
+
+use axum::{
+    body::HttpBody as _,
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderName},
+    middleware::Next,
+    response::Response,
+};
+use chrono::offset::Utc;
+use log::warn;
+
+use super::current_request_id;
+use super::rotation::RotatingFile;
+use crate::config::RotationConfig;
+use crate::proxy::ClientInfo;
+
+/// A file to which a line is appended for each request.
+pub struct AccessLog {
+    file: Mutex<RotatingFile>,
+}
+
+impl AccessLog {
+    /// Opens the access log at `path`, creating it if it doesn't exist. The
+    /// log is rotated according to `rotation`, if present.
+    pub fn open(
+        path: &Path,
+        rotation: Option<RotationConfig>,
+    ) -> Result<Self, String> {
+        Ok(Self {
+            file: Mutex::new(RotatingFile::open(path, rotation)?),
+        })
+    }
+
+    /// Appends `line` and a line break to the log. Failures are logged.
+    fn write_line(&self, line: &str) {
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Err(error) = file.write_line(line) {
+            warn!("Could not write to access log: {error}");
+        }
+    }
+}
+
+/// Returns the value of the header `name` in `headers` quoted as in the
+/// Combined Log Format, or `"-"` if there isn't one. Quotes, backslashes and
+/// unprintable bytes are escaped.
+fn quoted_header(headers: &HeaderMap, name: HeaderName) -> String {
+    let Some(value) = headers.get(name) else {
+        return "\"-\"".to_owned();
+    };
+    let mut quoted = "\"".to_owned();
+    for &byte in value.as_bytes() {
+        match byte {
+            b'"' | b'\\' => {
+                quoted.push('\\');
+                quoted.push(char::from(byte));
+            }
+            b' '..=b'~' => quoted.push(char::from(byte)),
+            _ => {
+                let _ = write!(quoted, "\\x{byte:02x}");
+            }
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Writes a line for each request to the access log: the Combined Log Format
+/// followed by the time taken to respond in milliseconds and the request's
+/// ID.
+///
+/// The client's address is taken from the `ClientInfo` added by
+/// `resolve_client`, so this must run after it.
+pub async fn log_access(
+    State(access_log): State<Arc<AccessLog>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let start = Instant::now();
+    let time = Utc::now().format("%d/%b/%Y:%H:%M:%S %z");
+    let client = request
+        .extensions()
+        .get::<ClientInfo>()
+        .and_then(|client| client.address)
+        .map_or_else(|| "-".to_owned(), |address| address.to_string());
+    let request_line = format!(
+        "{} {} {:?}",
+        request.method(),
+        request
+            .uri()
+            .path_and_query()
+            .map_or("/", |path_and_query| path_and_query.as_str()),
+        request.version()
+    );
+    let referer = quoted_header(request.headers(), header::REFERER);
+    let user_agent = quoted_header(request.headers(), header::USER_AGENT);
+
+    let response = next.run(request).await;
+
+    // Bodies whose size isn't known in advance (e.g. streamed ones) and
+    // empty bodies are logged as "-".
+    let size = response
+        .body()
+        .size_hint()
+        .exact()
+        .filter(|&size| size != 0)
+        .map_or_else(|| "-".to_owned(), |size| size.to_string());
+    access_log.write_line(&format!(
+        "{client} - - [{time}] \"{request_line}\" {} {size} {referer} \
+         {user_agent} {:.3} {}",
+        response.status().as_u16(),
+        start.elapsed().as_secs_f64() * 1000.0,
+        current_request_id().as_deref().unwrap_or("-"),
+    ));
+    response
+}
@@ -0,0 +1,202 @@
+//! Log files that are rotated when they grow too large or get too old.
+
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+
+use crate::config::{RotationConfig, RotationInterval};
+
+/// Returns the number of the `interval`-long period that `time` is in.
+fn period(interval: RotationInterval, time: DateTime<Utc>) -> i64 {
+    let seconds = match interval {
+        RotationInterval::Hourly => 60 * 60,
+        RotationInterval::Daily => 24 * 60 * 60,
+    };
+    time.timestamp().div_euclid(seconds)
+}
+
+/// Returns the path to which the log file at `path` is renamed after it has
+/// been rotated `count` times.
+fn rotated_path(path: &Path, count: u32) -> PathBuf {
+    let mut rotated_path = OsString::from(path);
+    rotated_path.push(format!(".{count}"));
+    PathBuf::from(rotated_path)
+}
+
+/// A log file that is appended to and rotated according to a
+/// `RotationConfig`.
+pub struct RotatingFile {
+    path: PathBuf,
+    rotation: Option<RotationConfig>,
+    file: File,
+
+    /// The size of the file in bytes.
+    size: u64,
+
+    /// The number of the rotation period in which the file was last written.
+    period: Option<i64>,
+}
+
+impl RotatingFile {
+    /// Opens the log file at `path` for appending, creating it if it doesn't
+    /// exist. If `rotation` is `None`, the file is never rotated.
+    pub fn open(
+        path: &Path,
+        rotation: Option<RotationConfig>,
+    ) -> Result<Self, String> {
+        if rotation.as_ref().is_some_and(|rotation| {
+            rotation.max_size_bytes.is_none() && rotation.interval.is_none()
+        }) {
+            return Err(
+                "Log rotation needs `max_size_bytes` or `interval`".to_owned()
+            );
+        }
+
+        let (file, metadata) = File::options()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|file| {
+                let metadata = file.metadata()?;
+                Ok((file, metadata))
+            })
+            .map_err(|error| {
+                format!("Could not open {}: {error}", path.display())
+            })?;
+        let interval = rotation.as_ref().and_then(|rotation| rotation.interval);
+        let period = interval.map(|interval| {
+            let modified = metadata
+                .modified()
+                .map_or_else(|_| Utc::now(), DateTime::from);
+            period(interval, modified)
+        });
+
+        Ok(Self {
+            path: path.to_owned(),
+            rotation,
+            file,
+            size: metadata.len(),
+            period,
+        })
+    }
+
+    /// Returns whether the file should be rotated before `length` more bytes
+    /// are written to it.
+    fn needs_rotation(&self, length: u64) -> bool {
+        let Some(rotation) = &self.rotation else {
+            return false;
+        };
+        if self.size == 0 {
+            return false;
+        }
+        let too_large = rotation
+            .max_size_bytes
+            .is_some_and(|max_size| self.size + length > max_size);
+        let too_old = rotation.interval.is_some_and(|interval| {
+            self.period != Some(period(interval, Utc::now()))
+        });
+        too_large || too_old
+    }
+
+    /// Renames the file and the files rotated before it, deletes the oldest
+    /// file if there are too many, and starts a new file.
+    fn rotate(&mut self) -> io::Result<()> {
+        let retained_files = self
+            .rotation
+            .as_ref()
+            .map_or(0, |rotation| rotation.retained_files);
+        if retained_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            match fs::remove_file(rotated_path(&self.path, retained_files)) {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => {
+                    return Err(error);
+                }
+                _ => {}
+            }
+            for count in (1..retained_files).rev() {
+                let from = rotated_path(&self.path, count);
+                if from.exists() {
+                    fs::rename(from, rotated_path(&self.path, count + 1))?;
+                }
+            }
+            fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        }
+
+        self.file =
+            File::options().create(true).append(true).open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+
+    /// Appends `line` and a line break to the file, rotating it first if
+    /// needed.
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let length = line.len() as u64 + 1;
+        if self.needs_rotation(length) {
+            self.rotate()?;
+        }
+        if let Some(interval) = self
+            .rotation
+            .as_ref()
+            .and_then(|rotation| rotation.interval)
+        {
+            self.period = Some(period(interval, Utc::now()));
+        }
+        writeln!(self.file, "{line}")?;
+        self.size += length;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size_rotation() {
+        let directory = std::env::temp_dir()
+            .join(format!("recipes-rotation-test-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("test.log");
+        let rotation = RotationConfig {
+            max_size_bytes: Some(10),
+            interval: None,
+            retained_files: 2,
+        };
+
+        let mut file = RotatingFile::open(&path, Some(rotation)).unwrap();
+        for line in ["first", "second", "third", "fourth"] {
+            file.write_line(line).unwrap();
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fourth\n");
+        assert_eq!(
+            fs::read_to_string(rotated_path(&path, 1)).unwrap(),
+            "third\n"
+        );
+        assert_eq!(
+            fs::read_to_string(rotated_path(&path, 2)).unwrap(),
+            "second\n"
+        );
+        assert!(!rotated_path(&path, 3).exists());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_period() {
+        let time = "2024-07-01T13:45:00Z".parse::<DateTime<Utc>>().unwrap();
+        let later = "2024-07-01T14:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert_ne!(
+            period(RotationInterval::Hourly, time),
+            period(RotationInterval::Hourly, later)
+        );
+        assert_eq!(
+            period(RotationInterval::Daily, time),
+            period(RotationInterval::Daily, later)
+        );
+    }
+}
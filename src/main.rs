@@ -1,98 +1,177 @@
 mod api;
+mod cli;
+mod commands;
 mod config;
+mod cooking;
 mod database;
+mod formats;
 mod frontend;
+mod health;
+mod logging;
+mod metrics;
 mod models;
+mod proxy;
+mod server;
+mod systemd;
+mod units;
 mod util;
 
-use std::fs::File;
-use std::net::SocketAddr;
 use std::process::ExitCode;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
 
-use axum::Router;
-use log::{error, info, trace};
-use simplelog::WriteLogger;
-use tokio::net::TcpListener;
+use axum::{middleware, response::Redirect, routing::get, Router};
+use clap::Parser;
+use log::{error, info, trace, warn};
 
-use crate::config::{get_config, LoggingConfig};
+use crate::cli::{Cli, Command, ConfigArgs};
+use crate::cooking::CookingSessions;
 use crate::database::Database;
 use crate::util::stringify_err;
 
-/// Initializes logging based on the log file path and verbosity in `config`.
-fn init_logging(config: &LoggingConfig) -> Result<(), String> {
-    println!(
-        "Writing logs to path {} with verbosity {}",
-        config.log_file_path.display(),
-        config.verbosity
-    );
-
-    let simplelog_config = simplelog::ConfigBuilder::new()
-        .set_time_format_rfc3339()
-        .build();
-
-    stringify_err(WriteLogger::init(
-        config.verbosity,
-        simplelog_config,
-        stringify_err(
-            File::options()
-                .create(true)
-                .append(true)
-                .open(&config.log_file_path),
-        )?,
-    ))
-}
-
-/// Starts the server asynchronously and runs it until shutdown or an error
-/// occurs.
-///
-/// This function serves as the entrypoint to the asynchronous runtime.
-#[tokio::main]
-async fn run_server() -> Result<(), String> {
+/// Starts the server using the configuration given by `config_args`, and runs
+/// it until shutdown or an error occurs.
+async fn serve(config_args: &ConfigArgs) -> Result<(), String> {
     println!("Reading configuration...");
-    let config = get_config()?;
+    let config = config_args.read_config()?;
 
-    init_logging(&config.logging)?;
+    logging::init_logging(&config.logging)?;
     trace!(
         "Logging initiated with verbosity {}",
         config.logging.verbosity
     );
 
     info!("Starting server: recipes");
+    metrics::record_start_time();
+
+    // The sockets are bound and health checks served before connecting to
+    // the database, which may take a while to migrate. Until then, `/readyz`
+    // reports that the server is starting, and other requests get a 503.
+    frontend::set_base_path(&config.server.base_path);
+    let database = Arc::new(OnceLock::<Arc<Database>>::new());
+    let database_app = Arc::new(OnceLock::new());
+    let database_admin_app = Arc::new(OnceLock::new());
+    let admin_app = server::deferred_router(database_admin_app.clone())
+        .merge(health::create_router(database.clone()));
+    let mut app = server::deferred_router(database_app.clone()).merge(
+        health::create_router(database.clone())
+            .layer(middleware::from_fn(metrics::record_request)),
+    );
+    if !config.server.base_path.is_empty() {
+        // Nesting doesn't match the base path with a trailing slash, which
+        // is where proxies usually send people.
+        let base_path = config.server.base_path.clone();
+        app = Router::new().nest(&config.server.base_path, app).route(
+            &format!("{base_path}/"),
+            get(|| async move { Redirect::permanent(&base_path) }),
+        );
+    }
+    if let Some(access_log_path) = &config.logging.access_log_path {
+        app = app.layer(middleware::from_fn_with_state(
+            Arc::new(logging::AccessLog::open(
+                access_log_path,
+                config.logging.rotation.clone(),
+            )?),
+            logging::log_access,
+        ));
+    }
+    let app = app
+        .layer(middleware::from_fn_with_state(
+            Arc::from(config.server.trusted_proxies.as_slice()),
+            proxy::resolve_client,
+        ))
+        .layer(middleware::from_fn(logging::assign_request_id));
 
-    info!("Connecting to database...");
-    let database =
-        Arc::new(stringify_err(Database::new(config.database).await)?);
-    info!("Database connected (version = {})", database.get_version());
+    let database_config = config.database;
+    let serves_metrics = config.server.admin_address.is_none();
+    let startup = async {
+        info!("Connecting to database...");
+        systemd::notify_status("Connecting to database and migrating");
+        let connected =
+            Arc::new(stringify_err(Database::new(database_config).await)?);
+        info!("Database connected (version = {})", connected.get_version());
+        systemd::spawn_watchdog(connected.clone());
 
-    let app = Router::new()
-        .nest("/", frontend::create_router(database.clone()))
-        .nest("/api", api::create_router(database));
+        let mut app = Router::new()
+            .nest(
+                "/",
+                frontend::create_router(
+                    connected.clone(),
+                    config.admin.clone(),
+                ),
+            )
+            .nest(
+                "/api",
+                api::create_router(
+                    connected.clone(),
+                    Arc::new(CookingSessions::default()),
+                ),
+            );
+        if serves_metrics {
+            app = app.merge(metrics::create_router(connected.clone()));
+        }
+        let _ = database_app
+            .set(app.layer(middleware::from_fn(metrics::record_request)));
+        let _ =
+            database_admin_app.set(metrics::create_router(connected.clone()));
+        let _ = database.set(connected);
+        Ok(())
+    };
 
-    info!(
-        "Binding to {}:{}",
-        config.server.ip_address, config.server.port
-    );
+    server::run_server(&config.server, app, admin_app, startup).await?;
 
-    let listener = stringify_err(
-        TcpListener::bind(&SocketAddr::new(
-            config.server.ip_address,
-            config.server.port,
-        ))
-        .await,
-    )?;
+    let Some(database) = database.get() else {
+        return Ok(());
+    };
+    info!("Closing database connections...");
+    systemd::notify_status("Closing database connections");
+    let shutdown_timeout =
+        Duration::from_secs(config.server.shutdown_timeout_seconds);
+    if tokio::time::timeout(shutdown_timeout, database.close())
+        .await
+        .is_err()
+    {
+        warn!("Database connections still in use; not waiting for them");
+    }
 
-    stringify_err(axum::serve(listener, app).await)
+    Ok(())
+}
+
+/// Runs the subcommand given by `cli`.
+///
+/// This function serves as the entrypoint to the asynchronous runtime.
+#[tokio::main]
+async fn run(cli: Cli) -> Result<(), String> {
+    match cli.command {
+        Command::Serve(args) => serve(&args).await,
+        Command::InitDb(args) => commands::init_db(args.read_config()?).await,
+        Command::Migrate {
+            config,
+            to,
+            dry_run,
+        } => commands::migrate(config.read_config()?, to, dry_run).await,
+        Command::CheckConfig(args) => {
+            commands::check_config(&args.config, &args.read_config()?)
+        }
+        Command::Export { config, output } => {
+            commands::export(config.read_config()?, output.as_deref()).await
+        }
+        Command::Import { config, input } => {
+            commands::import(config.read_config()?, &input).await
+        }
+        Command::Seed(args) => commands::seed(args.read_config()?).await,
+    }
 }
 
-/// Runs the server until shutdown or an error occurs.
+/// Runs the subcommand given in the program's arguments until it finishes or
+/// an error occurs.
 fn main() -> ExitCode {
-    if let Err(err) = run_server() {
+    if let Err(err) = run(Cli::parse()) {
         eprintln!("{err}");
-        error!("Server exiting with error: {err}");
+        error!("Exiting with error: {err}");
         ExitCode::FAILURE
     } else {
-        info!("Server exiting normally");
+        info!("Exiting normally");
         ExitCode::SUCCESS
     }
 }
@@ -0,0 +1,467 @@
+//! Metrics about the server's requests, database and process, served in the
+//! Prometheus text format.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+
+use crate::database::Database;
+use crate::frontend::base_path;
+
+/// The upper bounds of the histograms' buckets, in seconds.
+const DURATION_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// The content type of the Prometheus text format.
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// A distribution of durations.
+struct Histogram {
+    /// The number of durations in each of `DURATION_BUCKETS`, not including
+    /// those in earlier buckets.
+    bucket_counts: [u64; DURATION_BUCKETS.len()],
+
+    count: u64,
+
+    /// The sum of the durations, in seconds.
+    sum: f64,
+}
+
+impl Histogram {
+    const fn new() -> Self {
+        Self {
+            bucket_counts: [0; DURATION_BUCKETS.len()],
+            count: 0,
+            sum: 0.0,
+        }
+    }
+
+    /// Adds `duration` to the histogram.
+    fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        if let Some(bucket) =
+            DURATION_BUCKETS.iter().position(|&bound| seconds <= bound)
+        {
+            self.bucket_counts[bucket] += 1;
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+
+    /// Writes the histogram's samples as the metric `name` with `labels`
+    /// (e.g. `route="/",status="200"`, or empty) to `output`.
+    fn write(&self, output: &mut String, name: &str, labels: &str) {
+        let separator = if labels.is_empty() { "" } else { "," };
+        let mut cumulative_count = 0;
+        for (bound, count) in DURATION_BUCKETS.iter().zip(self.bucket_counts) {
+            cumulative_count += count;
+            let _ = writeln!(
+                output,
+                "{name}_bucket{{{labels}{separator}le=\"{bound}\"}} \
+                 {cumulative_count}"
+            );
+        }
+        let _ = writeln!(
+            output,
+            "{name}_bucket{{{labels}{separator}le=\"+Inf\"}} {}",
+            self.count
+        );
+        let labels = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{labels}}}")
+        };
+        let _ = writeln!(output, "{name}_sum{labels} {}", self.sum);
+        let _ = writeln!(output, "{name}_count{labels} {}", self.count);
+    }
+}
+
+/// Writes the `# HELP` and `# TYPE` lines for the metric `name` to `output`.
+fn write_header(output: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(output, "# HELP {name} {help}");
+    let _ = writeln!(output, "# TYPE {name} {kind}");
+}
+
+/// Returns `value` escaped for use as a label value.
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// The labels of the request metrics.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct RequestLabels {
+    /// The route that matched the request, e.g. `/recipes/:id`.
+    route: String,
+
+    method: &'static str,
+    status: u16,
+}
+
+/// The request methods that are recorded by name. Others are recorded as
+/// "other", so that clients can't add any number of label values.
+const STANDARD_METHODS: [&str; 9] = [
+    "GET", "HEAD", "POST", "PUT", "DELETE", "CONNECT", "OPTIONS", "TRACE",
+    "PATCH",
+];
+
+/// Returns the label value for the request method `method`.
+fn method_label(method: &str) -> &'static str {
+    STANDARD_METHODS
+        .into_iter()
+        .find(|&standard| standard == method)
+        .unwrap_or("other")
+}
+
+/// The durations of requests, by route, method and status.
+static REQUESTS: Mutex<BTreeMap<RequestLabels, Histogram>> =
+    Mutex::new(BTreeMap::new());
+
+/// The durations of database transactions that were committed.
+static COMMITTED_TRANSACTIONS: Mutex<Histogram> = Mutex::new(Histogram::new());
+
+/// The durations of database transactions that failed.
+static FAILED_TRANSACTIONS: Mutex<Histogram> = Mutex::new(Histogram::new());
+
+/// How long database transactions waited for a connection from the pool.
+static POOL_WAITS: Mutex<Histogram> = Mutex::new(Histogram::new());
+
+/// When the server started.
+static START_TIME: OnceLock<SystemTime> = OnceLock::new();
+
+/// Records that the server has started, for the process start time metric.
+pub fn record_start_time() {
+    START_TIME.get_or_init(SystemTime::now);
+}
+
+/// Returns when the server started, if it has.
+pub fn start_time() -> Option<SystemTime> {
+    START_TIME.get().copied()
+}
+
+/// Records that a database transaction finished after `duration`, and
+/// whether it was `committed`.
+pub fn record_transaction(duration: Duration, committed: bool) {
+    let histogram = if committed {
+        &COMMITTED_TRANSACTIONS
+    } else {
+        &FAILED_TRANSACTIONS
+    };
+    histogram
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .observe(duration);
+}
+
+/// Records that a database transaction waited `duration` for a connection.
+pub fn record_pool_wait(duration: Duration) {
+    POOL_WAITS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .observe(duration);
+}
+
+/// Returns the label value for the matched route `route`, without the base
+/// path, which only some of the routers that record requests are nested in.
+fn route_label(route: &str) -> &str {
+    match route.strip_prefix(base_path()) {
+        Some(rest) if rest.starts_with('/') => rest,
+        _ => route,
+    }
+}
+
+/// Records the duration and status of each request, by the route that it
+/// matched, without the base path.
+pub async fn record_request(request: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let route = request.extensions().get::<MatchedPath>().map_or_else(
+        || "unmatched".to_owned(),
+        |path| route_label(path.as_str()).to_owned(),
+    );
+    let method = method_label(request.method().as_str());
+
+    let response = next.run(request).await;
+
+    let labels = RequestLabels {
+        route,
+        method,
+        status: response.status().as_u16(),
+    };
+    REQUESTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry(labels)
+        .or_insert_with(Histogram::new)
+        .observe(start.elapsed());
+    response
+}
+
+/// Writes the request metrics to `output`.
+fn write_request_metrics(output: &mut String) {
+    let requests = REQUESTS.lock().unwrap_or_else(PoisonError::into_inner);
+
+    write_header(
+        output,
+        "http_requests_total",
+        "counter",
+        "Requests handled, by route, method and status.",
+    );
+    for (labels, histogram) in requests.iter() {
+        let _ = writeln!(
+            output,
+            "http_requests_total{{route=\"{}\",method=\"{}\",status=\"{}\"}} \
+             {}",
+            escape_label_value(&labels.route),
+            labels.method,
+            labels.status,
+            histogram.count
+        );
+    }
+
+    write_header(
+        output,
+        "http_request_duration_seconds",
+        "histogram",
+        "Time taken to handle requests, by route, method and status.",
+    );
+    for (labels, histogram) in requests.iter() {
+        histogram.write(
+            output,
+            "http_request_duration_seconds",
+            &format!(
+                "route=\"{}\",method=\"{}\",status=\"{}\"",
+                escape_label_value(&labels.route),
+                labels.method,
+                labels.status
+            ),
+        );
+    }
+}
+
+/// Writes the database metrics for `database` to `output`.
+fn write_database_metrics(output: &mut String, database: &Database) {
+    let pool = database.pool_statistics();
+    for (name, help, value) in [
+        (
+            "recipes_db_pool_connections",
+            "Connections in the database pool.",
+            pool.size,
+        ),
+        (
+            "recipes_db_pool_idle_connections",
+            "Idle connections in the database pool.",
+            pool.idle,
+        ),
+        (
+            "recipes_db_pool_max_connections",
+            "The most connections that the database pool may hold.",
+            pool.max_size,
+        ),
+    ] {
+        write_header(output, name, "gauge", help);
+        let _ = writeln!(output, "{name} {value}");
+    }
+
+    write_header(
+        output,
+        "recipes_db_pool_wait_seconds",
+        "histogram",
+        "Time that transactions waited for a database connection.",
+    );
+    POOL_WAITS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .write(output, "recipes_db_pool_wait_seconds", "");
+
+    write_header(
+        output,
+        "recipes_db_transaction_duration_seconds",
+        "histogram",
+        "Duration of database transactions, by whether they were committed \
+         or failed.",
+    );
+    for (result, histogram) in [
+        ("committed", &COMMITTED_TRANSACTIONS),
+        ("failed", &FAILED_TRANSACTIONS),
+    ] {
+        histogram
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .write(
+                output,
+                "recipes_db_transaction_duration_seconds",
+                &format!("result=\"{result}\""),
+            );
+    }
+
+    write_header(
+        output,
+        "recipes_db_transaction_failures_total",
+        "counter",
+        "Database transactions that failed and were rolled back.",
+    );
+    let _ = writeln!(
+        output,
+        "recipes_db_transaction_failures_total {}",
+        FAILED_TRANSACTIONS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .count
+    );
+}
+
+/// Writes the standard Prometheus process metrics that can be determined on
+/// this platform to `output`.
+fn write_process_metrics(output: &mut String) {
+    let mut write_metric = |name: &str, kind: &str, help: &str, value: f64| {
+        write_header(output, name, kind, help);
+        let _ = writeln!(output, "{name} {value}");
+    };
+
+    if let Some(start_time) = START_TIME
+        .get()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+    {
+        write_metric(
+            "process_start_time_seconds",
+            "gauge",
+            "Start time of the process since the Unix epoch in seconds.",
+            start_time.as_secs_f64(),
+        );
+    }
+
+    #[cfg(unix)]
+    {
+        // SAFETY: `getrusage` and `getrlimit` only write to the structs
+        // passed to them, which are valid and zero-initialized.
+        let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+        if unsafe { libc::getrusage(libc::RUSAGE_SELF, &raw mut usage) } == 0 {
+            #[allow(clippy::cast_precision_loss)]
+            let seconds = |time: libc::timeval| {
+                time.tv_sec as f64 + time.tv_usec as f64 / 1_000_000.0
+            };
+            write_metric(
+                "process_cpu_seconds_total",
+                "counter",
+                "Total user and system CPU time spent in seconds.",
+                seconds(usage.ru_utime) + seconds(usage.ru_stime),
+            );
+        }
+
+        let mut limit: libc::rlimit = unsafe { std::mem::zeroed() };
+        if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &raw mut limit) } == 0
+        {
+            #[allow(clippy::cast_precision_loss)]
+            write_metric(
+                "process_max_fds",
+                "gauge",
+                "Maximum number of open file descriptors.",
+                limit.rlim_cur as f64,
+            );
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        if let Ok(entries) = std::fs::read_dir("/proc/self/fd") {
+            #[allow(clippy::cast_precision_loss)]
+            write_metric(
+                "process_open_fds",
+                "gauge",
+                "Number of open file descriptors.",
+                entries.count() as f64,
+            );
+        }
+
+        // The sizes in /proc/self/status are in kibibytes.
+        let status =
+            std::fs::read_to_string("/proc/self/status").unwrap_or_default();
+        let status_bytes = |field: &str| {
+            status.lines().find_map(|line| {
+                let kibibytes = line
+                    .strip_prefix(field)?
+                    .trim()
+                    .strip_suffix("kB")?
+                    .trim()
+                    .parse::<f64>()
+                    .ok()?;
+                Some(kibibytes * 1024.0)
+            })
+        };
+        if let Some(bytes) = status_bytes("VmRSS:") {
+            write_metric(
+                "process_resident_memory_bytes",
+                "gauge",
+                "Resident memory size in bytes.",
+                bytes,
+            );
+        }
+        if let Some(bytes) = status_bytes("VmSize:") {
+            write_metric(
+                "process_virtual_memory_bytes",
+                "gauge",
+                "Virtual memory size in bytes.",
+                bytes,
+            );
+        }
+    }
+}
+
+/// Serves all metrics in the Prometheus text format.
+async fn serve_metrics(State(database): State<Arc<Database>>) -> Response {
+    let mut output = String::new();
+    write_request_metrics(&mut output);
+    write_database_metrics(&mut output, &database);
+    write_process_metrics(&mut output);
+    ([(header::CONTENT_TYPE, CONTENT_TYPE)], output).into_response()
+}
+
+/// Creates a router that serves `/metrics` with metrics about the server and
+/// `database`.
+pub fn create_router(database: Arc<Database>) -> Router {
+    Router::new()
+        .route("/metrics", get(serve_metrics))
+        .with_state(database)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram() {
+        let mut histogram = Histogram::new();
+        histogram.observe(Duration::from_millis(1));
+        histogram.observe(Duration::from_millis(20));
+        histogram.observe(Duration::from_mins(1));
+
+        let mut output = String::new();
+        histogram.write(&mut output, "test", "a=\"b\"");
+        assert!(output.contains("test_bucket{a=\"b\",le=\"0.005\"} 1\n"));
+        assert!(output.contains("test_bucket{a=\"b\",le=\"0.025\"} 2\n"));
+        assert!(output.contains("test_bucket{a=\"b\",le=\"10\"} 2\n"));
+        assert!(output.contains("test_bucket{a=\"b\",le=\"+Inf\"} 3\n"));
+        assert!(output.contains("test_count{a=\"b\"} 3\n"));
+    }
+
+    #[test]
+    fn test_method_label() {
+        assert_eq!(method_label("GET"), "GET");
+        assert_eq!(method_label("PATCH"), "PATCH");
+        assert_eq!(method_label("get"), "other");
+        assert_eq!(method_label("PROPFIND"), "other");
+    }
+}
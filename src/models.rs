@@ -4,10 +4,14 @@ mod model;
 mod modelref;
 mod recipe;
 mod recipeversion;
+mod validation;
 
 pub use category::Category;
 pub use ingredient::Ingredient;
 pub use model::Model;
 pub use modelref::Ref;
 pub use recipe::Recipe;
-pub use recipeversion::{RecipeVersion, RecipeVersionID};
+pub use recipeversion::{
+    Instruction, MeasurementType, QuantifiedIngredient, RecipeVersion,
+    RecipeVersionID,
+};
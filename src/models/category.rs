@@ -1,8 +1,9 @@
 use serde::{Deserialize, Serialize};
 use sqlx::{Any, Transaction};
 
+use super::validation::validate_name;
 use super::Model;
-use crate::database::DBResult;
+use crate::database::{self, DBResult};
 
 /// Represents a category of recipes.
 #[derive(Clone, Deserialize, Serialize)]
@@ -15,14 +16,51 @@ pub struct Category {
 }
 
 impl Category {
+    /// Looks up the ID of the category named `name`, ignoring case.
+    ///
+    /// Returns `None` if no category has that name.
+    pub async fn find_by_name(
+        transaction: &mut Transaction<'_, Any>,
+        name: &str,
+    ) -> DBResult<Option<i64>> {
+        Ok(sqlx::query_scalar(
+            "SELECT id FROM categories WHERE LOWER(name) = LOWER($1) \
+             ORDER BY id LIMIT 1",
+        )
+        .bind(name)
+        .fetch_optional(&mut **transaction)
+        .await?)
+    }
+
+    /// Fails if another category is already named `name`, ignoring case.
+    ///
+    /// `store_new` doesn't check this, as the API allows such names.
+    pub async fn check_name_unused(
+        transaction: &mut Transaction<'_, Any>,
+        name: &str,
+    ) -> DBResult<()> {
+        if Self::find_by_name(transaction, name).await?.is_some() {
+            return Err(database::Error::BadArguments(format!(
+                "A category named \"{name}\" already exists"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Stores a new category named `name` using `transaction` and returns its
+    /// ID.
+    ///
+    /// Fails if the name is invalid.
     pub async fn store_new(
         transaction: &mut Transaction<'_, Any>,
         name: &str,
     ) -> DBResult<i64> {
-        let last_category_id: Option<i64> =
-            sqlx::query_scalar("SELECT MAX(id) FROM categories")
-                .fetch_optional(&mut **transaction)
-                .await?;
+        validate_name("Category", name)?;
+        let last_category_id: Option<i64> = sqlx::query_scalar(
+            "SELECT id FROM categories ORDER BY id DESC LIMIT 1",
+        )
+        .fetch_optional(&mut **transaction)
+        .await?;
 
         let id = last_category_id.map_or(0, |old_id| old_id + 1);
 
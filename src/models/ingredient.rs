@@ -1,12 +1,13 @@
 use serde::Serialize;
 use sqlx::{Any, Transaction};
 
+use super::validation::{validate_name, validate_non_negative};
 use super::Model;
-use crate::database::DBResult;
+use crate::database::{self, DBResult};
 
 /// Represents a general ingredient that can be used in recipes. This can be
 /// any edible recipe ingredient, from water to a spice to a baked good.
-#[derive(Serialize)]
+#[derive(Clone, Serialize)]
 pub struct Ingredient {
     /// The ingredient's internal ID.
     pub id: i64,
@@ -19,15 +20,53 @@ pub struct Ingredient {
 }
 
 impl Ingredient {
+    /// Looks up the ID of the ingredient named `name`, ignoring case.
+    ///
+    /// Returns `None` if no ingredient has that name.
+    pub async fn find_by_name(
+        transaction: &mut Transaction<'_, Any>,
+        name: &str,
+    ) -> DBResult<Option<i64>> {
+        Ok(sqlx::query_scalar(
+            "SELECT id FROM ingredients WHERE LOWER(name) = LOWER($1) \
+             ORDER BY id LIMIT 1",
+        )
+        .bind(name)
+        .fetch_optional(&mut **transaction)
+        .await?)
+    }
+
+    /// Fails if another ingredient is already named `name`, ignoring case.
+    ///
+    /// `store_new` doesn't check this, as the API allows such names.
+    pub async fn check_name_unused(
+        transaction: &mut Transaction<'_, Any>,
+        name: &str,
+    ) -> DBResult<()> {
+        if Self::find_by_name(transaction, name).await?.is_some() {
+            return Err(database::Error::BadArguments(format!(
+                "An ingredient named \"{name}\" already exists"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Stores a new ingredient named `name` using `transaction` and returns
+    /// its ID.
+    ///
+    /// Fails if the name is invalid, or if `energy_density` is negative.
     pub async fn store_new(
         transaction: &mut Transaction<'_, Any>,
         name: &str,
         energy_density: f64,
     ) -> DBResult<i64> {
-        let last_ingredient_id: Option<i64> =
-            sqlx::query_scalar("SELECT MAX(id) FROM ingredients")
-                .fetch_optional(&mut **transaction)
-                .await?;
+        validate_name("Ingredient", name)?;
+        validate_non_negative("Energy density", energy_density)?;
+        let last_ingredient_id: Option<i64> = sqlx::query_scalar(
+            "SELECT id FROM ingredients ORDER BY id DESC LIMIT 1",
+        )
+        .fetch_optional(&mut **transaction)
+        .await?;
 
         let id = last_ingredient_id.map_or(0, |old_id| old_id + 1);
 
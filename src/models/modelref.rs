@@ -24,6 +24,21 @@ impl<M: Model> Ref<M> {
         Self { id, value: None }
     }
 
+    /// Creates a reference to `value`, which has the ID `id`.
+    ///
+    /// The reference initially holds a cached version of the model.
+    pub fn with_value(id: M::ID, value: M) -> Self {
+        Self {
+            id,
+            value: Some(value),
+        }
+    }
+
+    /// Returns the cached version of the referenced model, if there is one.
+    pub fn get(&self) -> Option<&M> {
+        self.value.as_ref()
+    }
+
     /// Attempts to retrieve the referenced model from the database using
     /// `transaction`.
     ///
@@ -3,6 +3,7 @@ use std::collections::HashMap;
 use serde::Serialize;
 use sqlx::{Any, Transaction};
 
+use super::validation::validate_name;
 use super::{Category, Model, RecipeVersion, RecipeVersionID, Ref};
 use crate::database::{self, DBResult};
 
@@ -25,15 +26,33 @@ pub struct Recipe {
 }
 
 impl Recipe {
+    /// Stores a new recipe named `name` in `categories` using `transaction`
+    /// and returns its ID. The recipe initially has no versions.
+    ///
+    /// Fails if the name is invalid or if any category does not exist or is
+    /// listed more than once.
     pub async fn store_new(
         transaction: &mut Transaction<'_, Any>,
         name: &str,
         categories: Vec<Category>,
     ) -> DBResult<i64> {
-        let last_recipe_id: Option<i64> =
-            sqlx::query_scalar("SELECT MAX(id) FROM recipes")
-                .fetch_optional(&mut **transaction)
-                .await?;
+        validate_name("Recipe", name)?;
+        for (index, category) in categories.iter().enumerate() {
+            if categories[..index]
+                .iter()
+                .any(|other| other.id == category.id)
+            {
+                return Err(database::Error::BadArguments(
+                    "Category listed more than once".to_owned(),
+                ));
+            }
+        }
+
+        let last_recipe_id: Option<i64> = sqlx::query_scalar(
+            "SELECT id FROM recipes ORDER BY id DESC LIMIT 1",
+        )
+        .fetch_optional(&mut **transaction)
+        .await?;
 
         let id = last_recipe_id.map_or(0, |old_id| old_id + 1);
 
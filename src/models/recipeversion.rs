@@ -1,13 +1,14 @@
 use chrono::{offset::Utc, DateTime, Duration, NaiveDateTime};
 use log::warn;
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Serialize, Serializer};
 use sqlx::{Any, Transaction};
 
+use super::validation::validate_non_negative;
 use super::{Ingredient, Model, Ref};
 use crate::database::{self, to_internal_db_error, DBResult};
 
 /// The kind of quantity of a recipe ingredient measurement.
-#[derive(Clone, Copy, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
 #[repr(i64)]
 pub enum MeasurementType {
     Mass = 0,
@@ -56,7 +57,7 @@ pub struct RecipeVersion {
     pub duration: Duration,
 }
 
-#[derive(Clone, Copy, Serialize)]
+#[derive(Clone, Copy, Eq, Hash, PartialEq, Serialize)]
 pub struct RecipeVersionID {
     pub recipe_id: i64,
     pub version_id: i64,
@@ -81,8 +82,73 @@ async fn ensure_recipe_visible(
     }
 }
 
+/// Checks that the contents of a new recipe version are valid.
+///
+/// Every ingredient must exist, be listed only once, and have a non-negative
+/// quantity. Every instruction must have text, and the duration must not be
+/// negative.
+async fn validate_contents(
+    transaction: &mut Transaction<'_, Any>,
+    ingredients: &[QuantifiedIngredient],
+    instructions: &[Instruction],
+    duration: Duration,
+) -> DBResult<()> {
+    for (index, ingredient) in ingredients.iter().enumerate() {
+        let id = ingredient.ingredient.id;
+        validate_non_negative(
+            &format!("Quantity of ingredient {}", index + 1),
+            ingredient.quantity,
+        )?;
+
+        if ingredients[..index]
+            .iter()
+            .any(|other| other.ingredient.id == id)
+        {
+            return Err(database::Error::BadArguments(format!(
+                "Ingredient {} is listed more than once",
+                index + 1
+            )));
+        }
+
+        let matching_ingredient_count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(id) FROM ingredients WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_one(&mut **transaction)
+        .await?;
+        if matching_ingredient_count != 1 {
+            return Err(database::Error::BadArguments(format!(
+                "Ingredient {} does not exist",
+                index + 1
+            )));
+        }
+    }
+
+    if let Some(index) = instructions
+        .iter()
+        .position(|instruction| instruction.text.trim().is_empty())
+    {
+        return Err(database::Error::BadArguments(format!(
+            "Step {} must not be empty",
+            index + 1
+        )));
+    }
+
+    if duration < Duration::zero() {
+        return Err(database::Error::BadArguments(
+            "Duration must not be negative".to_owned(),
+        ));
+    }
+
+    Ok(())
+}
+
 impl RecipeVersion {
-    #[allow(dead_code)]
+    /// Stores a new version of the recipe with ID `recipe_id` using
+    /// `transaction` and returns its ID.
+    ///
+    /// Fails if the recipe is not visible or the version's contents are
+    /// invalid (see `validate_contents`).
     pub async fn store_new(
         transaction: &mut Transaction<'_, Any>,
         recipe_id: i64,
@@ -92,10 +158,12 @@ impl RecipeVersion {
         duration: Duration,
     ) -> DBResult<RecipeVersionID> {
         ensure_recipe_visible(transaction, recipe_id).await?;
+        validate_contents(transaction, &ingredients, &instructions, duration)
+            .await?;
 
         let last_version_id: Option<i64> = sqlx::query_scalar(
-            "SELECT MAX(version_id) FROM recipes_versions \
-            WHERE recipe_id = $1",
+            "SELECT version_id FROM recipes_versions \
+            WHERE recipe_id = $1 ORDER BY version_id DESC LIMIT 1",
         )
         .bind(recipe_id)
         .fetch_optional(&mut **transaction)
@@ -165,10 +233,12 @@ impl Model for RecipeVersion {
     ) -> DBResult<Self> {
         ensure_recipe_visible(transaction, id.recipe_id).await?;
 
-        // Retrieve everything needed from the recipes_versions table.
+        // Retrieve everything needed from the recipes_versions table. The
+        // Any driver cannot decode DATETIME columns, so `created` is cast.
         let (created_secs_since_epoch, duration_secs): (i64, i64) =
             sqlx::query_as(
-                "SELECT created, duration FROM recipes_versions \
+                "SELECT CAST(created AS INTEGER), duration \
+                FROM recipes_versions \
                 WHERE recipe_id = $1 AND version_id = $2",
             )
             .bind(id.recipe_id)
@@ -0,0 +1,34 @@
+//! Checks shared by all ways of creating models, so that the JSON API, the
+//! HTML forms and importers all accept and reject the same data.
+
+use crate::database::{self, DBResult};
+
+/// The maximum length (in characters) of a name.
+const MAX_NAME_LENGTH: usize = 256;
+
+/// Checks that `name` is a valid name for a `kind` (e.g. "Ingredient").
+pub fn validate_name(kind: &str, name: &str) -> DBResult<()> {
+    if name.trim().is_empty() {
+        return Err(database::Error::BadArguments(format!(
+            "{kind} name must not be empty"
+        )));
+    }
+    if name.chars().count() > MAX_NAME_LENGTH {
+        return Err(database::Error::BadArguments(format!(
+            "{kind} name must be at most {MAX_NAME_LENGTH} characters long"
+        )));
+    }
+    Ok(())
+}
+
+/// Checks that `value`, the `description` of something (e.g. "Energy
+/// density"), is a finite, non-negative number.
+pub fn validate_non_negative(description: &str, value: f64) -> DBResult<()> {
+    if value.is_finite() && value >= 0.0 {
+        Ok(())
+    } else {
+        Err(database::Error::BadArguments(format!(
+            "{description} must be a non-negative number"
+        )))
+    }
+}
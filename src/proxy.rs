@@ -0,0 +1,375 @@
+//! Working out who sent a request when the server is behind reverse proxies,
+//! using the `Forwarded` and `X-Forwarded-*` headers added by trusted
+//! proxies.
+
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+/// An IP network, e.g. `10.0.0.0/8`, or a single IP address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct IpNetwork {
+    address: IpAddr,
+    prefix_length: u8,
+}
+
+impl IpNetwork {
+    /// Returns whether `address` is in the network.
+    pub fn contains(&self, address: IpAddr) -> bool {
+        // Compare IPv4-mapped IPv6 addresses (e.g. from dual-stack sockets)
+        // as IPv4 addresses.
+        let address = match address {
+            IpAddr::V6(v6) => {
+                v6.to_ipv4_mapped().map_or(IpAddr::V6(v6), IpAddr::V4)
+            }
+            IpAddr::V4(_) => address,
+        };
+        match (self.address, address) {
+            (IpAddr::V4(network), IpAddr::V4(address)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix_length))
+                    .unwrap_or(0);
+                u32::from(network) & mask == u32::from(address) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(address)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix_length))
+                    .unwrap_or(0);
+                u128::from(network) & mask == u128::from(address) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = String;
+
+    fn from_str(network: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid IP address or network {network:?}");
+        let (address, prefix_length) = match network.split_once('/') {
+            Some((address, prefix_length)) => (
+                address.parse::<IpAddr>().map_err(|_| invalid())?,
+                Some(prefix_length.parse::<u8>().map_err(|_| invalid())?),
+            ),
+            None => (network.parse::<IpAddr>().map_err(|_| invalid())?, None),
+        };
+        let max_prefix_length = if address.is_ipv4() { 32 } else { 128 };
+        let prefix_length = prefix_length.unwrap_or(max_prefix_length);
+        if prefix_length > max_prefix_length {
+            return Err(invalid());
+        }
+        Ok(Self {
+            address,
+            prefix_length,
+        })
+    }
+}
+
+impl TryFrom<String> for IpNetwork {
+    type Error = String;
+
+    fn try_from(network: String) -> Result<Self, Self::Error> {
+        network.parse()
+    }
+}
+
+impl fmt::Display for IpNetwork {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{}/{}", self.address, self.prefix_length)
+    }
+}
+
+impl From<IpNetwork> for String {
+    fn from(network: IpNetwork) -> Self {
+        network.to_string()
+    }
+}
+
+/// The connection that a request arrived on. The server adds this to every
+/// request.
+#[derive(Clone, Copy)]
+pub struct ConnectionInfo {
+    /// The address of the peer, or `None` for Unix socket connections.
+    pub peer_address: Option<IpAddr>,
+
+    /// Whether the connection uses TLS.
+    pub tls: bool,
+}
+
+/// Who sent a request, taking trusted proxies into account. `resolve_client`
+/// adds this to every request.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClientInfo {
+    /// The client's address, if known.
+    pub address: Option<IpAddr>,
+
+    /// The scheme that the client used: "http" or "https".
+    pub scheme: String,
+
+    /// The host that the client requested, if known.
+    pub host: Option<String>,
+}
+
+impl fmt::Display for ClientInfo {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.address {
+            Some(address) => write!(formatter, "{address}")?,
+            None => write!(formatter, "unknown client")?,
+        }
+        write!(formatter, " ({})", self.scheme)
+    }
+}
+
+/// What one proxy recorded about the client (or proxy) that it received a
+/// request from.
+#[derive(Default)]
+struct Hop {
+    client: Option<IpAddr>,
+    scheme: Option<String>,
+    host: Option<String>,
+}
+
+/// Parses a node identifier such as `192.0.2.1`, `192.0.2.1:80` or
+/// `"[2001:db8::1]:80"`. Returns `None` for unknown and obfuscated nodes.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Some(bracketed) = node.strip_prefix('[') {
+        return bracketed.split(']').next()?.parse().ok();
+    }
+    node.parse().ok().or_else(|| {
+        // An IPv4 address with a port.
+        node.rsplit_once(':')?.0.parse().ok()
+    })
+}
+
+/// Returns the hops listed in the request's `Forwarded` headers (RFC 7239),
+/// from the original client to the nearest proxy.
+fn forwarded_hops(headers: &HeaderMap) -> Vec<Hop> {
+    headers
+        .get_all(header::FORWARDED)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|element| {
+            let mut hop = Hop::default();
+            for pair in element.split(';') {
+                let Some((name, value)) = pair.split_once('=') else {
+                    continue;
+                };
+                let value = value.trim().trim_matches('"');
+                match name.trim().to_ascii_lowercase().as_str() {
+                    "for" => hop.client = parse_node(value),
+                    "proto" => hop.scheme = Some(value.to_ascii_lowercase()),
+                    "host" => hop.host = Some(value.to_owned()),
+                    _ => {}
+                }
+            }
+            hop
+        })
+        .collect()
+}
+
+/// Returns the hops listed in the request's `X-Forwarded-For` headers, from
+/// the original client to the nearest proxy.
+///
+/// `X-Forwarded-Proto` and `X-Forwarded-Host` are usually set only by the
+/// nearest proxy, so their last values are attributed to the last hop.
+fn x_forwarded_hops(headers: &HeaderMap) -> Vec<Hop> {
+    let values = |name: &str| {
+        headers
+            .get_all(name)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|value| value.trim().to_owned())
+            .filter(|value| !value.is_empty())
+            .collect::<Vec<_>>()
+    };
+
+    let mut hops = values("x-forwarded-for")
+        .iter()
+        .map(|client| Hop {
+            client: parse_node(client),
+            ..Hop::default()
+        })
+        .collect::<Vec<_>>();
+    if let Some(last_hop) = hops.last_mut() {
+        last_hop.scheme = values("x-forwarded-proto")
+            .pop()
+            .map(|scheme| scheme.to_ascii_lowercase());
+        last_hop.host = values("x-forwarded-host").pop();
+    }
+    hops
+}
+
+/// Works out who sent a request with `headers` over `connection`.
+///
+/// The forwarding headers are only believed if the peer is one of the
+/// `trusted_proxies` (or a Unix socket client). The hops they list are then
+/// followed back towards the original client for as long as each hop was
+/// recorded by a trusted proxy. `Forwarded` is preferred over
+/// `X-Forwarded-*` if both are present.
+pub fn resolve(
+    connection: Option<ConnectionInfo>,
+    headers: &HeaderMap,
+    trusted_proxies: &[IpNetwork],
+) -> ClientInfo {
+    let is_trusted = |address: Option<IpAddr>| match address {
+        Some(address) => trusted_proxies
+            .iter()
+            .any(|network| network.contains(address)),
+        None => true,
+    };
+
+    let mut client = ClientInfo {
+        address: connection.and_then(|connection| connection.peer_address),
+        scheme: if connection.is_some_and(|connection| connection.tls) {
+            "https".to_owned()
+        } else {
+            "http".to_owned()
+        },
+        host: headers
+            .get(header::HOST)
+            .and_then(|host| host.to_str().ok())
+            .map(str::to_owned),
+    };
+    if connection.is_none() || !is_trusted(client.address) {
+        return client;
+    }
+
+    let mut hops = forwarded_hops(headers);
+    if hops.is_empty() {
+        hops = x_forwarded_hops(headers);
+    }
+    for hop in hops.into_iter().rev() {
+        let Some(address) = hop.client else {
+            break;
+        };
+        client.address = Some(address);
+        client.scheme = hop.scheme.unwrap_or(client.scheme);
+        client.host = hop.host.or(client.host);
+        if !is_trusted(Some(address)) {
+            break;
+        }
+    }
+    client
+}
+
+/// Adds a `ClientInfo` describing who sent each request to the request's
+/// extensions, taking the forwarding headers of `trusted_proxies` into
+/// account.
+pub async fn resolve_client(
+    State(trusted_proxies): State<Arc<[IpNetwork]>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let client = resolve(
+        request.extensions().get::<ConnectionInfo>().copied(),
+        request.headers(),
+        &trusted_proxies,
+    );
+    debug!(
+        "Request from {client} for {} {}",
+        request.method(),
+        request.uri()
+    );
+    request.extensions_mut().insert(client);
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    fn connection(peer_address: &str) -> ConnectionInfo {
+        ConnectionInfo {
+            peer_address: Some(peer_address.parse().unwrap()),
+            tls: false,
+        }
+    }
+
+    #[test]
+    fn test_ip_network() {
+        let network = "10.0.0.0/8".parse::<IpNetwork>().unwrap();
+        assert!(network.contains("10.1.2.3".parse().unwrap()));
+        assert!(network.contains("::ffff:10.1.2.3".parse().unwrap()));
+        assert!(!network.contains("11.0.0.1".parse().unwrap()));
+
+        let network = "::1".parse::<IpNetwork>().unwrap();
+        assert!(network.contains("::1".parse().unwrap()));
+        assert!(!network.contains("::2".parse().unwrap()));
+
+        assert!("0.0.0.0/0"
+            .parse::<IpNetwork>()
+            .unwrap()
+            .contains("1.2.3.4".parse().unwrap()));
+        assert!("10.0.0.0/33".parse::<IpNetwork>().is_err());
+        assert!("localhost".parse::<IpNetwork>().is_err());
+    }
+
+    #[test]
+    fn test_untrusted_peer() {
+        let headers = headers(&[
+            ("host", "example.com"),
+            ("x-forwarded-for", "1.2.3.4"),
+            ("x-forwarded-proto", "https"),
+        ]);
+        let client = resolve(Some(connection("5.6.7.8")), &headers, &[]);
+        assert_eq!(client.address, Some("5.6.7.8".parse().unwrap()));
+        assert_eq!(client.scheme, "http");
+        assert_eq!(client.host.as_deref(), Some("example.com"));
+    }
+
+    #[test]
+    fn test_x_forwarded() {
+        let trusted = ["10.0.0.0/8".parse().unwrap()];
+        let headers = headers(&[
+            ("host", "internal"),
+            ("x-forwarded-for", "6.6.6.6, 1.2.3.4, 10.0.0.2"),
+            ("x-forwarded-proto", "https"),
+            ("x-forwarded-host", "recipes.example.com"),
+        ]);
+        let client = resolve(Some(connection("10.0.0.1")), &headers, &trusted);
+        assert_eq!(client.address, Some("1.2.3.4".parse().unwrap()));
+        assert_eq!(client.scheme, "https");
+        assert_eq!(client.host.as_deref(), Some("recipes.example.com"));
+    }
+
+    #[test]
+    fn test_forwarded() {
+        let trusted = ["127.0.0.1".parse().unwrap()];
+        let headers = headers(&[
+            (
+                "forwarded",
+                "for=\"[2001:db8::1]:4711\";proto=https;host=a.b",
+            ),
+            ("x-forwarded-for", "1.2.3.4"),
+        ]);
+        let client = resolve(Some(connection("127.0.0.1")), &headers, &trusted);
+        assert_eq!(client.address, Some("2001:db8::1".parse().unwrap()));
+        assert_eq!(client.scheme, "https");
+        assert_eq!(client.host.as_deref(), Some("a.b"));
+    }
+}
@@ -0,0 +1,484 @@
+//! The HTTP(S) server: accepting connections, serving the app on them, and
+//! shutting down gracefully.
+
+mod tls;
+
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use axum::{
+    extract::Request,
+    http::{header, HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Redirect},
+    Extension, Router,
+};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto::Builder;
+use hyper_util::service::TowerToHyperService;
+use log::{debug, info, warn};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, watch};
+use tokio_rustls::TlsAcceptor;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::config::ServerConfig;
+#[cfg(unix)]
+use crate::config::UnixSocketConfig;
+use crate::proxy::ConnectionInfo;
+use crate::systemd;
+use crate::util::stringify_err;
+
+/// How long a client has to complete a TLS handshake, in seconds.
+const TLS_HANDSHAKE_TIMEOUT_SECONDS: u64 = 10;
+
+/// How long to wait before accepting again after accepting a connection
+/// fails (e.g. because too many files are open), in milliseconds.
+const ACCEPT_ERROR_DELAY_MILLISECONDS: u64 = 100;
+
+/// Waits until the process is asked to stop by SIGINT (e.g. Ctrl+C) or, on
+/// Unix, SIGTERM. Returns the name of the signal received.
+async fn shutdown_signal() -> Result<&'static str, String> {
+    #[cfg(unix)]
+    let mut terminate = stringify_err(tokio::signal::unix::signal(
+        tokio::signal::unix::SignalKind::terminate(),
+    ))?;
+
+    #[cfg(unix)]
+    let terminated = terminate.recv();
+    #[cfg(not(unix))]
+    let terminated = std::future::pending::<Option<()>>();
+
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => {
+            stringify_err(result)?;
+            Ok("SIGINT")
+        }
+        _ = terminated => Ok("SIGTERM"),
+    }
+}
+
+/// A socket that accepts connections.
+trait Listener: Send + 'static {
+    /// A connection accepted by the listener.
+    type Stream: AsyncRead + AsyncWrite + Send + Unpin + 'static;
+
+    /// Waits for a connection, and returns it along with the address of the
+    /// peer, if it has one.
+    fn accept(
+        &self,
+    ) -> impl Future<Output = io::Result<(Self::Stream, Option<SocketAddr>)>> + Send;
+}
+
+impl Listener for TcpListener {
+    type Stream = tokio::net::TcpStream;
+
+    async fn accept(&self) -> io::Result<(Self::Stream, Option<SocketAddr>)> {
+        let (stream, address) = TcpListener::accept(self).await?;
+        Ok((stream, Some(address)))
+    }
+}
+
+#[cfg(unix)]
+impl Listener for tokio::net::UnixListener {
+    type Stream = tokio::net::UnixStream;
+
+    async fn accept(&self) -> io::Result<(Self::Stream, Option<SocketAddr>)> {
+        let (stream, _) = tokio::net::UnixListener::accept(self).await?;
+        Ok((stream, None))
+    }
+}
+
+/// Serves `app` on `io` until the client closes the connection or `shutdown`
+/// changes. After `shutdown` changes, the request in progress (if any) is
+/// finished before the connection is closed.
+///
+/// `connection_info` is added to the extensions of every request.
+async fn serve_connection<I>(
+    io: I,
+    app: Router,
+    connection_info: ConnectionInfo,
+    mut shutdown: watch::Receiver<()>,
+) where
+    I: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let builder = Builder::new(TokioExecutor::new());
+    let connection = builder.serve_connection_with_upgrades(
+        TokioIo::new(io),
+        TowerToHyperService::new(Extension(connection_info).layer(app)),
+    );
+    tokio::pin!(connection);
+
+    let result = tokio::select! {
+        result = connection.as_mut() => result,
+        _ = shutdown.changed() => {
+            connection.as_mut().graceful_shutdown();
+            connection.await
+        }
+    };
+    if let Err(error) = result {
+        debug!("Error serving connection: {error}");
+    }
+}
+
+/// Accepts connections from `listener` and serves `app` on each of them,
+/// using TLS if `tls_acceptor` is present, until `shutdown` changes.
+///
+/// Each connection holds a clone of `in_progress` until it is closed, so
+/// that shutdown can wait for connections to finish.
+async fn accept_connections(
+    listener: impl Listener,
+    tls_acceptor: Option<TlsAcceptor>,
+    app: Router,
+    mut shutdown: watch::Receiver<()>,
+    in_progress: mpsc::Sender<()>,
+) {
+    loop {
+        let (stream, peer_address) = tokio::select! {
+            result = listener.accept() => match result {
+                Ok(accepted) => accepted,
+                Err(error) => {
+                    warn!("Could not accept connection: {error}");
+                    tokio::time::sleep(Duration::from_millis(
+                        ACCEPT_ERROR_DELAY_MILLISECONDS,
+                    ))
+                    .await;
+                    continue;
+                }
+            },
+            _ = shutdown.changed() => return,
+        };
+
+        let connection_info = ConnectionInfo {
+            peer_address: peer_address.map(|address| address.ip()),
+            tls: tls_acceptor.is_some(),
+        };
+        let tls_acceptor = tls_acceptor.clone();
+        let app = app.clone();
+        let shutdown = shutdown.clone();
+        let in_progress = in_progress.clone();
+        tokio::spawn(async move {
+            let Some(tls_acceptor) = tls_acceptor else {
+                serve_connection(stream, app, connection_info, shutdown).await;
+                drop(in_progress);
+                return;
+            };
+
+            let handshake = tokio::time::timeout(
+                Duration::from_secs(TLS_HANDSHAKE_TIMEOUT_SECONDS),
+                tls_acceptor.accept(stream),
+            );
+            let client = connection_info.peer_address.map_or_else(
+                || "client".to_owned(),
+                |address| address.to_string(),
+            );
+            match handshake.await {
+                Ok(Ok(stream)) => {
+                    serve_connection(stream, app, connection_info, shutdown)
+                        .await;
+                }
+                Ok(Err(error)) => {
+                    debug!("TLS handshake with {client} failed: {error}");
+                }
+                Err(_) => debug!("TLS handshake with {client} timed out"),
+            }
+            drop(in_progress);
+        });
+    }
+}
+
+/// Returns a router that redirects every request to the same URL over HTTPS
+/// on `https_port`.
+fn redirect_router(https_port: u16) -> Router {
+    Router::new().fallback(move |headers: HeaderMap, uri: Uri| async move {
+        let Some(host) = headers
+            .get(header::HOST)
+            .and_then(|host| host.to_str().ok())
+        else {
+            return (StatusCode::BAD_REQUEST, "Missing Host header")
+                .into_response();
+        };
+
+        // Remove any port from the host, taking care with IPv6 addresses such
+        // as "[::1]:80".
+        let host = match host.rfind(':') {
+            Some(index) if !host[index..].contains(']') => &host[..index],
+            _ => host,
+        };
+        let authority = if https_port == 443 {
+            host.to_owned()
+        } else {
+            format!("{host}:{https_port}")
+        };
+        let path = uri.path_and_query().map_or("/", |path| path.as_str());
+
+        Redirect::permanent(&format!("https://{authority}{path}"))
+            .into_response()
+    })
+}
+
+/// Binds a TCP listener to `address`.
+async fn bind(address: SocketAddr) -> Result<TcpListener, String> {
+    info!("Binding to {address}");
+    TcpListener::bind(address)
+        .await
+        .map_err(|error| format!("Could not bind to {address}: {error}"))
+}
+
+/// Creates a Unix domain socket as configured by `config`, replacing any
+/// socket left at its path by a previous run.
+#[cfg(unix)]
+fn bind_unix_socket(
+    config: &UnixSocketConfig,
+) -> Result<tokio::net::UnixListener, String> {
+    use std::fs;
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    let path = &config.path;
+    info!("Binding to Unix socket {}", path.display());
+
+    // Only sockets are removed, so that a mistyped path can't delete a file.
+    if fs::symlink_metadata(path)
+        .is_ok_and(|metadata| metadata.file_type().is_socket())
+    {
+        fs::remove_file(path).map_err(|error| {
+            format!("Could not remove old socket {}: {error}", path.display())
+        })?;
+    }
+
+    let listener = tokio::net::UnixListener::bind(path).map_err(|error| {
+        format!("Could not bind to {}: {error}", path.display())
+    })?;
+    fs::set_permissions(path, fs::Permissions::from_mode(config.mode))
+        .map_err(|error| {
+            format!("Could not set permissions of {}: {error}", path.display())
+        })?;
+    Ok(listener)
+}
+
+/// The sockets that the server accepts connections from.
+#[derive(Default)]
+struct Listeners {
+    /// Sockets serving the app, over TLS if it is configured.
+    tcp: Vec<TcpListener>,
+
+    /// Unix sockets serving the app, never over TLS.
+    #[cfg(unix)]
+    unix: Vec<tokio::net::UnixListener>,
+
+    /// The sockets serving redirects from HTTP to HTTPS, if any.
+    redirect: Vec<TcpListener>,
+
+    /// The socket serving administrative endpoints, if any.
+    admin: Option<TcpListener>,
+
+    /// The path of the Unix socket created by the server, if any, which is
+    /// removed on shutdown.
+    created_socket_path: Option<PathBuf>,
+}
+
+/// Binds the sockets described by `config`. Sockets passed by systemd socket
+/// activation are used instead of the configured addresses and Unix socket.
+async fn bind_listeners(config: &ServerConfig) -> Result<Listeners, String> {
+    let mut listeners = Listeners::default();
+
+    let activated = systemd::activated_listeners()?;
+    if activated.is_empty() {
+        for address in
+            std::iter::once(SocketAddr::new(config.ip_address, config.port))
+                .chain(config.additional_addresses.iter().copied())
+        {
+            listeners.tcp.push(bind(address).await?);
+        }
+
+        #[cfg(unix)]
+        if let Some(unix_socket) = &config.unix_socket {
+            listeners.unix.push(bind_unix_socket(unix_socket)?);
+            listeners.created_socket_path = Some(unix_socket.path.clone());
+        }
+        #[cfg(not(unix))]
+        if config.unix_socket.is_some() {
+            return Err(
+                "Unix sockets are not supported on this platform".to_owned()
+            );
+        }
+    } else {
+        info!("Using sockets passed by systemd");
+        for listener in activated.tcp {
+            stringify_err(listener.set_nonblocking(true))?;
+            listeners
+                .tcp
+                .push(stringify_err(TcpListener::from_std(listener))?);
+        }
+
+        #[cfg(unix)]
+        for listener in activated.unix {
+            stringify_err(listener.set_nonblocking(true))?;
+            listeners.unix.push(stringify_err(
+                tokio::net::UnixListener::from_std(listener),
+            )?);
+        }
+    }
+
+    // Redirects are served on the redirect port of each configured IP
+    // address, once per address.
+    if let Some(redirect_port) =
+        config.tls.as_ref().and_then(|tls| tls.redirect_http_port)
+    {
+        let mut ip_addresses = vec![config.ip_address];
+        for address in &config.additional_addresses {
+            if !ip_addresses.contains(&address.ip()) {
+                ip_addresses.push(address.ip());
+            }
+        }
+        for ip_address in ip_addresses {
+            listeners
+                .redirect
+                .push(bind(SocketAddr::new(ip_address, redirect_port)).await?);
+        }
+    }
+    if let Some(admin_address) = config.admin_address {
+        listeners.admin = Some(bind(admin_address).await?);
+    }
+
+    Ok(listeners)
+}
+
+/// Returns a router that passes requests on to the router in `app` once it
+/// has been set, and responds to them with a 503 status until then.
+pub fn deferred_router(app: Arc<OnceLock<Router>>) -> Router {
+    Router::new().fallback(move |request: Request| async move {
+        match app.get() {
+            Some(app) => app.clone().call(request).await.into_response(),
+            None => (StatusCode::SERVICE_UNAVAILABLE, "The server is starting")
+                .into_response(),
+        }
+    })
+}
+
+/// Removes the Unix socket at `path`, if the server created one.
+fn remove_created_socket(path: Option<PathBuf>) {
+    if let Some(path) = path {
+        if let Err(error) = std::fs::remove_file(&path) {
+            warn!("Could not remove socket {}: {error}", path.display());
+        }
+    }
+}
+
+/// Serves `app` as configured by `config` until the process receives a
+/// shutdown signal.
+///
+/// `app` is served on every configured address and on the Unix socket, if
+/// any. TLS is used for TCP connections if it is configured, but not for Unix
+/// sockets, which are expected to sit behind a reverse proxy. `admin_app` is
+/// served over plain HTTP on the admin address, if one is configured.
+///
+/// `startup` runs once the sockets are being served, and the server is only
+/// reported to systemd as ready once it finishes. If it fails, the server
+/// stops with its error.
+///
+/// On shutdown, the server stops accepting connections and waits up to
+/// `config.shutdown_timeout_seconds` for requests in progress to finish.
+pub async fn run_server(
+    config: &ServerConfig,
+    app: Router,
+    admin_app: Router,
+    startup: impl Future<Output = Result<(), String>>,
+) -> Result<(), String> {
+    let (shutdown_sender, shutdown) = watch::channel(());
+    let (in_progress, mut all_finished) = mpsc::channel::<()>(1);
+
+    // Load the certificate and bind everything before serving anything, so
+    // that configuration errors are reported before the server starts.
+    let tls_acceptor = match &config.tls {
+        Some(tls_config) => Some(tls::create_acceptor(tls_config.clone())?),
+        None => None,
+    };
+    let listeners = bind_listeners(config).await?;
+
+    // Redirect to the port HTTPS is actually served on, which differs from
+    // `config.port` if the sockets were passed by systemd.
+    let https_port = listeners
+        .tcp
+        .first()
+        .and_then(|listener| listener.local_addr().ok())
+        .map_or(config.port, |address| address.port());
+
+    for listener in listeners.tcp {
+        tokio::spawn(accept_connections(
+            listener,
+            tls_acceptor.clone(),
+            app.clone(),
+            shutdown.clone(),
+            in_progress.clone(),
+        ));
+    }
+    #[cfg(unix)]
+    for listener in listeners.unix {
+        tokio::spawn(accept_connections(
+            listener,
+            None,
+            app.clone(),
+            shutdown.clone(),
+            in_progress.clone(),
+        ));
+    }
+    if !listeners.redirect.is_empty() {
+        info!("Redirecting HTTP to HTTPS on port {https_port}");
+    }
+    for listener in listeners.redirect {
+        tokio::spawn(accept_connections(
+            listener,
+            None,
+            redirect_router(https_port),
+            shutdown.clone(),
+            in_progress.clone(),
+        ));
+    }
+    if let Some(listener) = listeners.admin {
+        tokio::spawn(accept_connections(
+            listener,
+            None,
+            admin_app,
+            shutdown.clone(),
+            in_progress.clone(),
+        ));
+    }
+    drop(in_progress);
+
+    if let Err(error) = startup.await {
+        shutdown_sender.send_replace(());
+        remove_created_socket(listeners.created_socket_path);
+        return Err(error);
+    }
+    systemd::notify("READY=1");
+    systemd::notify_status("Serving requests");
+
+    let signal = shutdown_signal().await?;
+    info!("Received {signal}; shutting down");
+    systemd::notify("STOPPING=1");
+    systemd::notify_status("Shutting down");
+
+    // Stop accepting connections, then give requests in progress up to
+    // `shutdown_timeout` to finish.
+    shutdown_sender.send_replace(());
+    let shutdown_timeout = Duration::from_secs(config.shutdown_timeout_seconds);
+    if tokio::time::timeout(shutdown_timeout, all_finished.recv())
+        .await
+        .is_err()
+    {
+        warn!(
+            "Requests still in progress after {}s; abandoning them",
+            shutdown_timeout.as_secs()
+        );
+    }
+
+    remove_created_socket(listeners.created_socket_path);
+
+    Ok(())
+}
@@ -0,0 +1,155 @@
+//! TLS for the HTTPS server, with certificates that are reloaded when their
+//! files change.
+
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::{Duration, SystemTime};
+
+use log::{debug, info, warn};
+use tokio_rustls::rustls::crypto::ring::sign::any_supported_type;
+use tokio_rustls::rustls::server::{ClientHello, ResolvesServerCert};
+use tokio_rustls::rustls::sign::CertifiedKey;
+use tokio_rustls::rustls::{Error, InconsistentKeys, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+
+use crate::config::TlsConfig;
+use crate::util::stringify_err;
+
+/// How often to check whether the certificate files have changed, in seconds.
+const RELOAD_CHECK_INTERVAL_SECONDS: u64 = 10;
+
+/// Reads the certificate chain and private key at the paths in `config`.
+///
+/// Fails if the key doesn't match the certificate, e.g. when only one of the
+/// files has been replaced so far during a renewal.
+fn load_certified_key(config: &TlsConfig) -> Result<CertifiedKey, String> {
+    let open = |path: &Path| {
+        File::open(path).map(BufReader::new).map_err(|error| {
+            format!("Could not open {}: {error}", path.display())
+        })
+    };
+
+    let certificates =
+        rustls_pemfile::certs(&mut open(&config.certificate_path)?)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|error| {
+                format!(
+                    "Invalid certificate file {}: {error}",
+                    config.certificate_path.display()
+                )
+            })?;
+    if certificates.is_empty() {
+        return Err(format!(
+            "No certificates found in {}",
+            config.certificate_path.display()
+        ));
+    }
+
+    let key = rustls_pemfile::private_key(&mut open(&config.key_path)?)
+        .map_err(|error| {
+            format!("Invalid key file {}: {error}", config.key_path.display())
+        })?
+        .ok_or_else(|| {
+            format!("No private key found in {}", config.key_path.display())
+        })?;
+    let signing_key = any_supported_type(&key).map_err(|error| {
+        format!("Unsupported key in {}: {error}", config.key_path.display())
+    })?;
+
+    let certified_key = CertifiedKey::new(certificates, signing_key);
+    match certified_key.keys_match() {
+        // Some keys can't report their public key, so can't be checked.
+        Ok(()) | Err(Error::InconsistentKeys(InconsistentKeys::Unknown)) => {
+            Ok(certified_key)
+        }
+        Err(error) => Err(format!(
+            "The key in {} does not match the certificate in {}: {error}",
+            config.key_path.display(),
+            config.certificate_path.display()
+        )),
+    }
+}
+
+/// Returns when the certificate and key files were last modified, or `None`
+/// if either can't be determined.
+fn modification_times(config: &TlsConfig) -> Option<(SystemTime, SystemTime)> {
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified());
+    Some((
+        modified(&config.certificate_path).ok()?,
+        modified(&config.key_path).ok()?,
+    ))
+}
+
+/// Provides the current certificate to every TLS handshake.
+#[derive(Debug)]
+struct CertificateResolver {
+    certified_key: RwLock<Arc<CertifiedKey>>,
+}
+
+impl ResolvesServerCert for CertificateResolver {
+    fn resolve(&self, _: ClientHello) -> Option<Arc<CertifiedKey>> {
+        Some(
+            self.certified_key
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone(),
+        )
+    }
+}
+
+/// Creates an acceptor for TLS connections using the certificate and key in
+/// `config`.
+///
+/// The files are checked for changes every `RELOAD_CHECK_INTERVAL_SECONDS`,
+/// and reloaded if they have changed. If they can't be reloaded, the previous
+/// certificate is kept.
+pub fn create_acceptor(config: TlsConfig) -> Result<TlsAcceptor, String> {
+    let resolver = Arc::new(CertificateResolver {
+        certified_key: RwLock::new(Arc::new(load_certified_key(&config)?)),
+    });
+
+    let mut server_config = stringify_err(
+        ServerConfig::builder_with_provider(Arc::new(
+            tokio_rustls::rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions(),
+    )?
+    .with_no_client_auth()
+    .with_cert_resolver(resolver.clone());
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    tokio::spawn(async move {
+        let mut last_modified = modification_times(&config);
+        let mut interval = tokio::time::interval(Duration::from_secs(
+            RELOAD_CHECK_INTERVAL_SECONDS,
+        ));
+        loop {
+            interval.tick().await;
+            let modified = modification_times(&config);
+            if modified.is_none() || modified == last_modified {
+                continue;
+            }
+            last_modified = modified;
+
+            debug!("TLS certificate files changed; reloading");
+            match load_certified_key(&config) {
+                Ok(certified_key) => {
+                    *resolver
+                        .certified_key
+                        .write()
+                        .unwrap_or_else(PoisonError::into_inner) =
+                        Arc::new(certified_key);
+                    info!("Reloaded TLS certificate");
+                }
+                Err(error) => warn!(
+                    "Could not reload TLS certificate; keeping the previous \
+                     one: {error}"
+                ),
+            }
+        }
+    });
+
+    Ok(TlsAcceptor::from(Arc::new(server_config)))
+}
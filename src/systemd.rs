@@ -0,0 +1,160 @@
+//! Integration with systemd: socket activation, readiness and status
+//! notifications, and the watchdog.
+//!
+//! Everything here does nothing when the server isn't run by systemd, or on
+//! platforms other than Unix.
+
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{debug, warn};
+
+use crate::database::Database;
+
+/// How long the database has to answer a watchdog health check.
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Listening sockets passed to the server by systemd socket activation.
+#[derive(Default)]
+pub struct ActivatedListeners {
+    pub tcp: Vec<std::net::TcpListener>,
+
+    #[cfg(unix)]
+    pub unix: Vec<std::os::unix::net::UnixListener>,
+}
+
+impl ActivatedListeners {
+    /// Returns whether systemd passed no sockets.
+    pub fn is_empty(&self) -> bool {
+        #[cfg(unix)]
+        return self.tcp.is_empty() && self.unix.is_empty();
+        #[cfg(not(unix))]
+        return self.tcp.is_empty();
+    }
+}
+
+/// Returns the value of the environment variable `name` if it was set by
+/// systemd for this process, as shown by `pid_variable` (e.g. `LISTEN_PID`)
+/// being absent or matching the process's ID.
+fn variable_for_this_process(name: &str, pid_variable: &str) -> Option<String> {
+    let value = env::var(name).ok()?;
+    match env::var(pid_variable) {
+        Ok(pid) if pid != std::process::id().to_string() => None,
+        _ => Some(value),
+    }
+}
+
+/// Takes the listening sockets passed by systemd socket activation, as
+/// described by the `LISTEN_PID` and `LISTEN_FDS` environment variables.
+///
+/// Returns no listeners if the server was not socket activated.
+#[cfg(unix)]
+pub fn activated_listeners() -> Result<ActivatedListeners, String> {
+    use std::os::fd::{FromRawFd, IntoRawFd, RawFd};
+
+    /// The first file descriptor passed by systemd.
+    const LISTEN_FDS_START: RawFd = 3;
+
+    let mut listeners = ActivatedListeners::default();
+    if env::var("LISTEN_PID").is_err() {
+        return Ok(listeners);
+    }
+    let Some(count) = variable_for_this_process("LISTEN_FDS", "LISTEN_PID")
+    else {
+        return Ok(listeners);
+    };
+    let count: RawFd = count
+        .parse()
+        .map_err(|_| format!("Invalid LISTEN_FDS value {count}"))?;
+
+    for fd in LISTEN_FDS_START..LISTEN_FDS_START + count {
+        // SAFETY: systemd passes `count` open listening sockets starting at
+        // `LISTEN_FDS_START`, and nothing else in the process owns them.
+        let tcp_listener = unsafe { std::net::TcpListener::from_raw_fd(fd) };
+        if tcp_listener.local_addr().is_ok() {
+            debug!("Received TCP socket {fd} from systemd");
+            listeners.tcp.push(tcp_listener);
+        } else {
+            // Sockets without an IP address are taken to be Unix sockets.
+            let fd = tcp_listener.into_raw_fd();
+            // SAFETY: as above; ownership was just released by `into_raw_fd`.
+            let unix_listener =
+                unsafe { std::os::unix::net::UnixListener::from_raw_fd(fd) };
+            debug!("Received Unix socket {fd} from systemd");
+            listeners.unix.push(unix_listener);
+        }
+    }
+    Ok(listeners)
+}
+
+#[cfg(not(unix))]
+pub fn activated_listeners() -> Result<ActivatedListeners, String> {
+    Ok(ActivatedListeners::default())
+}
+
+/// Sends `state` (e.g. `"READY=1"`) to systemd's notification socket, given
+/// by the `NOTIFY_SOCKET` environment variable. Failures are logged.
+pub fn notify(state: &str) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::net::UnixDatagram;
+
+        let Ok(socket_path) = env::var("NOTIFY_SOCKET") else {
+            return;
+        };
+        let result = UnixDatagram::unbound().and_then(|socket| {
+            // Paths starting with "@" are in Linux's abstract namespace.
+            #[cfg(target_os = "linux")]
+            if let Some(name) = socket_path.strip_prefix('@') {
+                use std::os::linux::net::SocketAddrExt;
+                let address =
+                    std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+                return socket.send_to_addr(state.as_bytes(), &address);
+            }
+            socket.send_to(state.as_bytes(), &socket_path)
+        });
+        if let Err(error) = result {
+            warn!("Could not notify systemd of {state:?}: {error}");
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = state;
+}
+
+/// Sets the status shown by `systemctl status`.
+pub fn notify_status(status: &str) {
+    notify(&format!("STATUS={status}"));
+}
+
+/// Starts a task that pings systemd's watchdog while `database` responds to
+/// queries, if systemd enabled the watchdog with `WATCHDOG_USEC`.
+///
+/// If the database stops responding, the pings stop, so systemd restarts the
+/// server.
+pub fn spawn_watchdog(database: Arc<Database>) {
+    let Some(interval) =
+        variable_for_this_process("WATCHDOG_USEC", "WATCHDOG_PID")
+            .and_then(|microseconds| microseconds.parse().ok())
+            .map(Duration::from_micros)
+    else {
+        return;
+    };
+
+    debug!("Pinging systemd watchdog every {:?}", interval / 2);
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(interval / 2);
+        loop {
+            ticks.tick().await;
+            match tokio::time::timeout(HEALTH_CHECK_TIMEOUT, database.ping())
+                .await
+            {
+                Ok(Ok(())) => notify("WATCHDOG=1"),
+                Ok(Err(error)) => {
+                    warn!("Database health check failed: {error}");
+                }
+                Err(_) => warn!("Database health check timed out"),
+            }
+        }
+    });
+}
@@ -0,0 +1,382 @@
+use chrono::Duration;
+
+use crate::models::MeasurementType;
+
+/// A unit of measurement that can appear in a human-written recipe.
+#[derive(Clone, Copy)]
+pub struct Unit {
+    /// The canonical (short) name of the unit.
+    pub name: &'static str,
+
+    /// The kind of quantity that the unit measures.
+    pub measurement: MeasurementType,
+
+    /// The number of SI standard units (kg, m³, or items) in one of this unit.
+    pub si_factor: f64,
+}
+
+const fn unit(
+    name: &'static str,
+    measurement: MeasurementType,
+    si_factor: f64,
+) -> Unit {
+    Unit {
+        name,
+        measurement,
+        si_factor,
+    }
+}
+
+const GRAM: Unit = unit("g", MeasurementType::Mass, 1e-3);
+const KILOGRAM: Unit = unit("kg", MeasurementType::Mass, 1.0);
+const MILLIGRAM: Unit = unit("mg", MeasurementType::Mass, 1e-6);
+const OUNCE: Unit = unit("oz", MeasurementType::Mass, 0.028_349_523_125);
+const POUND: Unit = unit("lb", MeasurementType::Mass, 0.453_592_37);
+
+const MILLILITER: Unit = unit("ml", MeasurementType::Volume, 1e-6);
+const CENTILITER: Unit = unit("cl", MeasurementType::Volume, 1e-5);
+const DECILITER: Unit = unit("dl", MeasurementType::Volume, 1e-4);
+const LITER: Unit = unit("l", MeasurementType::Volume, 1e-3);
+const TEASPOON: Unit =
+    unit("tsp", MeasurementType::Volume, 4.928_921_593_75e-6);
+const TABLESPOON: Unit =
+    unit("tbsp", MeasurementType::Volume, 1.478_676_478_125e-5);
+const FLUID_OUNCE: Unit =
+    unit("fl oz", MeasurementType::Volume, 2.957_352_956_25e-5);
+const CUP: Unit = unit("cup", MeasurementType::Volume, 2.365_882_365e-4);
+const PINT: Unit = unit("pt", MeasurementType::Volume, 4.731_764_73e-4);
+const QUART: Unit = unit("qt", MeasurementType::Volume, 9.463_529_46e-4);
+const GALLON: Unit = unit("gal", MeasurementType::Volume, 3.785_411_784e-3);
+const PINCH: Unit = unit("pinch", MeasurementType::Volume, 3.080_575_996e-7);
+const DASH: Unit = unit("dash", MeasurementType::Volume, 6.161_151_992e-7);
+
+const ITEM: Unit = unit("", MeasurementType::Count, 1.0);
+
+/// Case-sensitive spellings that must be checked before case-insensitive
+/// lookup (e.g. "T" is a tablespoon, but "t" is a teaspoon).
+const CASE_SENSITIVE_UNITS: &[(&str, Unit)] =
+    &[("T", TABLESPOON), ("t", TEASPOON), ("C", CUP), ("c", CUP)];
+
+/// All recognized (lowercase) spellings of units.
+const UNITS: &[(&str, Unit)] = &[
+    ("g", GRAM),
+    ("gr", GRAM),
+    ("gram", GRAM),
+    ("grams", GRAM),
+    ("gramme", GRAM),
+    ("grammes", GRAM),
+    ("kg", KILOGRAM),
+    ("kgs", KILOGRAM),
+    ("kilo", KILOGRAM),
+    ("kilos", KILOGRAM),
+    ("kilogram", KILOGRAM),
+    ("kilograms", KILOGRAM),
+    ("mg", MILLIGRAM),
+    ("milligram", MILLIGRAM),
+    ("milligrams", MILLIGRAM),
+    ("oz", OUNCE),
+    ("ounce", OUNCE),
+    ("ounces", OUNCE),
+    ("lb", POUND),
+    ("lbs", POUND),
+    ("pound", POUND),
+    ("pounds", POUND),
+    ("ml", MILLILITER),
+    ("milliliter", MILLILITER),
+    ("milliliters", MILLILITER),
+    ("millilitre", MILLILITER),
+    ("millilitres", MILLILITER),
+    ("cc", MILLILITER),
+    ("cb", MILLILITER),
+    ("cl", CENTILITER),
+    ("dl", DECILITER),
+    ("l", LITER),
+    ("liter", LITER),
+    ("liters", LITER),
+    ("litre", LITER),
+    ("litres", LITER),
+    ("tsp", TEASPOON),
+    ("tsps", TEASPOON),
+    ("ts", TEASPOON),
+    ("teaspoon", TEASPOON),
+    ("teaspoons", TEASPOON),
+    ("tbsp", TABLESPOON),
+    ("tbsps", TABLESPOON),
+    ("tbs", TABLESPOON),
+    ("tb", TABLESPOON),
+    ("tablespoon", TABLESPOON),
+    ("tablespoons", TABLESPOON),
+    ("fl oz", FLUID_OUNCE),
+    ("fl. oz", FLUID_OUNCE),
+    ("fl", FLUID_OUNCE),
+    ("floz", FLUID_OUNCE),
+    ("fluid ounce", FLUID_OUNCE),
+    ("fluid ounces", FLUID_OUNCE),
+    ("cup", CUP),
+    ("cups", CUP),
+    ("pt", PINT),
+    ("pint", PINT),
+    ("pints", PINT),
+    ("qt", QUART),
+    ("quart", QUART),
+    ("quarts", QUART),
+    ("ga", GALLON),
+    ("gal", GALLON),
+    ("gallon", GALLON),
+    ("gallons", GALLON),
+    ("pinch", PINCH),
+    ("pinches", PINCH),
+    ("pn", PINCH),
+    ("dash", DASH),
+    ("dashes", DASH),
+    ("ds", DASH),
+    ("", ITEM),
+    ("x", ITEM),
+    ("ea", ITEM),
+    ("each", ITEM),
+    ("piece", ITEM),
+    ("pieces", ITEM),
+    ("whole", ITEM),
+];
+
+/// Looks up the unit named `name`, ignoring a trailing period and (except for
+/// a few single-letter abbreviations) case.
+///
+/// Returns `None` if the unit is not recognized.
+pub fn lookup_unit(name: &str) -> Option<Unit> {
+    let name = name.trim().trim_end_matches('.');
+
+    if let Some((_, unit)) = CASE_SENSITIVE_UNITS
+        .iter()
+        .find(|(spelling, _)| *spelling == name)
+    {
+        return Some(*unit);
+    }
+
+    let lowercase = name.to_lowercase();
+    UNITS
+        .iter()
+        .find(|(spelling, _)| *spelling == lowercase)
+        .map(|(_, unit)| *unit)
+}
+
+/// Converts `amount` of `unit` into SI standard units.
+pub fn to_si(amount: f64, unit: Unit) -> f64 {
+    amount * unit.si_factor
+}
+
+/// Picks a metric unit that is convenient for a human to read when displaying
+/// `quantity` (in SI standard units) of `measurement`.
+///
+/// Returns the amount in the chosen unit along with the unit's name. Counts
+/// have an empty unit name.
+pub fn to_human(
+    quantity: f64,
+    measurement: MeasurementType,
+) -> (f64, &'static str) {
+    let unit = match measurement {
+        MeasurementType::Mass if quantity >= KILOGRAM.si_factor => KILOGRAM,
+        MeasurementType::Mass => GRAM,
+        MeasurementType::Volume if quantity >= LITER.si_factor => LITER,
+        MeasurementType::Volume => MILLILITER,
+        MeasurementType::Count => ITEM,
+    };
+    (quantity / unit.si_factor, unit.name)
+}
+
+/// Formats a number with at most two decimal places and no trailing zeros.
+pub fn format_amount(amount: f64) -> String {
+    let formatted = format!("{amount:.2}");
+    let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+    if trimmed == "-0" {
+        "0".to_owned()
+    } else {
+        trimmed.to_owned()
+    }
+}
+
+/// Formats a quantity in SI standard units for display in human-friendly
+/// units, e.g. "250 g" or "1.5 l". Counts are formatted without a unit.
+pub fn format_quantity(quantity: f64, measurement: MeasurementType) -> String {
+    let (amount, unit) = to_human(quantity, measurement);
+    if unit.is_empty() {
+        format_amount(amount)
+    } else {
+        format!("{} {unit}", format_amount(amount))
+    }
+}
+
+/// Returns the numeric value of a Unicode vulgar fraction character.
+fn vulgar_fraction_value(character: char) -> Option<f64> {
+    Some(match character {
+        '¼' => 0.25,
+        '½' => 0.5,
+        '¾' => 0.75,
+        '⅓' => 1.0 / 3.0,
+        '⅔' => 2.0 / 3.0,
+        '⅕' => 0.2,
+        '⅖' => 0.4,
+        '⅗' => 0.6,
+        '⅘' => 0.8,
+        '⅙' => 1.0 / 6.0,
+        '⅚' => 5.0 / 6.0,
+        '⅛' => 0.125,
+        '⅜' => 0.375,
+        '⅝' => 0.625,
+        '⅞' => 0.875,
+        _ => return None,
+    })
+}
+
+/// Parses a single number written as an integer, a decimal, a fraction
+/// ("1/2"), or a vulgar fraction, optionally preceded by an integer ("1½").
+fn parse_number(text: &str) -> Option<f64> {
+    if let Some((numerator, denominator)) = text.split_once('/') {
+        let numerator: f64 = numerator.trim().parse().ok()?;
+        let denominator: f64 = denominator.trim().parse().ok()?;
+        return (denominator != 0.0).then(|| numerator / denominator);
+    }
+
+    if let Some(last) = text.chars().last() {
+        if let Some(fraction) = vulgar_fraction_value(last) {
+            let whole = &text[..text.len() - last.len_utf8()];
+            let whole: f64 = if whole.is_empty() {
+                0.0
+            } else {
+                whole.parse().ok()?
+            };
+            return Some(whole + fraction);
+        }
+    }
+
+    if !text.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+        return None;
+    }
+    text.parse().ok()
+}
+
+/// Parses a human-written amount such as "2", "0.5", "1/2", "1 1/2", "½" or
+/// "1½".
+///
+/// Ranges such as "2-3" are parsed as their lower bound.
+pub fn parse_amount(text: &str) -> Option<f64> {
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+
+    if let Some((lower, _)) = text.split_once(['-', '–']) {
+        if !lower.trim().is_empty() {
+            return parse_amount(lower);
+        }
+    }
+
+    let mut total = 0.0;
+    for part in text.split_whitespace() {
+        total += parse_number(part)?;
+    }
+    Some(total)
+}
+
+/// Returns the number of seconds in the time unit named `name` (e.g. "min",
+/// "hours").
+pub fn duration_unit_seconds(name: &str) -> Option<i64> {
+    Some(match name.to_lowercase().trim_end_matches('.') {
+        "s" | "sec" | "secs" | "second" | "seconds" => 1,
+        "m" | "min" | "mins" | "minute" | "minutes" => 60,
+        "h" | "hr" | "hrs" | "hour" | "hours" => 60 * 60,
+        "d" | "day" | "days" => 24 * 60 * 60,
+        _ => return None,
+    })
+}
+
+/// Splits `text` into runs of numeric characters and runs of letters,
+/// dropping whitespace and punctuation between them.
+fn tokenize(text: &str) -> Vec<(bool, String)> {
+    let mut tokens: Vec<(bool, String)> = vec![];
+    for character in text.chars() {
+        let is_numeric = character.is_ascii_digit()
+            || character == '/'
+            || vulgar_fraction_value(character).is_some();
+        let is_word = character.is_alphabetic() && !is_numeric;
+        let continues_number =
+            character == '.' && matches!(tokens.last(), Some((true, _)));
+
+        if is_numeric || continues_number {
+            match tokens.last_mut() {
+                Some((true, token)) => token.push(character),
+                _ => tokens.push((true, character.to_string())),
+            }
+        } else if is_word {
+            match tokens.last_mut() {
+                Some((false, token)) => token.push(character),
+                _ => tokens.push((false, character.to_string())),
+            }
+        } else if let Some((true, token)) = tokens.last_mut() {
+            // Keep whitespace inside amounts such as "1 1/2".
+            if !token.ends_with(' ') {
+                token.push(' ');
+            }
+        }
+    }
+    tokens
+}
+
+/// Parses a human-written duration such as "25 minutes", "1 hour 30 min",
+/// "1h30m" or "1 1/2 hours".
+///
+/// Returns `None` if any part of `text` is not understood.
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let mut seconds = 0.0;
+    let mut amount: Option<f64> = None;
+    let mut found_any = false;
+
+    for (is_numeric, token) in tokenize(text) {
+        if is_numeric {
+            amount = Some(parse_amount(&token)?);
+        } else if !(token.eq_ignore_ascii_case("and") && amount.is_none()) {
+            let unit_seconds = duration_unit_seconds(&token)?;
+            #[allow(clippy::cast_precision_loss)]
+            {
+                seconds += amount.take()? * unit_seconds as f64;
+            }
+            found_any = true;
+        }
+    }
+
+    if !found_any || amount.is_some() {
+        return None;
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    Some(Duration::seconds(seconds.round() as i64))
+}
+
+/// Formats `duration` for display, e.g. "1 hour 30 minutes".
+pub fn format_duration(duration: Duration) -> String {
+    let total_minutes = duration.num_minutes();
+    let hours = total_minutes / 60;
+    let minutes = total_minutes % 60;
+    let seconds = duration.num_seconds() % 60;
+
+    let mut parts = vec![];
+    if hours > 0 {
+        parts.push(format!(
+            "{hours} {}",
+            if hours == 1 { "hour" } else { "hours" }
+        ));
+    }
+    if minutes > 0 {
+        parts.push(format!(
+            "{minutes} {}",
+            if minutes == 1 { "minute" } else { "minutes" }
+        ));
+    }
+    if seconds > 0 || parts.is_empty() {
+        parts.push(format!(
+            "{seconds} {}",
+            if seconds == 1 { "second" } else { "seconds" }
+        ));
+    }
+    parts.join(" ")
+}
@@ -0,0 +1,59 @@
+// Registers the service worker that makes the site usable offline, and shows
+// whether changes made offline are waiting to be sent.
+if ("serviceWorker" in navigator) {
+  const status = document.createElement("p");
+  status.className = "status";
+  status.hidden = true;
+  document.body.prepend(status);
+
+  let pending = 0;
+  const rejected = [];
+
+  const showStatus = () => {
+    const messages = [];
+    if (!navigator.onLine) {
+      messages.push("You are offline. Recently viewed pages can still be read.");
+    }
+    if (pending > 0) {
+      messages.push(
+        `${pending} ${pending === 1 ? "change is" : "changes are"} ` +
+          "waiting to be sent.",
+      );
+    }
+    for (const change of rejected) {
+      messages.push(
+        `A change made offline (${change.method} ${change.url}) was ` +
+          `rejected with status ${change.status}.`,
+      );
+    }
+    status.textContent = messages.join(" ");
+    status.hidden = messages.length === 0;
+  };
+
+  navigator.serviceWorker.addEventListener("message", (event) => {
+    if (event.data?.type === "outbox") {
+      pending = event.data.pending;
+    } else if (event.data?.type === "rejected") {
+      rejected.push(event.data);
+    }
+    showStatus();
+  });
+
+  const replay = async () => {
+    const registration = await navigator.serviceWorker.ready;
+    registration.active?.postMessage({ type: "replay" });
+  };
+
+  window.addEventListener("online", () => {
+    showStatus();
+    replay();
+  });
+  window.addEventListener("offline", showStatus);
+
+  const basePath = document.documentElement.dataset.basePath ?? "";
+  navigator.serviceWorker
+    .register(`${basePath}/service-worker.js`, { scope: `${basePath}/` })
+    .then(replay)
+    .catch((error) => console.error(error));
+  showStatus();
+}
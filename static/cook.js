@@ -0,0 +1,198 @@
+// Cooking mode shows one step at a time. The current step and any timers are
+// kept on the server and polled for, so every device cooking the same recipe
+// shows the same thing. Without JavaScript, all steps are listed.
+const api = document.body.dataset.api;
+const steps = Array.from(document.querySelectorAll("#steps > li"));
+const timerList = document.getElementById("timers");
+const position = document.getElementById("position");
+const POLL_INTERVAL = 2000; // In milliseconds.
+
+let session = { step: 0, timers: [] };
+let clockOffset = 0; // The server's time minus this device's, in ms.
+const alerted = new Set();
+
+async function request(method, path = "", body = undefined) {
+  const options = { method };
+  if (body !== undefined) {
+    options.headers = { "Content-Type": "application/json" };
+    options.body = JSON.stringify(body);
+  }
+  const response = await fetch(api + path, options);
+  if (!response.ok) {
+    throw new Error(`${method} ${api}${path} failed: ${response.status}`);
+  }
+  return response.status === 204 ? null : response.json();
+}
+
+function secondsLeft(timer) {
+  const left = Date.parse(timer.ends) - (Date.now() + clockOffset);
+  return Math.max(0, Math.ceil(left / 1000));
+}
+
+function formatSeconds(total) {
+  const hours = Math.floor(total / 3600);
+  const minutes = Math.floor(total / 60) % 60;
+  const seconds = String(total % 60).padStart(2, "0");
+  return hours > 0
+    ? `${hours}:${String(minutes).padStart(2, "0")}:${seconds}`
+    : `${minutes}:${seconds}`;
+}
+
+function beep() {
+  navigator.vibrate?.([300, 200, 300, 200, 300]);
+  const AudioContext = window.AudioContext || window.webkitAudioContext;
+  if (!AudioContext) {
+    return;
+  }
+  const context = new AudioContext();
+  for (let index = 0; index < 3; index++) {
+    const oscillator = context.createOscillator();
+    oscillator.frequency.value = 880;
+    oscillator.connect(context.destination);
+    oscillator.start(context.currentTime + index * 0.5);
+    oscillator.stop(context.currentTime + index * 0.5 + 0.3);
+  }
+}
+
+// Updates the countdowns, alerting once for each timer that has finished.
+function tick() {
+  for (const timer of session.timers) {
+    const item = document.getElementById(`timer-${timer.id}`);
+    const left = secondsLeft(timer);
+    item.querySelector(".remaining").textContent =
+      left > 0 ? formatSeconds(left) : "Done";
+    item.classList.toggle("done", left === 0);
+    if (left === 0 && !alerted.has(timer.id)) {
+      alerted.add(timer.id);
+      beep();
+    }
+  }
+}
+
+function showStep(current) {
+  steps.forEach((step, index) => {
+    step.classList.toggle("current", index === current);
+  });
+  position.textContent = `Step ${current + 1} of ${steps.length}`;
+}
+
+function show(view) {
+  clockOffset = Date.parse(view.now) - Date.now();
+  session = view.session;
+  showStep(session.step);
+
+  timerList.replaceChildren(
+    ...session.timers.map((timer) => {
+      const item = document.createElement("li");
+      item.id = `timer-${timer.id}`;
+      const label = document.createElement("span");
+      label.textContent = `${timer.label} (step ${timer.step + 1})`;
+      const remaining = document.createElement("span");
+      remaining.className = "remaining";
+      const button = document.createElement("button");
+      button.type = "button";
+      button.dataset.action = "dismiss";
+      button.dataset.timer = timer.id;
+      button.textContent = "Stop";
+      item.append(label, remaining, button);
+      return item;
+    }),
+  );
+  tick();
+}
+
+async function run(method, path, body) {
+  try {
+    show(await request(method, path, body));
+  } catch (error) {
+    console.error(error);
+  }
+}
+
+// Steps are changed locally first, so that moving between them still works
+// while offline.
+function moveBy(offset) {
+  const step = session.step + offset;
+  if (step >= 0 && step < steps.length) {
+    session.step = step;
+    showStep(step);
+    run("PUT", "", { step });
+  }
+}
+
+document.addEventListener("click", async (event) => {
+  const button = event.target.closest("button[data-action]");
+  if (!button) {
+    return;
+  }
+  switch (button.dataset.action) {
+    case "previous":
+      moveBy(-1);
+      break;
+    case "next":
+      moveBy(1);
+      break;
+    case "timer":
+      run("POST", "/timers", {
+        step: steps.indexOf(button.closest("li")),
+        label: button.dataset.label,
+        seconds: Number(button.dataset.seconds),
+      });
+      break;
+    case "dismiss":
+      run("DELETE", `/timers/${button.dataset.timer}`);
+      break;
+    case "end":
+      try {
+        await request("DELETE");
+        window.location.href = document.body.dataset.recipe;
+      } catch (error) {
+        console.error(error);
+      }
+      break;
+  }
+});
+
+document.addEventListener("keydown", (event) => {
+  if (["ArrowRight", "PageDown", " "].includes(event.key)) {
+    event.preventDefault();
+    moveBy(1);
+  } else if (["ArrowLeft", "PageUp"].includes(event.key)) {
+    event.preventDefault();
+    moveBy(-1);
+  }
+});
+
+// Swiping left or right moves between steps.
+let touchStartX = null;
+document.addEventListener("touchstart", (event) => {
+  touchStartX = event.changedTouches[0].clientX;
+});
+document.addEventListener("touchend", (event) => {
+  const distance = event.changedTouches[0].clientX - touchStartX;
+  if (Math.abs(distance) > 80) {
+    moveBy(distance < 0 ? 1 : -1);
+  }
+});
+
+// Keep the screen on while cooking, where the browser allows it.
+async function keepAwake() {
+  if (document.visibilityState === "visible") {
+    await navigator.wakeLock?.request("screen").catch(() => {});
+  }
+}
+document.addEventListener("visibilitychange", keepAwake);
+keepAwake();
+
+document.documentElement.classList.add("cooking");
+document.querySelectorAll("[hidden]").forEach((element) => {
+  element.hidden = false;
+});
+showStep(0);
+run("GET");
+setInterval(() => {
+  if (document.visibilityState === "visible") {
+    run("GET");
+  }
+}, POLL_INTERVAL);
+setInterval(tick, 250);
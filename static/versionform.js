@@ -0,0 +1,40 @@
+// Rows are added by copying and clearing the last row of a list. Without
+// JavaScript, each list still ends with a blank row, and blank rows are
+// ignored when the form is submitted.
+document.addEventListener("click", (event) => {
+  const button = event.target.closest("button[data-action]");
+  if (!button) {
+    return;
+  }
+  const row = button.closest("li");
+  switch (button.dataset.action) {
+    case "add": {
+      const list = document.getElementById(button.dataset.list);
+      const copy = list.lastElementChild.cloneNode(true);
+      copy.querySelectorAll("input, textarea").forEach((field) => {
+        field.value = "";
+      });
+      copy.querySelectorAll("select").forEach((field) => {
+        field.selectedIndex = 0;
+      });
+      copy.querySelectorAll(".error").forEach((error) => error.remove());
+      list.appendChild(copy);
+      break;
+    }
+    case "remove":
+      if (row.parentElement.children.length > 1) {
+        row.remove();
+      }
+      break;
+    case "up":
+      if (row.previousElementSibling) {
+        row.parentElement.insertBefore(row, row.previousElementSibling);
+      }
+      break;
+    case "down":
+      if (row.nextElementSibling) {
+        row.parentElement.insertBefore(row.nextElementSibling, row);
+      }
+      break;
+  }
+});
@@ -0,0 +1,267 @@
+// The service worker makes the site usable on a flaky connection:
+//
+// - Static assets are cached when the service worker is installed.
+// - Pages and API responses are fetched from the network when possible, and
+//   the most recently viewed ones are cached for reading offline.
+// - New categories, ingredients, recipes and versions submitted while
+//   offline are queued in an outbox (stored in IndexedDB) and replayed, in
+//   order, when the connection returns.
+const STATIC_CACHE = "static-{{ version }}";
+const PAGES_CACHE = "pages";
+const PRECACHE_URLS = [
+{%- for url in precache_urls %}
+  "{{ url }}",
+{%- endfor %}
+];
+const BASE_PATH = "{{ base_path }}";
+const OFFLINE_PATH = "{{ offline_path }}";
+const MAX_CACHED_PAGES = 100;
+
+// Requests for live state, or too large to keep, that are never cached or
+// queued.
+const UNCACHED_PREFIXES = [
+  `${BASE_PATH}/admin`,
+  `${BASE_PATH}/api/cooking/`,
+];
+
+// Paths (after the base path) of the changes that are queued while offline:
+// creating categories, ingredients, recipes and versions, through forms or
+// the API. Other requests that aren't GETs, such as parsing ingredient lines
+// or importing recipes, go straight to the network, as replaying them later
+// would be pointless or could add recipes twice.
+const QUEUED_PATHS = [
+  /^\/(api\/)?(categories|ingredients|recipes)\/?$/,
+  /^\/(api\/)?recipes\/\d+\/versions\/?$/,
+];
+
+const DATABASE_NAME = "recipes";
+const OUTBOX_STORE = "outbox";
+const SYNC_TAG = "outbox";
+
+self.addEventListener("install", (event) => {
+  event.waitUntil(
+    caches
+      .open(STATIC_CACHE)
+      .then((cache) => cache.addAll(PRECACHE_URLS))
+      .then(() => self.skipWaiting()),
+  );
+});
+
+self.addEventListener("activate", (event) => {
+  event.waitUntil(
+    caches
+      .keys()
+      .then((names) =>
+        Promise.all(
+          names
+            .filter((name) => name.startsWith("static-"))
+            .filter((name) => name !== STATIC_CACHE)
+            .map((name) => caches.delete(name)),
+        ),
+      )
+      .then(() => self.clients.claim()),
+  );
+});
+
+function openDatabase() {
+  return new Promise((resolve, reject) => {
+    const request = indexedDB.open(DATABASE_NAME, 1);
+    request.onupgradeneeded = () => {
+      request.result.createObjectStore(OUTBOX_STORE, {
+        keyPath: "id",
+        autoIncrement: true,
+      });
+    };
+    request.onsuccess = () => resolve(request.result);
+    request.onerror = () => reject(request.error);
+  });
+}
+
+// Runs `action` with the outbox store, resolving to the result of the
+// request that `action` returns once the transaction completes.
+async function withOutbox(mode, action) {
+  const database = await openDatabase();
+  return new Promise((resolve, reject) => {
+    const transaction = database.transaction(OUTBOX_STORE, mode);
+    const request = action(transaction.objectStore(OUTBOX_STORE));
+    transaction.oncomplete = () => resolve(request.result);
+    transaction.onerror = () => reject(transaction.error);
+  });
+}
+
+async function notifyClients(message) {
+  const clients = await self.clients.matchAll({ includeUncontrolled: true });
+  for (const client of clients) {
+    client.postMessage(message);
+  }
+}
+
+async function notifyPending() {
+  const pending = await withOutbox("readonly", (store) => store.count());
+  await notifyClients({ type: "outbox", pending });
+}
+
+async function queue(request) {
+  const headers = {};
+  for (const name of ["Accept", "Content-Type"]) {
+    if (request.headers.has(name)) {
+      headers[name] = request.headers.get(name);
+    }
+  }
+  // The body is read first, as IndexedDB transactions end when control
+  // returns to the event loop.
+  const entry = {
+    url: request.url,
+    method: request.method,
+    headers,
+    body: await request.arrayBuffer(),
+    queued: Date.now(),
+  };
+  await withOutbox("readwrite", (store) => store.add(entry));
+  await self.registration.sync?.register(SYNC_TAG).catch(() => {});
+  await notifyPending();
+}
+
+// Sends a change, or queues it if the network is unavailable.
+async function sendOrQueue(event) {
+  const copy = event.request.clone();
+  try {
+    return await fetch(event.request);
+  } catch {
+    await queue(copy);
+    if (event.request.mode === "navigate") {
+      const page = await caches.match(OFFLINE_PATH);
+      return new Response(page ? page.body : "Saved offline.", {
+        status: 202,
+        headers: { "Content-Type": "text/html; charset=utf-8" },
+      });
+    }
+    return new Response(JSON.stringify({ queued: true }), {
+      status: 202,
+      headers: { "Content-Type": "application/json" },
+    });
+  }
+}
+
+async function replayOutbox() {
+  const entries = await withOutbox("readonly", (store) => store.getAll());
+  for (const entry of entries) {
+    let response;
+    try {
+      response = await fetch(entry.url, {
+        method: entry.method,
+        headers: entry.headers,
+        body: entry.body,
+        redirect: "manual",
+      });
+    } catch {
+      // Still offline, so leave the rest for later.
+      break;
+    }
+    await withOutbox("readwrite", (store) => store.delete(entry.id));
+    // Form submissions redirect when they succeed.
+    if (!response.ok && response.type !== "opaqueredirect") {
+      await notifyClients({
+        type: "rejected",
+        method: entry.method,
+        url: entry.url,
+        status: response.status,
+      });
+    }
+  }
+  await notifyPending();
+}
+
+let replaying = null;
+
+// Replays the outbox, unless it is already being replayed.
+function replay() {
+  if (!replaying) {
+    replaying = replayOutbox().finally(() => {
+      replaying = null;
+    });
+  }
+  return replaying;
+}
+
+// Keeps only the `MAX_CACHED_PAGES` most recently viewed pages.
+async function cachePage(request, response) {
+  const cache = await caches.open(PAGES_CACHE);
+  await cache.delete(request);
+  await cache.put(request, response);
+  const keys = await cache.keys();
+  await Promise.all(
+    keys
+      .slice(0, Math.max(0, keys.length - MAX_CACHED_PAGES))
+      .map((key) => cache.delete(key)),
+  );
+}
+
+async function networkFirst(event) {
+  try {
+    const response = await fetch(event.request);
+    if (response.ok && response.type === "basic") {
+      event.waitUntil(cachePage(event.request, response.clone()));
+    }
+    return response;
+  } catch (error) {
+    const cached = await caches.match(event.request);
+    if (cached) {
+      return cached;
+    }
+    if (event.request.mode === "navigate") {
+      return caches.match(OFFLINE_PATH);
+    }
+    throw error;
+  }
+}
+
+async function cacheFirst(request) {
+  const cached = await caches.match(request);
+  if (cached) {
+    return cached;
+  }
+  const response = await fetch(request);
+  if (response.ok) {
+    const cache = await caches.open(STATIC_CACHE);
+    await cache.put(request, response.clone());
+  }
+  return response;
+}
+
+self.addEventListener("fetch", (event) => {
+  const url = new URL(event.request.url);
+  if (
+    url.origin !== self.location.origin ||
+    UNCACHED_PREFIXES.some((prefix) => url.pathname.startsWith(prefix))
+  ) {
+    return;
+  }
+
+  if (event.request.method !== "GET") {
+    const path = url.pathname.slice(BASE_PATH.length);
+    if (
+      event.request.method === "POST" &&
+      url.pathname.startsWith(BASE_PATH) &&
+      QUEUED_PATHS.some((pattern) => pattern.test(path))
+    ) {
+      event.respondWith(sendOrQueue(event));
+    }
+  } else if (url.pathname.startsWith(`${BASE_PATH}/static/`)) {
+    event.respondWith(cacheFirst(event.request));
+  } else {
+    event.respondWith(networkFirst(event));
+  }
+});
+
+self.addEventListener("sync", (event) => {
+  if (event.tag === SYNC_TAG) {
+    event.waitUntil(replay());
+  }
+});
+
+self.addEventListener("message", (event) => {
+  if (event.data?.type === "replay") {
+    event.waitUntil(replay());
+  }
+});